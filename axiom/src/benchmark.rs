@@ -0,0 +1,201 @@
+// Semiotic
+// `benchmark` mode: checks that `calculate_optimized` agrees with `calculate_original` on random
+// tick walks of several sizes and times both, so a regression of the optimized path is caught.
+// Sizes up to `SAMPLE_SIZE` also run through `VolatilityChip` in the mock prover, which
+// exercises the chip on other sizes than the one of the Axiom circuit.
+
+use crate::{
+    utils::{calculate_optimized, calculate_original},
+    volatility::VolatilityChip,
+    PRECISION, SAMPLE_SIZE,
+};
+use anyhow::{bail, Context, Result};
+use axiom_sdk::Fr;
+use halo2_base::{
+    gates::circuit::builder::BaseCircuitBuilder, halo2_proofs::dev::MockProver,
+    QuantumCell::Witness,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+pub const COMMAND: &str = "benchmark";
+
+// 1000 and 1001 are an even and an odd size that are not powers of two
+const DEFAULT_SIZES: [usize; 7] = [16, 256, 1000, 1001, 2048, 8192, 65536];
+const RUNS: u32 = 20;
+// Relative to the variance, the two folds round differently
+const TOLERANCE: f64 = 1e-9;
+// Relative to the variance, the circuit rounds every product to its precision
+const CIRCUIT_TOLERANCE: f64 = 1e-6;
+// Largest size run through the mock prover, which holds every cell in memory
+const MAX_CIRCUIT_SIZE: usize = SAMPLE_SIZE;
+// Largest move between consecutive ticks of the random walks
+const MAX_STEP: i64 = 200;
+// Fixed so that a failing size can be reproduced
+const SEED: u64 = 8192;
+
+/// Returns the sizes of `benchmark 512,8192`, or the default ones.
+pub fn sizes(args: &[String]) -> Result<Vec<usize>> {
+    let Some(sizes) = args.get(1) else {
+        return Ok(DEFAULT_SIZES.to_vec());
+    };
+    let sizes = sizes
+        .split(',')
+        .map(|size| {
+            size.trim()
+                .parse()
+                .with_context(|| format!("Invalid benchmark size {:?}", size))
+        })
+        .collect::<Result<Vec<usize>>>()?;
+    if let Some(size) = sizes.iter().find(|size| **size < 2) {
+        bail!("At least 2 ticks are required, got a size of {}", size);
+    }
+    Ok(sizes)
+}
+
+/// Random walk of uniswap ticks starting at 0.
+fn random_ticks<R: Rng>(rng: &mut R, size: usize) -> Vec<f64> {
+    let mut tick = 0i64;
+    (0..size)
+        .map(|_| {
+            tick += rng.gen_range(-MAX_STEP..=MAX_STEP);
+            tick as f64
+        })
+        .collect()
+}
+
+/// Mean duration of `RUNS` calls of `f`, with its last result.
+fn time(f: impl Fn() -> f64) -> (f64, Duration) {
+    let start = Instant::now();
+    let mut result = 0f64;
+    for _ in 0..RUNS {
+        result = std::hint::black_box(f());
+    }
+    (result, start.elapsed() / RUNS)
+}
+
+/// Volatility square of `VolatilityChip` on the ticks, once the mock prover accepts the circuit.
+fn circuit_volatility(ticks: &[f64]) -> Result<f64> {
+    // About 6 cells per tick, the columns are sized by `calculate_params`
+    let k = (ticks.len() * 8)
+        .next_power_of_two()
+        .trailing_zeros()
+        .max(10) as usize;
+    let mut builder = BaseCircuitBuilder::<Fr>::new(false)
+        .use_k(k)
+        .use_lookup_bits(k - 1);
+    let chip: VolatilityChip<Fr, PRECISION> = VolatilityChip::new(&builder);
+    let values = chip.quantize_many(ticks).into_iter().map(Witness);
+    let volatility = chip.volatility(builder.main(0), values);
+    let volatility = chip.dequantization(*volatility.value());
+    builder.calculate_params(Some(9));
+
+    let prover = MockProver::run(k as u32, &builder, vec![])
+        .with_context(|| format!("Mock prover failed for {} ticks", ticks.len()))?;
+    if let Err(failures) = prover.verify() {
+        bail!(
+            "{} constraints are not satisfied for {} ticks, the first is {:?}",
+            failures.len(),
+            ticks.len(),
+            failures.first()
+        );
+    }
+    Ok(volatility)
+}
+
+fn check(name: &str, value: f64, original: f64, tolerance: f64, size: usize) -> Result<()> {
+    let error = (value - original).abs();
+    if error > tolerance * original.abs().max(1f64) {
+        bail!(
+            "{} volatility {} differs from the original {} by {} for {} ticks",
+            name,
+            value,
+            original,
+            error,
+            size
+        );
+    }
+    Ok(())
+}
+
+/// Runs both implementations on random ticks of every size, and the circuit on the smaller
+/// ones, failing on the first disagreement.
+pub fn benchmark(sizes: &[usize]) -> Result<()> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    println!(
+        "\x1b[93m{:>8} {:>14} {:>14} {:>14} {:>12} {:>12}\x1b[0m",
+        "ticks", "original", "optimized", "circuit", "original", "optimized"
+    );
+    for &size in sizes {
+        let ticks = random_ticks(&mut rng, size);
+        let (original, original_time) = time(|| calculate_original(&ticks));
+        let (optimized, optimized_time) = time(|| calculate_optimized(&ticks));
+        let circuit = if size <= MAX_CIRCUIT_SIZE {
+            Some(circuit_volatility(&ticks)?)
+        } else {
+            None
+        };
+        println!(
+            "{:>8} {:>14.4} {:>14.4} {:>14} {:>12?} {:>12?}",
+            size,
+            original,
+            optimized,
+            circuit.map_or("-".to_string(), |circuit| format!("{:.4}", circuit)),
+            original_time,
+            optimized_time
+        );
+        check("Optimized", optimized, original, TOLERANCE, size)?;
+        if let Some(circuit) = circuit {
+            check("Circuit", circuit, original, CIRCUIT_TOLERANCE, size)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn optimized_matches_original_on_random_ticks() {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        for _ in 0..500 {
+            let size = rng.gen_range(2..=2000);
+            // Walks from anywhere in the tick range, with steps up to large jumps
+            let start = rng.gen_range(-887272..=887272) as f64;
+            let max_step = rng.gen_range(1..=10000) as f64;
+            let ticks: Vec<f64> = (0..size)
+                .scan(start, |tick, _| {
+                    *tick += rng.gen_range(-max_step..=max_step).round();
+                    Some(*tick)
+                })
+                .collect();
+            let original = calculate_original(&ticks);
+            check(
+                "Optimized",
+                calculate_optimized(&ticks),
+                original,
+                TOLERANCE,
+                size,
+            )
+            .unwrap();
+        }
+    }
+    #[test]
+    fn circuit_matches_original_on_non_power_of_two_sizes() {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        // An even and an odd size, neither a power of two nor SAMPLE_SIZE
+        for size in [1000, 1001] {
+            let ticks = random_ticks(&mut rng, size);
+            let circuit = circuit_volatility(&ticks).unwrap();
+            check(
+                "Circuit",
+                circuit,
+                calculate_original(&ticks),
+                CIRCUIT_TOLERANCE,
+                size,
+            )
+            .unwrap();
+        }
+    }
+}
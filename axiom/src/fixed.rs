@@ -1,22 +1,62 @@
 // Semiotic
 // Fixed point number conversion and constants for the computation.
 
-use crate::utils::ScalarFieldExt;
-use halo2_base::utils::{biguint_to_fe, BigPrimeField};
+use halo2_base::utils::{biguint_to_fe, BigPrimeField, ScalarField};
 use num_bigint::BigUint;
 use std::ops::Sub;
 
+pub trait ScalarFieldExt {
+    /// Gets the least significant 128 bits of the field element.
+    fn get_lower_128(&self) -> u128;
+}
+
+impl<F: ScalarField> ScalarFieldExt for F {
+    fn get_lower_128(&self) -> u128 {
+        let bytes = self.to_bytes_le();
+        let mut lower_128 = 0u128;
+        for (i, byte) in bytes.into_iter().enumerate().take(16) {
+            lower_128 |= (byte as u128) << (i * 8);
+        }
+        lower_128
+    }
+}
+
 pub(crate) struct FixedPointConstants<F: BigPrimeField, const PRECISION_BITS: u32> {
     pub quantization_scale: F,
     pub bn254_max: F,
     pub negative_point: F,
+    /// Representable values are -max_value < x < max_value, quantized.
+    pub max_value: BigUint,
 }
 
 impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointConstants<F, PRECISION_BITS> {
     pub fn quantization(&self, value: f64) -> F {
+        self.quantize_with_scale(value, self.quantization_scale.get_lower_64() as f64)
+    }
+
+    /// Exact quantization of an integer, `value * 2^PRECISION_BITS`, without going through f64.
+    pub fn quantize_int(&self, value: i64) -> F {
+        let x_q = F::from_u128((value.unsigned_abs() as u128) << PRECISION_BITS);
+        if value < 0 {
+            self.bn254_max - x_q + F::ONE
+        } else {
+            x_q
+        }
+    }
+
+    /// Quantizes a batch of values, reading the scale once.
+    pub fn quantize_many(&self, values: &[f64]) -> Vec<F> {
+        let quantization_scale = self.quantization_scale.get_lower_64() as f64;
+        values
+            .iter()
+            .map(|value| self.quantize_with_scale(*value, quantization_scale))
+            .collect()
+    }
+
+    fn quantize_with_scale(&self, value: f64, quantization_scale: f64) -> F {
         let sign = value.signum();
         let x = value.abs();
-        let x_q = (x * self.quantization_scale.get_lower_64() as f64).round() as u128;
+        let x_q = (x * quantization_scale).round() as u128;
         let x_q_biguint = BigUint::from(x_q).to_bytes_le();
         let mut x_q_bytes_le = [0u8; 64];
         for (idx, val) in x_q_biguint.iter().enumerate() {
@@ -32,20 +72,47 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointConstants<F, PRECISI
     }
 
     pub fn dequantization(&self, value: F) -> f64 {
+        self.dequantize_with_scale(value, self.quantization_scale.get_lower_128())
+    }
+
+    /// Dequantizes a batch of values, reading the scale once.
+    pub fn dequantize_many(&self, values: &[F]) -> Vec<f64> {
+        let quantization_scale = self.quantization_scale.get_lower_128();
+        values
+            .iter()
+            .map(|value| self.dequantize_with_scale(*value, quantization_scale))
+            .collect()
+    }
+
+    fn dequantize_with_scale(&self, value: F, quantization_scale: u128) -> f64 {
         let mut x_mut = value;
         let negative = if value > self.negative_point {
-            x_mut = self.bn254_max - value - F::ONE;
+            x_mut = self.bn254_max - value + F::ONE;
             -1f64
         } else {
             1f64
         };
         let x_u128: u128 = x_mut.get_lower_128();
-        let quantization_scale = self.quantization_scale.get_lower_128();
         let x_int = (x_u128 / quantization_scale) as f64;
         let x_frac = (x_u128 % quantization_scale) as f64 / quantization_scale as f64;
-        let x_deq = negative * (x_int + x_frac);
+        negative * (x_int + x_frac)
+    }
+
+    /// Exclusive bound of the representable magnitudes, the dequantized `max_value`, i.e.
+    /// 2^PRECISION_BITS.
+    pub fn max_value(&self) -> f64 {
+        self.dequantization(biguint_to_fe(&self.max_value))
+    }
+
+    /// Exclusive lower bound of the representable values.
+    pub fn min_value(&self) -> f64 {
+        -self.max_value()
+    }
 
-        x_deq
+    /// Whether `value` is within (min_value, max_value), beyond which it would be read back
+    /// with the wrong sign.
+    pub fn is_in_range(&self, value: f64) -> bool {
+        value.is_finite() && value.abs() < self.max_value()
     }
 }
 
@@ -70,11 +137,93 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> Default
         );
         // -max_value % m = negative_point
         let negative_point = bn254_max - F::from_u128(2u128.pow(PRECISION_BITS * 2 + 1)) + F::ONE;
+        // min_value < x < max_value
+        let max_value = BigUint::from(2u32).pow(PRECISION_BITS * 2);
 
         Self {
             quantization_scale,
             bn254_max,
             negative_point,
+            max_value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PRECISION;
+    use axiom_sdk::Fr;
+
+    #[test]
+    fn range_bounds_are_exclusive() {
+        let constants = FixedPointConstants::<Fr, PRECISION>::default();
+        let max = 2f64.powi(PRECISION as i32);
+        assert_eq!(constants.max_value(), max);
+        assert_eq!(constants.min_value(), -max);
+
+        assert!(constants.is_in_range(0.0));
+        assert!(constants.is_in_range(max - 1.0));
+        assert!(constants.is_in_range(-(max - 1.0)));
+        assert!(!constants.is_in_range(max));
+        assert!(!constants.is_in_range(-max));
+        assert!(!constants.is_in_range(f64::NAN));
+        assert!(!constants.is_in_range(f64::INFINITY));
+
+        // The largest value in range is read back with its sign
+        let value = constants.dequantization(constants.quantization(-(max - 1.0)));
+        assert!(value < 0.0);
+    }
+
+    #[test]
+    fn quantize_int_matches_the_f64_path() {
+        let constants = FixedPointConstants::<Fr, PRECISION>::default();
+        let max = (1i64 << PRECISION) - 1;
+        for tick in [0, 1, -1, 887272, -887272, 123_456_789_012, max, -max] {
+            let quantized = constants.quantize_int(tick);
+            assert_eq!(
+                quantized,
+                constants.quantization(tick as f64),
+                "tick {}",
+                tick
+            );
+            assert_eq!(constants.dequantization(quantized), tick as f64);
+        }
+        assert_eq!(
+            constants.quantize_int(887272),
+            biguint_to_fe::<Fr>(&(BigUint::from(887272u32) << PRECISION))
+        );
+    }
+
+    #[test]
+    fn batches_round_trip() {
+        let constants = FixedPointConstants::<Fr, PRECISION>::default();
+        let values = [
+            0.0,
+            1.5,
+            -1.5,
+            0.1,
+            -0.3,
+            887272.0,
+            -887272.25,
+            1e-9,
+            -123456.789,
+        ];
+        let quantized = constants.quantize_many(&values);
+        let singles: Vec<Fr> = values
+            .iter()
+            .map(|value| constants.quantization(*value))
+            .collect();
+        assert_eq!(quantized, singles);
+
+        let quantum = 2f64.powi(-(PRECISION as i32));
+        for (value, expected) in constants.dequantize_many(&quantized).iter().zip(values) {
+            assert!(
+                (value - expected).abs() <= quantum.max(expected.abs() * f64::EPSILON),
+                "{} differs from {}",
+                value,
+                expected
+            );
         }
     }
 }
@@ -1,7 +1,9 @@
+// The polynomial coefficients are kept as generated, and the results named as upstream.
+#![allow(clippy::excessive_precision, clippy::let_and_return)]
 /// This file is the reference implementation of fixed point decimal arithmetic and input conversion.
 /// Based on the work https://github.com/DCMMC/ZKFixedPointChip/blob/main/src/gadget/fixed_point.rs
 
-use std::{fmt::Debug, iter, ops::Sub, vec};
+use std::{fmt::{self, Debug}, iter, vec};
 use axiom_sdk::axiom_circuit::{axiom_eth::Field, input::raw_input::RawInput};
 use halo2_base::{
     gates::{circuit::builder::BaseCircuitBuilder, GateChip, GateInstructions, RangeChip, RangeInstructions}, utils::{biguint_to_fe, fe_to_biguint, BigPrimeField, ScalarField}, AssignedValue, Context, QuantumCell
@@ -10,93 +12,7 @@ use halo2_base::QuantumCell::{Constant, Existing, Witness};
 use num_bigint::BigUint;
 use num_integer::Integer;
 use serde::{Deserialize, Serialize};
-use crate::utils::ScalarFieldExt;
-
-struct FixedPointConstants<F:BigPrimeField,const PRECISION_BITS: u32> {
-    pub bn254_max: F,
-    pub negative_point: F,
-    pub quantization_scale: F,
-    pub pow_of_two: Vec<F>,
-    pub max_value: BigUint,
-}
-
-impl<F:BigPrimeField,const PRECISION_BITS:u32> FixedPointConstants<F,PRECISION_BITS> {
-
-    pub fn quantization(&self,value:f64) -> F {
-
-        let sign = value.signum();
-        let x = value.abs();
-        let x_q = (x * self.quantization_scale.get_lower_64() as f64).round() as u128;
-        let x_q_biguint = BigUint::from(x_q).to_bytes_le();
-        let mut x_q_bytes_le = [0u8; 64];
-        for (idx, val) in x_q_biguint.iter().enumerate() {
-            x_q_bytes_le[idx] = *val;
-        }
-        let mut x_q_f = F::from_uniform_bytes(&x_q_bytes_le);
-
-        if sign < 0.0 {
-            x_q_f = self.bn254_max - x_q_f + F::ONE;
-        }
-
-        x_q_f
-    
-    }
-
-    pub fn dequantization(&self,value: F) -> f64 {
-        let mut x_mut = value;
-        let negative = if value > self.negative_point {
-            x_mut = self.bn254_max - value - F::ONE;
-            -1f64
-        } else {
-            1f64
-        };
-        let x_u128: u128 = x_mut.get_lower_128();
-        let quantization_scale = self.quantization_scale.get_lower_128();
-        let x_int = (x_u128 / quantization_scale) as f64;
-        let x_frac = (x_u128 % quantization_scale) as f64 / quantization_scale as f64;
-        let x_deq = negative * (x_int + x_frac);
-
-        x_deq
-    }
-}
-
-
-
-impl<F:BigPrimeField,const PRECISION_BITS: u32>  Default for FixedPointConstants<F,PRECISION_BITS> {
-    fn default() -> Self {
-        assert!(PRECISION_BITS <= 63, "support only precision bits <= 63");
-        assert!(PRECISION_BITS >= 32, "support only precision bits >= 32");
-
-        // Simple uniform symmetric quantization scheme which enforces zero point to be exactly 0
-        // to reduce lots of computations.
-        // Quantization: x_q = xS where S is `quantization_scale`
-        // De-quantization: x = x_q / S
-        let quantization_scale = F::from_u128(2u128.pow(PRECISION_BITS as u32));
-        // Becuase BN254 is cyclic, negative number will be denoted as (-x) % m = m - x where m = 2^254,
-        // in this chip, we treat all x > negative_point as a negative numbers.
-        let bn254_max = biguint_to_fe(&BigUint::parse_bytes(
-            &F::MODULUS[2..].bytes().collect::<Vec<u8>>(), 16).unwrap().sub(1u32));
-        // -max_value % m = negative_point
-        let negative_point = bn254_max - F::from_u128(2u128.pow(PRECISION_BITS * 2 + 1)) + F::ONE;
-        // min_value < x < max_value
-        let max_value = BigUint::from(2u32).pow(PRECISION_BITS * 2);
-
-        let mut pow_of_two = Vec::with_capacity(F::NUM_BITS as usize);
-        let two = F::from(2);
-        pow_of_two.push(F::ONE);
-        pow_of_two.push(two);
-        for _ in 2..F::NUM_BITS {
-            pow_of_two.push(two * pow_of_two.last().unwrap());
-        }
-        Self { 
-            bn254_max,
-            negative_point,
-            quantization_scale,
-            pow_of_two,
-            max_value
-        }
-    }
-}
+use crate::fixed::FixedPointConstants;
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct FixedPoint<const PRECISION_BITS: u32>(f64);
@@ -106,6 +22,18 @@ impl<const PRECISION_BITS: u32> FixedPoint<PRECISION_BITS> {
     pub fn new(x: f64) -> Self {
         Self(x)
     }
+
+    /// The quantized field element of the value in hex, as loaded into the circuit.
+    pub fn to_field_hex<F: BigPrimeField>(&self) -> String {
+        let constants = FixedPointConstants::<F,PRECISION_BITS>::default();
+        format!("0x{}", fe_to_biguint(&constants.quantization(self.0)).to_str_radix(16))
+    }
+}
+
+impl<const PRECISION_BITS: u32> fmt::Display for FixedPoint<PRECISION_BITS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 impl<const PRECISION_BITS: u32> Default for FixedPoint<PRECISION_BITS> {
@@ -134,18 +62,92 @@ impl<const PRECISION_BITS:u32, const N: usize> Default for FixedPointVec<PRECISI
     }
 }
 
+impl<const PRECISION_BITS:u32, const N: usize> FixedPointVec<PRECISION_BITS, N> {
+
+    /// Fails unless the vector holds exactly `N` values. Zeros are valid ticks, so a short input is
+    /// not padded with them.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.0.len() != N {
+            anyhow::bail!("FixedPointVec holds {} values, the circuit expects exactly {}", self.0.len(), N);
+        }
+        Ok(())
+    }
+
+    /// One line per value for the first `limit` values, with the decimal value and its
+    /// quantized field element, to diagnose quantization issues.
+    pub fn describe<F: BigPrimeField>(&self, limit: usize) -> String {
+        let mut lines: Vec<String> = self.0.iter().take(limit).enumerate()
+            .map(|(i, x)| format!("[{}] {} = {}", i, x, x.to_field_hex::<F>()))
+            .collect();
+        if self.0.len() > limit {
+            lines.push(format!("... {} more", self.0.len() - limit));
+        }
+        lines.join("\n")
+    }
+}
+
 impl<F: Field,const PRECISION_BITS:u32,const N: usize> RawInput<F> for FixedPointVec<PRECISION_BITS, N> {
     type FEType<T: Copy> = [T; N];
+    /// Panics unless `validate` passes, rather than reading out of bounds or leaving zero ticks.
     fn convert(&self) -> Self::FEType<F> {
+        if let Err(error) = self.validate() {
+            panic!("{}", error);
+        }
         let mut res = [F::ZERO; N];
-        for i in 0..self.0.len() {
-            res[i] = self.0[i].convert();
+        for (res, x) in res.iter_mut().zip(&self.0) {
+            *res = x.convert();
         }
         res
     }
 }
 
 
+/// Precision of a `FixedPointChip` chosen at runtime, one of the supported `PRECISION_BITS`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrecisionConfig {
+    Bits32,
+    Bits40,
+    Bits48,
+    Bits63,
+}
+
+impl PrecisionConfig {
+    /// The supported precisions, increasing, e.g. to sweep accuracy against circuit cost.
+    pub const ALL: [PrecisionConfig; 4] = [Self::Bits32, Self::Bits40, Self::Bits48, Self::Bits63];
+
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|precision| precision.bits() == bits)
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::Bits32 => 32,
+            Self::Bits40 => 40,
+            Self::Bits48 => 48,
+            Self::Bits63 => 63,
+        }
+    }
+}
+
+/// Computation run on a chip of any precision. A closure can't be generic over `PRECISION_BITS`,
+/// so `with_precision` calls this instead.
+pub trait WithPrecision<F: BigPrimeField> {
+    type Output;
+
+    fn call<const PRECISION_BITS: u32>(self, chip: FixedPointChip<F, PRECISION_BITS>) -> Self::Output;
+}
+
+/// Runs `f` on the chip monomorphized for `precision`, built on `gate`. The one match over the
+/// supported precisions, so that callers don't repeat it.
+pub fn with_precision<F: BigPrimeField, W: WithPrecision<F>>(precision: PrecisionConfig, gate: RangeChip<F>, f: W) -> W::Output {
+    match precision {
+        PrecisionConfig::Bits32 => f.call(FixedPointChip::<F, 32>::from_range_chip(gate)),
+        PrecisionConfig::Bits40 => f.call(FixedPointChip::<F, 40>::from_range_chip(gate)),
+        PrecisionConfig::Bits48 => f.call(FixedPointChip::<F, 48>::from_range_chip(gate)),
+        PrecisionConfig::Bits63 => f.call(FixedPointChip::<F, 63>::from_range_chip(gate)),
+    }
+}
+
 /// `PRECISION_BITS` indicates the precision of integer and fractional parts.
 /// For example, `PRECISION_BITS = 32` indicates this chip implements 32.32 fixed point decimal arithmetics.
 /// The valid range of the fixed point decimal is -max_value < x < max_value.
@@ -157,7 +159,12 @@ pub struct FixedPointChip<F: BigPrimeField, const PRECISION_BITS: u32> {
 impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointChip<F, PRECISION_BITS> {
 
     pub fn new(builder: &BaseCircuitBuilder<F>) -> Self {
-        let gate = builder.range_chip();
+        Self::from_range_chip(builder.range_chip())
+    }
+
+    /// Builds the chip on an existing range chip, e.g. the one of a `VolatilityChip`,
+    /// instead of creating another one from the builder.
+    pub fn from_range_chip(gate: RangeChip<F>) -> Self {
         let constants = FixedPointConstants::<F,PRECISION_BITS>::default();
         Self { gate, constants }
     }
@@ -167,13 +174,35 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointChip<F, PRECISION_BI
     }
 
     pub fn quantization(&self, x: f64) -> F {
-        self.constants.quantization(x.into())
+        self.constants.quantization(x)
     }
 
     pub fn dequantization(&self, x: F) -> f64 {
         self.constants.dequantization(x)
     }
 
+    /// Constrains 1 == 0, so that the reduction of an empty slice fails proving, returning zero.
+    fn assert_non_empty(&self, ctx: &mut Context<F>) -> AssignedValue<F> {
+        let one = ctx.load_constant(F::ONE);
+        self.gate().assert_is_const(ctx, &one, &F::ZERO);
+        ctx.load_zero()
+    }
+
+    /// Whether the witness `x` is within (-max_value, max_value), i.e. read back with the right sign.
+    fn is_witness_in_range(&self, x: &F) -> bool {
+        let magnitude = fe_to_biguint(x).min(fe_to_biguint(&-*x));
+        magnitude < self.constants.max_value
+    }
+
+    /// Clamps `a` to the largest representable magnitude, i.e. (-max_value, max_value).
+    /// Only valid while `a` itself is still classified correctly by `is_neg`, i.e. |a| < 2^{2p+1}.
+    fn saturate(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F> {
+        let max = biguint_to_fe::<F>(&(self.constants.max_value.clone() - 1u32));
+        let min = self.constants.bn254_max - max + F::ONE;
+        let upper = self.qmin(ctx, a, Constant(max));
+        self.qmax(ctx, upper, Constant(min))
+    }
+
     fn generate_exp2_poly(&self) -> Vec<QuantumCell<F>> {
         // generated by remez algorithm, poly degree 12, precision bits: 64.28
         let coef: Vec<F> = [
@@ -206,6 +235,15 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointChip<F, PRECISION_BI
         coef.iter().map(|x| Constant(*x)).collect()
     }
 
+    fn generate_erf_poly(&self) -> Vec<QuantumCell<F>> {
+        // Abramowitz-Stegun 7.1.26 coefficients a5..a1, the trailing zero multiplies the sum by t
+        let coef: Vec<F> = [
+            1.061405429, -1.453152027, 1.421413741, -0.284496736, 0.254829592, 0.0
+        ].into_iter().map(|c| self.quantization(c)).collect();
+
+        coef.iter().map(|x| Constant(*x)).collect()
+    }
+
     fn generate_sin_poly(&self) -> Vec<QuantumCell<F>> {
         // generated by lolremez -d 14  -r "0:pi" "sin(x)"
         // Estimated max error: 1.9323057584419826e-15
@@ -239,6 +277,12 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
     where 
         F: BigPrimeField;
 
+    /// `qabs` asserting in-circuit that the magnitude is below `max_value`, for values that may
+    /// sit at the boundary of the range. `qabs` itself is left unchecked for the hot paths.
+    fn qabs_checked(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
     fn is_neg(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
     where 
         F: BigPrimeField;
@@ -260,6 +304,36 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
     where 
         F: BigPrimeField;
 
+    /// 1 if the fractional bits of `a` are zero, 0 otherwise.
+    fn is_integer(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
+    /// Integer part of `a`, truncated toward zero, as a fixed point value.
+    fn qtrunc(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
+    /// `a` rounded to the nearest integer, halves away from zero like `f64::round`.
+    fn qround(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
+    /// `a` rounded to `places` decimal places, halves away from zero, e.g. a fee in basis points
+    /// with `places = 4`. `a * 10^places` must stay within (-max_value, max_value).
+    fn qround_to(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>, places: u32) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
+    /// |a| = q * 2^p + r, the integer and fractional parts of the magnitude, with the sign of `a`.
+    fn split_abs(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> (AssignedValue<F>, AssignedValue<F>, AssignedValue<F>)
+    where 
+        F: BigPrimeField;
+
+    /// Horner evaluation of the polynomial with the coefficients `coef`, highest degree first.
+    /// Nothing bounds the intermediates in-circuit, `x` must be reduced so that they stay within
+    /// (-max_value, max_value): the public functions do so first, `qexp2` on the fractional part,
+    /// `qlog2` on a mantissa in [1, 2) and `qsin` modulo 2π. Debug builds assert it on the witness.
     fn polynomial<QA>(
         &self,
         ctx: &mut Context<F>,
@@ -289,6 +363,9 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
         xor
     }
 
+    /// Sum of `a`, without any range check: over many values, e.g. 8192 quantized ticks at a
+    /// high precision, the sum can leave `(-max_value, max_value)` and later read with the wrong
+    /// sign. See `qsum_checked`.
     fn qsum<Q>(&self, ctx: &mut Context<F>, a: impl IntoIterator<Item = Q>) -> AssignedValue<F>
     where
         Q: Into<QuantumCell<F>>,
@@ -314,6 +391,46 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
         ctx.assign_region_last(cells, (0..len).map(|i| 3 * i as isize))
     }
 
+    /// `qsum` asserting in-circuit that the sum is within `(-max_value, max_value)`. Partial
+    /// sums may leave the range, the field does not wrap, so only the total is checked.
+    fn qsum_checked<Q>(&self, ctx: &mut Context<F>, a: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>;
+
+    /// Arithmetic mean, the `qsum` times the reciprocal of the length quantized at synthesis,
+    /// cheaper than a `qdiv` by the length. Zero for no values.
+    fn qmean<Q>(&self, ctx: &mut Context<F>, xs: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>;
+
+    /// Prefix sums of `a`, assigned in a single region with the same layout as `qsum`.
+    fn qcumsum<Q>(&self, ctx: &mut Context<F>, a: impl IntoIterator<Item = Q>) -> Vec<AssignedValue<F>>
+    where
+        Q: Into<QuantumCell<F>>,
+    {
+        let mut a = a.into_iter().peekable();
+        let start = a.next();
+        if start.is_none() {
+            return vec![];
+        }
+        let start = start.unwrap().into();
+        if a.peek().is_none() {
+            return vec![ctx.assign_region_last([start], [])];
+        }
+        let (len, hi) = a.size_hint();
+        assert_eq!(Some(len), hi);
+
+        let mut sum = *start.value();
+        let cells = iter::once(start).chain(a.flat_map(|a| {
+            let a = a.into();
+            sum += a.value();
+            [a, Constant(F::ONE), Witness(sum)]
+        }));
+        ctx.assign_region(cells, (0..len).map(|i| 3 * i as isize));
+        // the running sums sit every 3 cells of the 3 * len + 1 cell region
+        (0..=len).map(|i| ctx.get(-(3 * (len - i) as isize + 1))).collect()
+    }
+
     fn neg(
         &self,
         ctx: &mut Context<F>,
@@ -351,6 +468,39 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
     ) -> AssignedValue<F>
     where 
         F: BigPrimeField;
+
+    /// Multiplies by a constant, quantized at synthesis. A non-negative `c` leaves the sign
+    /// of the product to `a`, so it is scaled on the unsigned path after an offset, skipping
+    /// the `qabs` of `signed_div_scale`. `c` must be within the range of the chip.
+    fn qmul_const(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        c: f64
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
+    /// Adds a constant, quantized at synthesis. `c` must be within the range of the chip.
+    fn qadd_const(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        c: f64
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
+    /// Linear interpolation a + (b - a) * t, constraining 0 <= t <= 1.
+    fn qlerp(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        b: impl Into<QuantumCell<F>>,
+        t: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
     
     fn qdiv(
         &self,
@@ -360,6 +510,38 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
     ) -> AssignedValue<F>
     where 
         F: BigPrimeField;
+
+    /// Saturating variants clamp an overflowing result to ±max_value instead of wrapping
+    /// into the wrong sign. Each costs a `qmin` and a `qmax` on top of the base operation,
+    /// i.e. two extra `is_neg` range checks and two selects.
+    fn qadd_sat(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        b: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
+    fn qsub_sat(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        b: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
+    /// Saturates products up to 2^{2p+1}; `qmul` itself still rejects products whose
+    /// quotient exceeds 2^{3p}.
+    fn qmul_sat(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        b: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
     
     fn inner_product<QA>(
         &self,
@@ -370,6 +552,13 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
     where 
         F: BigPrimeField, QA: Into<QuantumCell<F>> + Copy;
 
+    /// Sum of squares, equal to `inner_product(a, a)` up to rounding. The squares are
+    /// non-negative, so the raw products are accumulated in a single region and scaled once
+    /// on the unsigned path, instead of a signed `qmul` with its range checks per element.
+    fn qsum_squares<Q>(&self, ctx: &mut Context<F>, a: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>;
+
     fn qmod(
         &self,
         ctx: &mut Context<F>,
@@ -379,6 +568,18 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
     where 
         F: BigPrimeField;
 
+    /// Greatest common divisor of two non-negative integer-valued fixed point numbers.
+    /// Euclid needs at most ~1.44 * PRECISION_BITS steps for integers below 2^PRECISION_BITS,
+    /// so the loop is unrolled 2 * PRECISION_BITS times.
+    fn qgcd(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        b: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
     /// exp2
     fn qexp2(
         &self,
@@ -475,6 +676,24 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
         y
     }
 
+    /// Error function, Abramowitz-Stegun 7.1.26 with |error| < 1.5e-7
+    fn qerf(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
+    /// Standard normal CDF, 0.5 * (1 + erf(a / sqrt(2)))
+    fn qnorm_cdf(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
     fn qmax(
         &self,
         ctx: &mut Context<F>,
@@ -493,6 +712,27 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
     where 
         F: BigPrimeField;
 
+    /// Smallest of `xs`, folding `qmin`. An empty `xs` makes the circuit unsatisfiable.
+    fn qmin_of<Q>(&self, ctx: &mut Context<F>, xs: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>;
+
+    /// Largest of `xs`, folding `qmax`. An empty `xs` makes the circuit unsatisfiable.
+    fn qmax_of<Q>(&self, ctx: &mut Context<F>, xs: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>;
+
+    /// Clamps a into [lo, hi], constraining lo <= hi
+    fn qclamp(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        lo: impl Into<QuantumCell<F>>,
+        hi: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
     fn qlog(
         &self,
         ctx: &mut Context<F>,
@@ -526,6 +766,40 @@ pub trait FixedPointInstructions<F: ScalarField, const PRECISION_BITS: u32> {
     where 
         F: BigPrimeField;
 
+    /// n-th root `exp(log(x) / n)` of a positive `x`, with the accuracy of `qlog` and `qexp`.
+    /// Matches `qsqrt` for `n = 2` and returns `x` for `n = 1`; `x > 0` is asserted in-circuit.
+    fn qnthroot(
+        &self,
+        ctx: &mut Context<F>,
+        x: impl Into<QuantumCell<F>>,
+        n: u32
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField;
+
+    /// Sample variance `sum((x - mean)^2) / (n - 1)` of arbitrary signed values, unlike
+    /// `VolatilityChip` which is specialized to tick deltas. `n >= 2` is asserted in-circuit.
+    fn qvariance<Q>(&self, ctx: &mut Context<F>, xs: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>;
+
+    /// Sample standard deviation, the `qsqrt` of `qvariance`.
+    fn qstddev<Q>(&self, ctx: &mut Context<F>, xs: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>;
+
+    /// Sample variance of the log returns of the prices |amount1| / |amount0| of each swap,
+    /// computing the prices in-circuit instead of trusting pre-extracted ticks. The amounts must
+    /// have the same length and amount0 must not be zero.
+    fn qlog_return_variance<QA, QB>(
+        &self,
+        ctx: &mut Context<F>,
+        amount0: impl IntoIterator<Item = QA>,
+        amount1: impl IntoIterator<Item = QB>
+    ) -> AssignedValue<F>
+    where
+        F: BigPrimeField, QA: Into<QuantumCell<F>>, QB: Into<QuantumCell<F>>;
+
     fn signed_div_scale(
         &self,
         ctx: &mut Context<F>,
@@ -543,7 +817,7 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
     }
 
     fn gate(&self) -> &Self::Gate {
-        &self.gate.gate()
+        self.gate.gate()
     }
 
     fn qadd(
@@ -570,6 +844,45 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         self.gate().sub(ctx, a, b)
     }
 
+    fn qadd_sat(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        b: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let sum = self.qadd(ctx, a, b);
+        self.saturate(ctx, sum)
+    }
+
+    fn qsub_sat(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        b: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let diff = self.qsub(ctx, a, b);
+        self.saturate(ctx, diff)
+    }
+
+    fn qmul_sat(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        b: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let product = self.qmul(ctx, a, b);
+        self.saturate(ctx, product)
+    }
+
     fn qabs(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
     where 
         F: BigPrimeField
@@ -582,14 +895,29 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         a_abs
     }
 
+    fn qabs_checked(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let a_abs = self.qabs(ctx, a);
+        self.range_gate().check_big_less_than_safe(ctx, a_abs, self.constants.max_value.clone());
+
+        a_abs
+    }
+
     fn is_neg(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
     where 
         F: BigPrimeField
     {
         let a = a.into();
-        let a_num_bits = 254;
+        // Field elements span the whole modulus, not only BN254's 254 bits
+        debug_assert!(
+            PRECISION_BITS * 2 + 1 < F::NUM_BITS,
+            "2 * PRECISION_BITS + 1 must be below the {} bits of the field", F::NUM_BITS
+        );
+        let a_num_bits = F::NUM_BITS as usize;
         let (a_shift, _) = self.range_gate().div_mod(
-            ctx, a, BigUint::from(2u32).pow((PRECISION_BITS * 2 + 1)as u32), a_num_bits);
+            ctx, a, BigUint::from(2u32).pow(PRECISION_BITS * 2 + 1), a_num_bits);
         let is_pos = self.gate().is_zero(ctx, a_shift);
         let is_neg = self.gate().not(ctx, is_pos);
 
@@ -623,19 +951,83 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         res
     }
 
-    fn clip(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
+    fn split_abs(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> (AssignedValue<F>, AssignedValue<F>, AssignedValue<F>)
     where 
         F: BigPrimeField
     {
         let a = a.into();
         let sign = self.is_neg(ctx, a);
-        let a_abs = self.qabs(ctx, a);
-        let a_num_bits = 254;
-        let m = self.constants.max_value.clone();
-        // clipped = a % m
-        // TODO (Wentao XIAO) should we just throw panic when overflow?
-        let (_, unsigned_cliped) = self.range_gate().div_mod(ctx, a_abs, m, a_num_bits);
-        let clipped = self.cond_neg(ctx, unsigned_cliped, sign);
+        let a_reverse = self.gate().neg(ctx, a);
+        let a_abs = self.gate().select(ctx, a_reverse, a, sign);
+        // |a| < 2^{2p+1}
+        let a_num_bits = PRECISION_BITS as usize * 2 + 1;
+        let scale = fe_to_biguint(&self.constants.quantization_scale);
+        let (int, frac) = self.range_gate().div_mod(ctx, a_abs, scale, a_num_bits);
+
+        (int, frac, sign)
+    }
+
+    fn is_integer(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let (_, frac, _) = self.split_abs(ctx, a);
+        self.gate().is_zero(ctx, frac)
+    }
+
+    fn qtrunc(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let (int, _, sign) = self.split_abs(ctx, a);
+        let int_abs = self.gate().mul(ctx, int, Constant(self.constants.quantization_scale));
+        self.cond_neg(ctx, int_abs, sign)
+    }
+
+    fn qround(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let (int, frac, sign) = self.split_abs(ctx, a);
+        // frac < 2^p, rounds the magnitude up from one half
+        let half = fe_to_biguint(&self.constants.quantization_scale) / 2u32;
+        let below_half = self.range_gate().is_less_than(ctx, frac, Constant(biguint_to_fe(&half)), PRECISION_BITS as usize + 1);
+        let round_up = self.gate().not(ctx, below_half);
+        let int = self.gate().add(ctx, int, round_up);
+        let int_abs = self.gate().mul(ctx, int, Constant(self.constants.quantization_scale));
+        self.cond_neg(ctx, int_abs, sign)
+    }
+
+    fn qround_to(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>, places: u32) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let factor = 10f64.powi(places as i32);
+        assert!(factor < self.constants.max_value(), "10^{} is out of the fixed point range", places);
+
+        let scaled = self.qmul_const(ctx, a, factor);
+        let rounded = self.qround(ctx, scaled);
+        // Dividing the integer by 10^places exactly, rather than multiplying by the inexact 10^-places
+        let sign = self.is_neg(ctx, rounded);
+        let magnitude = self.qabs(ctx, rounded);
+        let a_num_bits = PRECISION_BITS as usize * 2 + 1;
+        let (res_abs, _) = self.range_gate().div_mod(ctx, magnitude, BigUint::from(10u32).pow(places), a_num_bits);
+        self.cond_neg(ctx, res_abs, sign)
+    }
+
+    fn clip(&self, ctx: &mut Context<F>, a: impl Into<QuantumCell<F>>) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let a = a.into();
+        let sign = self.is_neg(ctx, a);
+        let a_abs = self.qabs(ctx, a);
+        let a_num_bits = 254;
+        let m = self.constants.max_value.clone();
+        // clipped = a % m
+        // TODO (Wentao XIAO) should we just throw panic when overflow?
+        let (_, unsigned_cliped) = self.range_gate().div_mod(ctx, a_abs, m, a_num_bits);
+        let clipped = self.cond_neg(ctx, unsigned_cliped, sign);
 
         clipped
     }
@@ -657,6 +1049,75 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
 
         res
     }
+
+    fn qmul_const(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        c: f64
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let ac = self.gate().mul(ctx, a, Constant(self.quantization(c)));
+        if c < 0.0 {
+            let (res, _) = self.signed_div_scale(ctx, ac);
+            return res;
+        }
+
+        // |ac| < 2^{4p}, shifting by 2^{4p}, a multiple of b = 2^p, makes it non-negative
+        // with the same remainder, so q = floor(ac / b) + 2^{3p}
+        let offset = BigUint::from(2u32).pow(PRECISION_BITS * 4);
+        let shifted = self.gate().add(ctx, ac, Constant(biguint_to_fe(&offset)));
+        let b = fe_to_biguint(&self.constants.quantization_scale);
+        let (q, r) = fe_to_biguint(shifted.value()).div_mod_floor(&b);
+        ctx.assign_region(
+            [Witness(biguint_to_fe(&r)), Constant(biguint_to_fe(&b)), Witness(biguint_to_fe(&q)), Existing(shifted)],
+            [0]
+        );
+        let rem = ctx.get(-4);
+        let div = ctx.get(-2);
+
+        self.range_gate().check_big_less_than_safe(ctx, rem, b);
+        let bound = BigUint::from(2u32).pow(PRECISION_BITS * 3 + 1);
+        self.range_gate().check_big_less_than_safe(ctx, div, bound);
+        let unshift = BigUint::from(2u32).pow(PRECISION_BITS * 3);
+
+        self.gate().sub(ctx, div, Constant(biguint_to_fe(&unshift)))
+    }
+
+    fn qadd_const(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        c: f64
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        self.gate().add(ctx, a, Constant(self.quantization(c)))
+    }
+
+    fn qlerp(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        b: impl Into<QuantumCell<F>>,
+        t: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let a = a.into();
+        let t = self.gate().add(ctx, t, Constant(F::ZERO));
+        // t < 2^p + 1 on the unsigned path also rejects negative t, which wrap to large elements
+        let one_bound = fe_to_biguint(&self.constants.quantization_scale) + 1u32;
+        self.range_gate().check_big_less_than_safe(ctx, t, one_bound);
+
+        let diff = self.qsub(ctx, b, a);
+        let step = self.qmul(ctx, diff, t);
+        self.qadd(ctx, a, step)
+    }
     
     fn qmod(
         &self,
@@ -685,6 +1146,40 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         res
     }
 
+    fn qgcd(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        b: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let a = a.into();
+        let b = b.into();
+        let one = Constant(self.constants.quantization_scale);
+        // both inputs must be non-negative integers
+        for x in [a, b] {
+            let x_sign = self.is_neg(ctx, x);
+            self.gate().assert_is_const(ctx, &x_sign, &F::ZERO);
+            let x_frac = self.qmod(ctx, x, one);
+            self.gate().assert_is_const(ctx, &x_frac, &F::ZERO);
+        }
+
+        let mut x = self.gate().add(ctx, a, Constant(F::ZERO));
+        let mut y = self.gate().add(ctx, b, Constant(F::ZERO));
+        for _ in 0..2 * PRECISION_BITS {
+            // once y hits zero, x holds the gcd and both stay fixed
+            let y_is_zero = self.gate().is_zero(ctx, y);
+            let divisor = self.gate().select(ctx, one, y, y_is_zero);
+            let r = self.qmod(ctx, x, divisor);
+            x = self.gate().select(ctx, x, y, y_is_zero);
+            y = self.gate().select(ctx, Constant(F::ZERO), r, y_is_zero);
+        }
+
+        x
+    }
+
     fn qdiv(
         &self,
         ctx: &mut Context<F>,
@@ -732,11 +1227,21 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         let last_idx_coef = coef_iter.len() - 1;
         let mut result: AssignedValue<F> = self.qadd(ctx, x, Constant(F::ZERO));
         for (idx, c) in coef_iter.into_iter().enumerate() {
-            let last_y = *intermediates.get(intermediates.len() - 1).unwrap();
+            let last_y = *intermediates.last().unwrap();
             let y_add = self.qadd(ctx, last_y, c);
+            debug_assert!(
+                self.is_witness_in_range(y_add.value()),
+                "polynomial intermediate {} overflows at degree {}, the argument must be reduced first",
+                self.dequantization(*y_add.value()), last_idx_coef - idx
+            );
             intermediates.push(Existing(y_add));
             if idx < last_idx_coef {
                 let y = self.qmul(ctx, x, Existing(y_add));
+                debug_assert!(
+                    self.is_witness_in_range(y.value()),
+                    "polynomial intermediate {} overflows at degree {}, the argument must be reduced first",
+                    self.dequantization(*y.value()), last_idx_coef - idx
+                );
                 intermediates.push(Existing(y));
             } else {
                 result = y_add;
@@ -783,7 +1288,7 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         let (int_part, frac_part) = self.range_gate().div_mod(
             ctx, Existing(a_abs), shift, num_bits);
         // int_part must be small as large number leads to overflow.
-        let pow_of_two: Vec<QuantumCell<F>> = self.constants.pow_of_two.iter().map(|x| Constant(*x)).collect();
+        let pow_of_two: Vec<QuantumCell<F>> = self.gate().pow_of_two().iter().map(|x| Constant(*x)).collect();
         let int_part_pow2 = self.gate().select_from_idx(
             ctx, pow_of_two, int_part);
         let coef = self.generate_exp2_poly();
@@ -942,7 +1447,7 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         let b: Vec<QA> = b.into_iter().collect();
         assert!(a.len() == b.len());
         let mut res = self.qadd(ctx, Constant(F::ZERO), Constant(F::ZERO));
-        for (ai, bi) in a.iter().zip(b.iter()).into_iter() {
+        for (ai, bi) in a.iter().zip(b.iter()) {
             let ai_bi = self.qmul(ctx, *ai, *bi);
             res = self.qadd(ctx, res, ai_bi);
         }
@@ -950,6 +1455,57 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         res
     }
 
+    fn qsum_checked<Q>(&self, ctx: &mut Context<F>, a: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>
+    {
+        let sum = self.qsum(ctx, a);
+        // -max_value < sum < max_value iff 0 <= sum + max_value - 1 < 2 * max_value - 1
+        let max_value = &self.constants.max_value;
+        let shift = max_value - BigUint::from(1u32);
+        let shifted = self.gate().add(ctx, sum, Constant(biguint_to_fe(&shift)));
+        self.range_gate().check_big_less_than_safe(ctx, shifted, max_value * 2u32 - 1u32);
+
+        sum
+    }
+
+    fn qmean<Q>(&self, ctx: &mut Context<F>, xs: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>
+    {
+        let xs: Vec<QuantumCell<F>> = xs.into_iter().map(Into::into).collect();
+        if xs.is_empty() {
+            return ctx.load_zero();
+        }
+        let n = xs.len() as f64;
+        let sum = self.qsum(ctx, xs);
+        self.qmul_const(ctx, sum, 1.0 / n)
+    }
+
+    fn qsum_squares<Q>(&self, ctx: &mut Context<F>, a: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>
+    {
+        let a: Vec<QuantumCell<F>> = a.into_iter().map(Into::into).collect();
+        let sq_sum = self.gate().inner_product(ctx, a.clone(), a);
+
+        // sq_sum = b * q + r, r in [0, b), no sign to handle
+        let b = fe_to_biguint(&self.constants.quantization_scale);
+        let (q, r) = fe_to_biguint(sq_sum.value()).div_mod_floor(&b);
+        ctx.assign_region(
+            [Witness(biguint_to_fe(&r)), Constant(biguint_to_fe(&b)), Witness(biguint_to_fe(&q)), Existing(sq_sum)],
+            [0]
+        );
+        let rem = ctx.get(-4);
+        let div = ctx.get(-2);
+
+        self.range_gate().check_big_less_than_safe(ctx, rem, b);
+        let bound = BigUint::from(2u32).pow(PRECISION_BITS * 3);
+        self.range_gate().check_big_less_than_safe(ctx, div, bound);
+
+        div
+    }
+
     fn qexp(
         &self,
         ctx: &mut Context<F>,
@@ -1004,6 +1560,54 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         y
     }
 
+    fn qerf(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        // erf(x) = 1 - t * P(t) * e^(-x^2), t = 1 / (1 + p * x) for x >= 0 and erf(-x) = -erf(x)
+        let a = a.into();
+        let a_sign = self.is_neg(ctx, a);
+        let a_abs = self.qabs(ctx, a);
+        // erf(6) = 1 - 2e-17, clamping keeps x^2 and the exponent in range
+        let x = self.qmin(ctx, a_abs, Constant(self.quantization(6.0)));
+        let one = Constant(self.quantization(1.0));
+        let px = self.qmul(ctx, x, Constant(self.quantization(0.3275911)));
+        let denom = self.qadd(ctx, one, px);
+        let t = self.qdiv(ctx, one, denom);
+        let coef = self.generate_erf_poly();
+        let tail_poly = self.polynomial(ctx, t, coef);
+        let x2 = self.qmul(ctx, x, x);
+        let neg_x2 = self.neg(ctx, x2);
+        let exp_neg_x2 = self.qexp(ctx, neg_x2);
+        let tail = self.qmul(ctx, tail_poly, exp_neg_x2);
+        let erf_abs = self.qsub(ctx, one, tail);
+        let y = self.cond_neg(ctx, erf_abs, a_sign);
+
+        y
+    }
+
+    fn qnorm_cdf(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let frac_1_sqrt_2 = Constant(self.quantization(std::f64::consts::FRAC_1_SQRT_2));
+        let half = Constant(self.quantization(0.5));
+        let x = self.qmul(ctx, a, frac_1_sqrt_2);
+        let erf_x = self.qerf(ctx, x);
+        let half_erf_x = self.qmul(ctx, erf_x, half);
+        let y = self.qadd(ctx, half_erf_x, half);
+
+        y
+    }
+
     fn qmax(
         &self,
         ctx: &mut Context<F>,
@@ -1040,6 +1644,51 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         y
     }
 
+    fn qmin_of<Q>(&self, ctx: &mut Context<F>, xs: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>
+    {
+        let mut xs = xs.into_iter();
+        let Some(first) = xs.next() else {
+            return self.assert_non_empty(ctx);
+        };
+        let first = self.gate().add(ctx, first, Constant(F::ZERO));
+        xs.fold(first, |min, x| self.qmin(ctx, min, x))
+    }
+
+    fn qmax_of<Q>(&self, ctx: &mut Context<F>, xs: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>
+    {
+        let mut xs = xs.into_iter();
+        let Some(first) = xs.next() else {
+            return self.assert_non_empty(ctx);
+        };
+        let first = self.gate().add(ctx, first, Constant(F::ZERO));
+        xs.fold(first, |max, x| self.qmax(ctx, max, x))
+    }
+
+    fn qclamp(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl Into<QuantumCell<F>>,
+        lo: impl Into<QuantumCell<F>>,
+        hi: impl Into<QuantumCell<F>>
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        let lo = lo.into();
+        let hi = hi.into();
+        let hml = self.qsub(ctx, hi, lo);
+        let sign_hml = self.is_neg(ctx, hml);
+        self.gate().assert_is_const(ctx, &sign_hml, &F::ZERO);
+        let a_hi = self.qmin(ctx, a, hi);
+        let y = self.qmax(ctx, a_hi, lo);
+
+        y
+    }
+
     fn qlog(
         &self,
         ctx: &mut Context<F>,
@@ -1068,6 +1717,83 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
         self.qpow(ctx, x, half)
     }
 
+    fn qnthroot(
+        &self,
+        ctx: &mut Context<F>,
+        x: impl Into<QuantumCell<F>>,
+        n: u32
+    ) -> AssignedValue<F>
+    where 
+        F: BigPrimeField
+    {
+        assert!(n >= 1, "root degree must be at least 1");
+        let x = self.gate().add(ctx, x, Constant(F::ZERO));
+        let x_neg = self.is_neg(ctx, x);
+        let x_zero = self.gate().is_zero(ctx, x);
+        let x_not_pos = self.gate().or(ctx, x_neg, x_zero);
+        self.gate().assert_is_const(ctx, &x_not_pos, &F::ZERO);
+        if n == 1 {
+            return x;
+        }
+
+        let logx = self.qlog(ctx, x);
+        let logx_n = self.qmul_const(ctx, logx, 1.0 / n as f64);
+        self.qexp(ctx, logx_n)
+    }
+
+    fn qvariance<Q>(&self, ctx: &mut Context<F>, xs: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>
+    {
+        let xs: Vec<QuantumCell<F>> = xs.into_iter().map(Into::into).collect();
+        let n = xs.len();
+        // 1 < n, unsatisfiable for fewer than two values
+        let one = ctx.load_constant(F::ONE);
+        self.range_gate().check_less_than_safe(ctx, one, n as u64);
+        if n < 2 {
+            return ctx.load_zero();
+        }
+
+        let sum = self.qsum(ctx, xs.clone());
+        let mean = self.qdiv(ctx, sum, Constant(self.quantization(n as f64)));
+        let deviations: Vec<AssignedValue<F>> = xs.into_iter().map(|x| self.qsub(ctx, x, mean)).collect();
+        let sq_sum = self.qsum_squares(ctx, deviations);
+
+        self.qdiv(ctx, sq_sum, Constant(self.quantization((n - 1) as f64)))
+    }
+
+    fn qstddev<Q>(&self, ctx: &mut Context<F>, xs: impl IntoIterator<Item = Q>) -> AssignedValue<F>
+    where
+        F: BigPrimeField, Q: Into<QuantumCell<F>>
+    {
+        let variance = self.qvariance(ctx, xs);
+        self.qsqrt(ctx, variance)
+    }
+
+    fn qlog_return_variance<QA, QB>(
+        &self,
+        ctx: &mut Context<F>,
+        amount0: impl IntoIterator<Item = QA>,
+        amount1: impl IntoIterator<Item = QB>
+    ) -> AssignedValue<F>
+    where
+        F: BigPrimeField, QA: Into<QuantumCell<F>>, QB: Into<QuantumCell<F>>
+    {
+        let amount0: Vec<QuantumCell<F>> = amount0.into_iter().map(Into::into).collect();
+        let amount1: Vec<QuantumCell<F>> = amount1.into_iter().map(Into::into).collect();
+        assert_eq!(amount0.len(), amount1.len(), "amount0 and amount1 must have the same length");
+
+        let log_prices: Vec<AssignedValue<F>> = amount0.into_iter().zip(amount1).map(|(a0, a1)| {
+            let a0 = self.qabs(ctx, a0);
+            let a1 = self.qabs(ctx, a1);
+            let price = self.qdiv(ctx, a1, a0);
+            self.qlog(ctx, price)
+        }).collect();
+        let returns: Vec<AssignedValue<F>> = log_prices.windows(2).map(|pair| self.qsub(ctx, pair[1], pair[0])).collect();
+
+        self.qvariance(ctx, returns)
+    }
+
     fn signed_div_scale(
         &self,
         ctx: &mut Context<F>,
@@ -1099,7 +1825,7 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
 
         self.range_gate().check_big_less_than_safe(ctx, rem, b);
         // a < 2^{4p}, b = 2^p, so |q| < 2^{3p}
-        let bound = BigUint::from(2u32).pow(PRECISION_BITS * 3 as u32);
+        let bound = BigUint::from(2u32).pow(PRECISION_BITS * 3);
         let div_abs = self.qabs(ctx, div);
         self.range_gate().check_big_less_than_safe(ctx, div_abs, bound);
 
@@ -1109,3 +1835,409 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> FixedPointInstructions<F, PREC
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PRECISION;
+    use axiom_sdk::Fr;
+    use halo2_base::halo2_proofs::dev::MockProver;
+
+    const K: usize = 14;
+
+    type Chip = FixedPointChip<Fr, PRECISION>;
+
+    /// Runs `f` on a chip in the mock prover and returns the dequantized outputs, whether the
+    /// constraints hold or not.
+    fn try_mock(f: impl FnOnce(&Chip, &mut Context<Fr>) -> Vec<AssignedValue<Fr>>) -> (Vec<f64>, bool) {
+        let mut builder = BaseCircuitBuilder::<Fr>::new(false).use_k(K).use_lookup_bits(K - 1);
+        let chip = Chip::new(&builder);
+        let outputs = f(&chip, builder.main(0));
+        let outputs = outputs.iter().map(|x| chip.dequantization(*x.value())).collect();
+        builder.calculate_params(Some(9));
+        let satisfied = MockProver::run(K as u32, &builder, vec![]).unwrap().verify().is_ok();
+        (outputs, satisfied)
+    }
+
+    /// Dequantized outputs of `f`, once the mock prover accepts the circuit.
+    fn mock(f: impl FnOnce(&Chip, &mut Context<Fr>) -> Vec<AssignedValue<Fr>>) -> Vec<f64> {
+        let (outputs, satisfied) = try_mock(f);
+        assert!(satisfied, "constraints are not satisfied");
+        outputs
+    }
+
+    fn witness(chip: &Chip, x: f64) -> QuantumCell<Fr> {
+        Witness(chip.quantization(x))
+    }
+
+    fn witnesses(chip: &Chip, xs: &[f64]) -> Vec<QuantumCell<Fr>> {
+        xs.iter().map(|x| witness(chip, *x)).collect()
+    }
+
+    fn assert_close(value: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (value - expected).abs() <= tolerance * expected.abs().max(1.0),
+            "{} differs from the expected {}", value, expected
+        );
+    }
+
+    #[test]
+    fn saturating_ops_clamp_to_max_value() {
+        let max = FixedPointConstants::<Fr, PRECISION>::default().max_value();
+        let big = 1.5 * 2f64.powi(PRECISION as i32 - 1);
+        let outputs = mock(|chip, ctx| vec![
+            chip.qadd_sat(ctx, witness(chip, 1.25), witness(chip, -3.5)),
+            chip.qsub_sat(ctx, witness(chip, 1.25), witness(chip, -3.5)),
+            chip.qmul_sat(ctx, witness(chip, 1.25), witness(chip, -3.5)),
+            chip.qadd_sat(ctx, witness(chip, big), witness(chip, big)),
+            chip.qsub_sat(ctx, witness(chip, -big), witness(chip, big)),
+            chip.qmul_sat(ctx, witness(chip, 2f64.powi(24)), witness(chip, -1.5 * 2f64.powi(24))),
+        ]);
+        for (output, expected) in outputs.iter().zip([1.25 + -3.5, 1.25 - -3.5, 1.25 * -3.5]) {
+            assert_close(*output, expected, 1e-12);
+        }
+        assert_close(outputs[3], max, 1e-12);
+        assert_close(outputs[4], -max, 1e-12);
+        assert_close(outputs[5], -max, 1e-12);
+    }
+
+    fn gcd(a: u64, b: u64) -> u64 {
+        if b == 0 { a } else { gcd(b, a % b) }
+    }
+
+    #[test]
+    fn qgcd_matches_euclid() {
+        let pairs = [(12u64, 18u64), (18, 12), (0, 5), (5, 0), (35, 64), (1 << 20, 3 << 18)];
+        let outputs = mock(|chip, ctx| pairs.iter()
+            .map(|(a, b)| chip.qgcd(ctx, witness(chip, *a as f64), witness(chip, *b as f64)))
+            .collect());
+        for ((a, b), output) in pairs.iter().zip(outputs) {
+            assert_eq!(output, gcd(*a, *b) as f64);
+        }
+    }
+
+    #[test]
+    fn qgcd_rejects_fractions() {
+        let (_, satisfied) = try_mock(|chip, ctx| vec![chip.qgcd(ctx, witness(chip, 2.5), witness(chip, 5.0))]);
+        assert!(!satisfied);
+    }
+
+    #[test]
+    fn qerf_and_qnorm_cdf_match_reference_values() {
+        // erf and the standard normal CDF to 10 digits
+        let erf = [(0.0, 0.0), (0.5, 0.5204998778), (1.0, 0.8427007929), (-1.0, -0.8427007929), (2.5, 0.9995930480)];
+        let cdf = [(0.0, 0.5), (1.0, 0.8413447461), (-1.0, 0.1586552539), (1.96, 0.9750021049)];
+        let outputs = mock(|chip, ctx| {
+            let mut outputs: Vec<_> = erf.iter().map(|(x, _)| chip.qerf(ctx, witness(chip, *x))).collect();
+            outputs.extend(cdf.iter().map(|(x, _)| chip.qnorm_cdf(ctx, witness(chip, *x))));
+            outputs
+        });
+        // Abramowitz-Stegun 7.1.26 is within 1.5e-7 of erf
+        for (output, (_, expected)) in outputs.iter().zip(erf.iter().chain(&cdf)) {
+            assert!((output - expected).abs() < 2e-7, "{} differs from {}", output, expected);
+        }
+    }
+
+    #[test]
+    fn qcumsum_matches_prefix_sums() {
+        let xs = [1.5, -2.25, 0.0, 10.125, -0.5];
+        let outputs = mock(|chip, ctx| {
+            let mut sums = chip.qcumsum(ctx, witnesses(chip, &xs));
+            sums.push(chip.qsum(ctx, witnesses(chip, &xs)));
+            sums
+        });
+        let expected: Vec<f64> = xs.iter().scan(0.0, |sum, x| { *sum += x; Some(*sum) }).collect();
+        assert_eq!(outputs.len(), xs.len() + 1);
+        for (output, expected) in outputs.iter().zip(&expected) {
+            assert_close(*output, *expected, 1e-12);
+        }
+        assert_eq!(outputs[xs.len() - 1], outputs[xs.len()]);
+    }
+
+    #[test]
+    fn is_neg_holds_at_the_edges_of_the_range() {
+        let max = FixedPointConstants::<Fr, PRECISION>::default().max_value();
+        let xs = [0.0, 1.0, -1.0, max / 2.0, -max / 2.0, max - 1.0, -(max - 1.0)];
+        let outputs = mock(|chip, ctx| xs.iter().map(|x| chip.is_neg(ctx, witness(chip, *x))).collect());
+        let quantum = 2f64.powi(-(PRECISION as i32));
+        for (output, x) in outputs.iter().zip(xs) {
+            // is_neg is 0 or 1, i.e. 0 or one quantum once dequantized
+            assert_eq!(*output, if x < 0.0 { quantum } else { 0.0 }, "is_neg({})", x);
+        }
+    }
+
+    #[test]
+    fn qsum_squares_matches_the_inner_product() {
+        let xs = [1.5, -2.25, 0.0, 10.125, -0.5, 3.0e3];
+        let outputs = mock(|chip, ctx| vec![
+            chip.qsum_squares(ctx, witnesses(chip, &xs)),
+            chip.inner_product(ctx, witnesses(chip, &xs), witnesses(chip, &xs)),
+        ]);
+        let expected: f64 = xs.iter().map(|x| x * x).sum();
+        assert_close(outputs[0], expected, 1e-12);
+        assert_close(outputs[1], expected, 1e-12);
+    }
+
+    /// Sample variance, two-pass.
+    fn sample_variance(xs: &[f64]) -> f64 {
+        let mean = xs.iter().sum::<f64>() / xs.len() as f64;
+        xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (xs.len() - 1) as f64
+    }
+
+    #[test]
+    fn qvariance_and_qstddev_match_the_sample_statistics() {
+        let xs = [1.5, -2.25, 0.0, 10.125, -0.5, 7.0];
+        let outputs = mock(|chip, ctx| vec![
+            chip.qvariance(ctx, witnesses(chip, &xs)),
+            chip.qstddev(ctx, witnesses(chip, &xs)),
+        ]);
+        let variance = sample_variance(&xs);
+        assert_close(outputs[0], variance, 1e-9);
+        assert_close(outputs[1], variance.sqrt(), 1e-6);
+    }
+
+    #[test]
+    fn qvariance_rejects_a_single_value() {
+        let (_, satisfied) = try_mock(|chip, ctx| vec![chip.qvariance(ctx, witnesses(chip, &[1.0]))]);
+        assert!(!satisfied);
+    }
+
+    #[test]
+    fn const_ops_match_f64() {
+        let cases = [(1.5, 0.1), (-2.25, 3.0), (10.125, 0.0), (-0.5, 1e-6)];
+        let outputs = mock(|chip, ctx| cases.iter()
+            .flat_map(|&(a, c)| {
+                let product = chip.qmul_const(ctx, witness(chip, a), c);
+                let sum = chip.qadd_const(ctx, witness(chip, a), -c);
+                [product, sum]
+            })
+            .collect());
+        for (outputs, (a, c)) in outputs.chunks(2).zip(cases) {
+            assert_close(outputs[0], a * c, 1e-12);
+            assert_close(outputs[1], a - c, 1e-12);
+        }
+    }
+
+    #[test]
+    fn qnthroot_matches_powf() {
+        let cases = [(27.0, 3u32), (2.0, 2), (1e6, 6), (0.125, 3), (5.5, 1)];
+        let outputs = mock(|chip, ctx| {
+            let mut roots: Vec<_> = cases.iter().map(|(x, n)| chip.qnthroot(ctx, witness(chip, *x), *n)).collect();
+            roots.push(chip.qsqrt(ctx, witness(chip, 2.0)));
+            roots
+        });
+        for (output, (x, n)) in outputs.iter().zip(cases) {
+            assert_close(*output, x.powf(1.0 / n as f64), 1e-6);
+        }
+        assert_close(outputs[1], outputs[cases.len()], 1e-6);
+    }
+
+    #[test]
+    fn qnthroot_rejects_non_positive_values() {
+        let (_, satisfied) = try_mock(|chip, ctx| vec![chip.qnthroot(ctx, witness(chip, -8.0), 3)]);
+        assert!(!satisfied);
+    }
+
+    #[test]
+    fn qsum_checked_rejects_a_sum_out_of_range() {
+        let half = FixedPointConstants::<Fr, PRECISION>::default().max_value() / 2.0;
+        let (outputs, satisfied) = try_mock(|chip, ctx| vec![chip.qsum_checked(ctx, witnesses(chip, &[1.5, -2.25, 10.0]))]);
+        assert!(satisfied);
+        assert_close(outputs[0], 9.25, 1e-12);
+        // Partial sums may leave the range as long as the total is back in it
+        let (_, satisfied) = try_mock(|chip, ctx| vec![chip.qsum_checked(ctx, witnesses(chip, &[half, half, -half]))]);
+        assert!(satisfied);
+        let (_, satisfied) = try_mock(|chip, ctx| vec![chip.qsum_checked(ctx, witnesses(chip, &[half, half, 1.0]))]);
+        assert!(!satisfied);
+    }
+
+    #[test]
+    fn fixed_point_prints_its_value_and_field_element() {
+        let x = FixedPoint::<PRECISION>::new(1.5);
+        assert_eq!(x.to_string(), "1.5");
+        // 1.5 * 2^48
+        assert_eq!(x.to_field_hex::<Fr>(), "0x1800000000000");
+        let xs: FixedPointVec<PRECISION, 3> = serde_json::from_str("[1.5, 0.0, 2.0]").unwrap();
+        assert_eq!(xs.describe::<Fr>(2), "[0] 1.5 = 0x1800000000000\n[1] 0 = 0x0\n... 1 more");
+    }
+
+    #[test]
+    fn qabs_checked_rejects_max_value() {
+        let max = FixedPointConstants::<Fr, PRECISION>::default().max_value();
+        let (outputs, satisfied) = try_mock(|chip, ctx| vec![
+            chip.qabs_checked(ctx, witness(chip, -2.5)),
+            chip.qabs_checked(ctx, witness(chip, max - 1.0)),
+        ]);
+        assert!(satisfied);
+        assert_close(outputs[0], 2.5, 1e-12);
+        assert_eq!(outputs[1], max - 1.0);
+        for x in [max, -max] {
+            let (_, satisfied) = try_mock(|chip, ctx| vec![chip.qabs_checked(ctx, witness(chip, x))]);
+            assert!(!satisfied, "|{}| is not below max_value", x);
+        }
+    }
+
+    #[test]
+    fn is_integer_and_qtrunc_match_f64() {
+        let xs = [0.0, 3.0, -3.0, 2.75, -2.75, 0.5, -0.5, 1e9 + 0.25];
+        let outputs = mock(|chip, ctx| xs.iter().flat_map(|x| {
+            let is_integer = chip.is_integer(ctx, witness(chip, *x));
+            let trunc = chip.qtrunc(ctx, witness(chip, *x));
+            [is_integer, trunc]
+        }).collect());
+        let quantum = 2f64.powi(-(PRECISION as i32));
+        for (outputs, x) in outputs.chunks(2).zip(xs) {
+            assert_eq!(outputs[0], if x.fract() == 0.0 { quantum } else { 0.0 }, "is_integer({})", x);
+            assert_close(outputs[1], x.trunc(), 1e-12);
+        }
+    }
+
+    #[test]
+    fn qlerp_matches_f64() {
+        let cases = [(1.0, 3.0, 0.0), (1.0, 3.0, 1.0), (1.0, 3.0, 0.25), (-2.5, 4.0, 0.5), (4.0, -2.5, 0.75)];
+        let outputs = mock(|chip, ctx| cases.iter()
+            .map(|(a, b, t)| chip.qlerp(ctx, witness(chip, *a), witness(chip, *b), witness(chip, *t)))
+            .collect());
+        for (output, (a, b, t)) in outputs.iter().zip(cases) {
+            assert_close(*output, a + (b - a) * t, 1e-12);
+        }
+    }
+
+    #[test]
+    fn qlerp_rejects_t_outside_the_unit_interval() {
+        for t in [-0.25, 1.25] {
+            let (_, satisfied) = try_mock(|chip, ctx| vec![chip.qlerp(ctx, witness(chip, 1.0), witness(chip, 3.0), witness(chip, t))]);
+            assert!(!satisfied, "t = {}", t);
+        }
+    }
+
+    #[test]
+    fn qclamp_matches_f64() {
+        // Below, within and above [lo, hi], then the degenerate lo == hi
+        let cases = [(-3.5, -1.0, 2.0), (0.75, -1.0, 2.0), (5.25, -1.0, 2.0), (-1.0, -1.0, 2.0), (2.0, -1.0, 2.0), (4.0, 1.5, 1.5), (-4.0, 1.5, 1.5)];
+        let outputs = mock(|chip, ctx| cases.iter()
+            .map(|(a, lo, hi)| chip.qclamp(ctx, witness(chip, *a), witness(chip, *lo), witness(chip, *hi)))
+            .collect());
+        for (output, (a, lo, hi)) in outputs.iter().zip(cases) {
+            assert_close(*output, f64::clamp(a, lo, hi), 1e-12);
+        }
+    }
+
+    #[test]
+    fn qclamp_rejects_lo_above_hi() {
+        let (_, satisfied) = try_mock(|chip, ctx| vec![chip.qclamp(ctx, witness(chip, 0.5), witness(chip, 2.0), witness(chip, -1.0))]);
+        assert!(!satisfied);
+    }
+
+    #[test]
+    fn qmean_matches_f64() {
+        let xs = [1.5, -2.25, 0.0, 10.125, -0.5, 7.0, 3.0];
+        let outputs = mock(|chip, ctx| vec![
+            chip.qmean(ctx, witnesses(chip, &xs)),
+            chip.qmean(ctx, witnesses(chip, &[])),
+        ]);
+        assert_close(outputs[0], xs.iter().sum::<f64>() / xs.len() as f64, 1e-12);
+        assert_eq!(outputs[1], 0.0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "the argument must be reduced first")]
+    fn polynomial_asserts_its_intermediates_in_debug_builds() {
+        mock(|chip, ctx| {
+            let coef = chip.generate_exp2_poly();
+            vec![chip.polynomial(ctx, witness(chip, 1e6), coef)]
+        });
+    }
+
+    #[test]
+    fn polynomial_matches_horner() {
+        let coef = [0.5, -1.25, 2.0, 3.0];
+        let x = 1.5;
+        let outputs = mock(|chip, ctx| vec![chip.polynomial(ctx, witness(chip, x), witnesses(chip, &coef))]);
+        assert_close(outputs[0], coef.iter().fold(0.0, |y, c| y * x + c), 1e-12);
+    }
+
+    #[test]
+    fn qround_matches_f64() {
+        let xs = [0.0, 2.5, -2.5, 2.49, -2.51, 0.5, -0.5, 1234.5678];
+        let places = [0u32, 2, 4];
+        let outputs = mock(|chip, ctx| xs.iter().flat_map(|x| {
+            let mut rounded = vec![chip.qround(ctx, witness(chip, *x))];
+            rounded.extend(places.iter().map(|places| chip.qround_to(ctx, witness(chip, *x), *places)));
+            rounded
+        }).collect());
+        for (outputs, x) in outputs.chunks(places.len() + 1).zip(xs) {
+            assert_close(outputs[0], x.round(), 1e-12);
+            for (output, places) in outputs[1..].iter().zip(places) {
+                let factor = 10f64.powi(places as i32);
+                assert_close(*output, (x * factor).round() / factor, 1e-12);
+            }
+        }
+    }
+
+    struct Product(f64, f64);
+
+    impl WithPrecision<Fr> for Product {
+        type Output = (u32, f64);
+
+        fn call<const PRECISION_BITS: u32>(self, chip: FixedPointChip<Fr, PRECISION_BITS>) -> Self::Output {
+            let mut builder = BaseCircuitBuilder::<Fr>::new(false).use_k(K).use_lookup_bits(K - 1);
+            let a = Witness(chip.quantization(self.0));
+            let b = Witness(chip.quantization(self.1));
+            let product = chip.qmul(builder.main(0), a, b);
+            (PRECISION_BITS, chip.dequantization(*product.value()))
+        }
+    }
+
+    #[test]
+    fn with_precision_picks_the_chip() {
+        for precision in PrecisionConfig::ALL {
+            assert_eq!(PrecisionConfig::from_bits(precision.bits()), Some(precision));
+            let builder = BaseCircuitBuilder::<Fr>::new(false).use_k(K).use_lookup_bits(K - 1);
+            let (bits, product) = with_precision(precision, builder.range_chip(), Product(1.5, -0.1));
+            assert_eq!(bits, precision.bits());
+            // One quantum of rounding at the chosen precision, no finer than f64 reads it back
+            assert_close(product, -0.15, 2f64.powi(1 - bits as i32).max(f64::EPSILON));
+        }
+        assert_eq!(PrecisionConfig::from_bits(64), None);
+    }
+
+    #[test]
+    fn fixed_point_vec_rejects_other_lengths() {
+        let exact: FixedPointVec<PRECISION, 3> = serde_json::from_str("[1.5, 0.0, 2.0]").unwrap();
+        assert!(exact.validate().is_ok());
+        let values = RawInput::<Fr>::convert(&exact);
+        assert_eq!(values[0], Fr::from(3u64 << 47));
+        for json in ["[1.5, 0.0]", "[1.5, 0.0, 2.0, 3.0]"] {
+            let vec: FixedPointVec<PRECISION, 3> = serde_json::from_str(json).unwrap();
+            assert!(vec.validate().is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "the circuit expects exactly 3")]
+    fn fixed_point_vec_conversion_panics_on_a_short_vec() {
+        let short: FixedPointVec<PRECISION, 3> = serde_json::from_str("[1.5, 0.0]").unwrap();
+        RawInput::<Fr>::convert(&short);
+    }
+
+    #[test]
+    fn qmin_of_and_qmax_of_match_f64() {
+        let xs = [1.5, -2.25, 0.0, 10.125, -0.5];
+        let outputs = mock(|chip, ctx| vec![
+            chip.qmin_of(ctx, witnesses(chip, &xs)),
+            chip.qmax_of(ctx, witnesses(chip, &xs)),
+            chip.qmin_of(ctx, witnesses(chip, &[4.0])),
+        ]);
+        assert_close(outputs[0], xs.iter().copied().fold(f64::INFINITY, f64::min), 1e-12);
+        assert_close(outputs[1], xs.iter().copied().fold(f64::NEG_INFINITY, f64::max), 1e-12);
+        assert_eq!(outputs[2], 4.0);
+    }
+
+    #[test]
+    fn qmin_of_rejects_no_values() {
+        let (_, satisfied) = try_mock(|chip, ctx| vec![chip.qmin_of(ctx, witnesses(chip, &[]))]);
+        assert!(!satisfied);
+    }
+}
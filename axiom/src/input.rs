@@ -2,52 +2,154 @@
 // This custom implementation is used to avoid memory allocation error with large number of inputs (ie. 8192)
 
 use anyhow::Result;
-use axiom_sdk::{axiom::AxiomComputeInput, axiom_circuit::{axiom_eth::Field, input::flatten::InputFlatten}};
+use axiom_sdk::{Fr, axiom::AxiomComputeInput, axiom_circuit::{axiom_eth::Field, input::flatten::InputFlatten}};
 use serde::{Deserialize, Serialize};
 use crate::fixed::FixedPointConstants;
 
 #[derive(Clone, Debug,Default,Serialize, Deserialize)]
 pub struct VolatilityInput<const PRECISION_BITS:u32,const N:usize>
 {
-    pub ticks: Vec<f64>
+    pub ticks: Vec<f64>,
+    /// Amounts of the swap of each tick, to prove the volatility of the prices |amount1| / |amount0|
+    /// with `FixedPointChip::qlog_return_variance` rather than trusting the ticks. Both or neither
+    /// are given, the Axiom compute proves the amounts when they are.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount0: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount1: Option<Vec<f64>>,
 }
 
+impl<const PRECISION_BITS:u32,const N:usize> VolatilityInput<PRECISION_BITS,N> {
+    /// Checks that the input holds exactly `N` ticks, the circuit size. Short inputs are not padded:
+    /// the circuit normalizes by `N`, so any padding would change the volatility.
+    /// Non-finite ticks are rejected, quantization would silently turn them into garbage, and so are
+    /// ticks out of the fixed point range, which would be read back with the wrong sign.
+    /// Amounts, when given, must be finite and in range with one pair per tick, and amount0 must not be zero.
+    pub fn validate(&self) -> Result<()> {
+        if let Some((index, tick)) = self.ticks.iter().enumerate().find(|(_, tick)| !tick.is_finite()) {
+            anyhow::bail!("Tick {} is not finite: {}", index, tick);
+        }
+        let constants = FixedPointConstants::<Fr,PRECISION_BITS>::default();
+        if let Some((index, tick)) = self.ticks.iter().enumerate().find(|(_, tick)| !constants.is_in_range(**tick)) {
+            anyhow::bail!(
+                "Tick {} is outside the representable range ({}, {}): {}", index, constants.min_value(), constants.max_value(), tick
+            );
+        }
+        if let Some((amount0, amount1)) = self.amounts()? {
+            if amount0.len() != self.ticks.len() || amount1.len() != self.ticks.len() {
+                anyhow::bail!(
+                    "Input has {} ticks but {} amount0 and {} amount1", self.ticks.len(), amount0.len(), amount1.len()
+                );
+            }
+            if let Some((index, amount)) = amount0.iter().enumerate().find(|(_, amount)| !amount.is_finite() || **amount == 0.0) {
+                anyhow::bail!("Amount0 {} is not finite and non-zero: {}", index, amount);
+            }
+            if let Some((index, amount)) = amount1.iter().enumerate().find(|(_, amount)| !amount.is_finite()) {
+                anyhow::bail!("Amount1 {} is not finite: {}", index, amount);
+            }
+            for (name, amounts) in [("Amount0", amount0), ("Amount1", amount1)] {
+                if let Some((index, amount)) = amounts.iter().enumerate().find(|(_, amount)| !constants.is_in_range(**amount)) {
+                    anyhow::bail!(
+                        "{} {} is outside the representable range ({}, {}): {}", name, index, constants.min_value(), constants.max_value(), amount
+                    );
+                }
+            }
+        }
+        match self.ticks.len() {
+            len if len == N => Ok(()),
+            0 => anyhow::bail!("Input has no ticks, the circuit expects {}", N),
+            len if len > N => anyhow::bail!(
+                "Input has {} ticks, the circuit expects {}: trim the input or update SAMPLE_SIZE", len, N
+            ),
+            len => anyhow::bail!(
+                "Input has {} ticks, the circuit expects {}: extend the input or update SAMPLE_SIZE", len, N
+            ),
+        }
+    }
+
+    /// The swap amounts, if the input carries them instead of relying on the ticks alone.
+    pub fn amounts(&self) -> Result<Option<(&[f64], &[f64])>> {
+        match (&self.amount0, &self.amount1) {
+            (Some(amount0), Some(amount1)) => Ok(Some((amount0, amount1))),
+            (None, None) => Ok(None),
+            _ => anyhow::bail!("amount0 and amount1 must be given together"),
+        }
+    }
+}
+
+/// Circuit input: the ticks, and the swap amounts when `use_amounts` is one, zeros otherwise.
+/// `VolatilityChip::input_volatility` proves the ticks or the amounts accordingly.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct VolatilityCircuitInput<T: Copy,const PRECISION_BITS:u32, const N: usize>(pub Vec<T>);
+pub struct VolatilityCircuitInput<T: Copy,const PRECISION_BITS:u32, const N: usize> {
+    pub ticks: Vec<T>,
+    pub amount0: Vec<T>,
+    pub amount1: Vec<T>,
+    pub use_amounts: T,
+}
 
 
 impl<T: Copy + Default, const PRECISION_BITS:u32, const N: usize> Default for VolatilityCircuitInput<T,PRECISION_BITS,N> {
     fn default() -> Self {
-        Self(vec![T::default(); N])
+        Self {
+            ticks: vec![T::default(); N],
+            amount0: vec![T::default(); N],
+            amount1: vec![T::default(); N],
+            use_amounts: T::default(),
+        }
     }
 }
 
 impl<T: Copy + Default,const PRECISION_BITS:u32, const N: usize> VolatilityCircuitInput<T,PRECISION_BITS,N> {
+    /// Input proving the ticks, without amounts.
     pub fn new(vec: Vec<T>) -> anyhow::Result<Self> {
         if vec.len() != N {
             anyhow::bail!("Invalid input length: {} != {}", vec.len(), N);
         }
-        Ok(VolatilityCircuitInput(vec))
+        Ok(Self { ticks: vec, ..Default::default() })
     }
 
     pub fn into_inner(self) -> Vec<T> {
-        self.0
+        self.ticks
     }
 }
 
 impl<F:Field,const PRECISION_BITS:u32,const N:usize> From<VolatilityInput<PRECISION_BITS,N>> for VolatilityCircuitInput<F,PRECISION_BITS,N> {
+    /// Panics unless the input holds `N` ticks, and as many amounts if any, see
+    /// `VolatilityInput::validate`.
     fn from(input: VolatilityInput<PRECISION_BITS,N>) -> Self {
+        assert_eq!(input.ticks.len(), N, "Input has {} ticks, the circuit expects {}", input.ticks.len(), N);
+        let constants = FixedPointConstants::<F,PRECISION_BITS>::default();
+        let mut circuit_input = Self::new(constants.quantize_many(&input.ticks)).unwrap();
+        if let Some((amount0, amount1)) = input.amounts().expect("Invalid input") {
+            assert!(amount0.len() == N && amount1.len() == N, "Input has {} ticks but {} amount0 and {} amount1", N, amount0.len(), amount1.len());
+            circuit_input.amount0 = constants.quantize_many(amount0);
+            circuit_input.amount1 = constants.quantize_many(amount1);
+            circuit_input.use_amounts = F::ONE;
+        }
+        circuit_input
+    }
+}
+
+impl<F:Field,const PRECISION_BITS:u32,const N:usize> VolatilityCircuitInput<F,PRECISION_BITS,N> {
+    /// Quantizes integer ticks exactly, large ticks lose precision when converted through f64.
+    pub fn from_int_ticks(ticks: &[i64]) -> anyhow::Result<Self> {
         let constants = FixedPointConstants::<F,PRECISION_BITS>::default();
-        VolatilityCircuitInput(input.ticks.iter().map(|x| constants.quantization(*x)).collect())
+        Self::new(ticks.iter().map(|tick| constants.quantize_int(*tick)).collect())
     }
 }
 
 impl<T: Copy,const PRECISION_BITS:u32, const N: usize> InputFlatten<T> for VolatilityCircuitInput<T,PRECISION_BITS,N> {
-    const NUM_FE: usize = N;
+    /// The ticks, amount0 and amount1, then `use_amounts`.
+    const NUM_FE: usize = 3 * N + 1;
     fn flatten_vec(&self) -> Vec<T> {
-        self.0.clone()
+        let mut vec = Vec::with_capacity(Self::NUM_FE);
+        vec.extend_from_slice(&self.ticks);
+        vec.extend_from_slice(&self.amount0);
+        vec.extend_from_slice(&self.amount1);
+        vec.push(self.use_amounts);
+        vec
     }
-    fn unflatten(vec: Vec<T>) -> Result<Self> {
+    fn unflatten(mut vec: Vec<T>) -> Result<Self> {
         if vec.len() != Self::NUM_FE {
             anyhow::bail!(
                 "Invalid input length: {} != {}",
@@ -55,7 +157,10 @@ impl<T: Copy,const PRECISION_BITS:u32, const N: usize> InputFlatten<T> for Volat
                 Self::NUM_FE
             );
         }
-        Ok(VolatilityCircuitInput(vec))
+        let use_amounts = vec.pop().unwrap();
+        let amount1 = vec.split_off(2 * N);
+        let amount0 = vec.split_off(N);
+        Ok(VolatilityCircuitInput { ticks: vec, amount0, amount1, use_amounts })
     }
 }
 
@@ -63,3 +168,60 @@ impl<const PRECISION_BITS:u32,const N:usize> AxiomComputeInput for VolatilityInp
      type LogicInput = VolatilityInput<PRECISION_BITS,N>;
      type Input<T: Copy> = VolatilityCircuitInput<T,PRECISION_BITS,N>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Input = VolatilityInput<48, 4>;
+
+    fn input(ticks: &[f64]) -> Input {
+        Input { ticks: ticks.to_vec(), ..Default::default() }
+    }
+
+    #[test]
+    fn validate_accepts_exactly_n_ticks() {
+        input(&[1.0, 2.0, 4.0, 3.0]).validate().unwrap();
+        let circuit_input: VolatilityCircuitInput<Fr, 48, 4> = input(&[1.0, 2.0, 4.0, 3.0]).into();
+        assert_eq!(circuit_input.ticks.len(), 4);
+    }
+
+    #[test]
+    fn validate_rejects_short_inputs() {
+        let error = input(&[1.0, 2.0, 4.0]).validate().unwrap_err();
+        assert_eq!(error.to_string(), "Input has 3 ticks, the circuit expects 4: extend the input or update SAMPLE_SIZE");
+        assert!(input(&[]).validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_non_finite_ticks_by_index() {
+        let error = input(&[1.0, 2.0, f64::NAN, 3.0]).validate().unwrap_err();
+        assert_eq!(error.to_string(), "Tick 2 is not finite: NaN");
+        let error = input(&[1.0, f64::NEG_INFINITY, 4.0, 3.0]).validate().unwrap_err();
+        assert_eq!(error.to_string(), "Tick 1 is not finite: -inf");
+    }
+
+    #[test]
+    fn validate_rejects_long_inputs() {
+        let error = input(&[1.0, 2.0, 4.0, 3.0, 5.0]).validate().unwrap_err();
+        assert_eq!(error.to_string(), "Input has 5 ticks, the circuit expects 4: trim the input or update SAMPLE_SIZE");
+    }
+
+    #[test]
+    #[should_panic(expected = "Input has 3 ticks, the circuit expects 4")]
+    fn short_inputs_are_not_padded() {
+        let _: VolatilityCircuitInput<Fr, 48, 4> = input(&[1.0, 2.0, 4.0]).into();
+    }
+
+    #[test]
+    fn from_int_ticks_matches_the_f64_ticks() {
+        let ticks = [-887272, 0, 5, 887272];
+        let from_ints = VolatilityCircuitInput::<Fr, 48, 4>::from_int_ticks(&ticks).unwrap();
+        let from_f64: VolatilityCircuitInput<Fr, 48, 4> = input(&ticks.map(|tick| tick as f64)).into();
+        assert_eq!(from_ints, from_f64);
+
+        let constants = FixedPointConstants::<Fr, 48>::default();
+        assert_eq!(constants.dequantize_many(&from_ints.ticks), ticks.map(|tick| tick as f64));
+        assert!(VolatilityCircuitInput::<Fr, 48, 4>::from_int_ticks(&ticks[1..]).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+// Semiotic
+// Reuse of the proving key generated by `keygen` across runs.
+//
+// The key only depends on the circuit (its version, PRECISION, SAMPLE_SIZE, whether the input
+// carries swap amounts, and the halo2 config file), so it is fingerprinted after keygen and regenerated only when that changes.
+// `run_cli` never generates a key on `run`/`prove`, it reads `<name>.pk` and the pinning
+// `keygen` saved in the data path, so a fresh key is what every later proof reuses.
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::{env, fs, path::PathBuf, process::Command};
+
+const DEFAULT_DATA_PATH: &str = "data";
+const DEFAULT_CIRCUIT_NAME: &str = "circuit";
+
+/// Version of the constraints of the volatility circuit, to bump whenever they change so that
+/// keys generated for the previous circuit are not reused. The crate version is fingerprinted too.
+pub const CIRCUIT_VERSION: u32 = 1;
+
+pub struct KeyCache {
+    command: Option<String>,
+    data_path: PathBuf,
+    name: String,
+    fingerprint: String,
+}
+
+/// Returns the value of a `--flag value` or `--flag=value` command line option.
+fn option_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == flag {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix(flag)
+                .and_then(|rest| rest.strip_prefix('='))
+                .map(str::to_string)
+        }
+    })
+}
+
+impl KeyCache {
+    pub fn from_args(
+        args: &[String],
+        precision: u32,
+        sample_size: usize,
+        amounts: bool,
+    ) -> Result<Self> {
+        Self::with_version(args, CIRCUIT_VERSION, precision, sample_size, amounts)
+    }
+
+    fn with_version(
+        args: &[String],
+        circuit_version: u32,
+        precision: u32,
+        sample_size: usize,
+        amounts: bool,
+    ) -> Result<Self> {
+        let command = args
+            .iter()
+            .find(|arg| ["mock", "keygen", "prove", "run"].contains(&arg.as_str()))
+            .cloned();
+        let data_path = option_value(args, "--data-path")
+            .unwrap_or_else(|| DEFAULT_DATA_PATH.to_string())
+            .into();
+        let name = option_value(args, "--name").unwrap_or_else(|| DEFAULT_CIRCUIT_NAME.to_string());
+
+        // A fixed hash, unlike DefaultHasher the fingerprint does not change with the toolchain
+        let mut hasher = Sha256::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update(circuit_version.to_le_bytes());
+        hasher.update(precision.to_le_bytes());
+        hasher.update((sample_size as u64).to_le_bytes());
+        hasher.update([amounts as u8]);
+        if let Some(config) = option_value(args, "--config") {
+            hasher.update(
+                fs::read(&config)
+                    .with_context(|| format!("Circuit config {} can not be read", config))?,
+            );
+        }
+        let fingerprint = format!("{:x}", hasher.finalize());
+
+        Ok(Self {
+            command,
+            data_path,
+            name,
+            fingerprint,
+        })
+    }
+
+    fn fingerprint_path(&self) -> PathBuf {
+        self.data_path.join(format!("{}.fingerprint", self.name))
+    }
+
+    /// True when a proving key exists and was generated for the current circuit configuration.
+    pub fn is_fresh(&self) -> bool {
+        self.data_path.join(format!("{}.pk", self.name)).exists()
+            && fs::read_to_string(self.fingerprint_path())
+                .map(|fingerprint| fingerprint.trim() == self.fingerprint)
+                .unwrap_or(false)
+    }
+
+    /// True for a `run`/`prove` without a fresh key, which must run `keygen` first.
+    pub fn needs_keygen(&self) -> bool {
+        matches!(self.command.as_deref(), Some("run" | "prove")) && !self.is_fresh()
+    }
+
+    /// Runs `keygen` in a child process before `run`/`prove` when the cached key is missing or
+    /// was generated for a different circuit configuration.
+    pub fn ensure_keys(&self, args: &[String]) -> Result<()> {
+        if !self.needs_keygen() {
+            return Ok(());
+        }
+        println!("\x1b[93mProving key missing or stale, running keygen\x1b[0m");
+        let keygen_args = args.iter().map(|arg| match arg.as_str() {
+            "run" | "prove" => "keygen",
+            arg => arg,
+        });
+        let status = Command::new(env::current_exe()?)
+            .args(keygen_args)
+            .status()
+            .context("Failed to start keygen")?;
+        if !status.success() {
+            bail!("Keygen failed with {}", status);
+        }
+        Ok(())
+    }
+
+    /// Records the configuration fingerprint after a successful `keygen`.
+    pub fn store(&self) -> Result<()> {
+        if self.command.as_deref() != Some("keygen") {
+            return Ok(());
+        }
+        fs::write(self.fingerprint_path(), &self.fingerprint)
+            .context("Failed to write key fingerprint")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn args(command: &str, data_path: &Path) -> Vec<String> {
+        vec![
+            "--data-path".to_string(),
+            data_path.display().to_string(),
+            command.to_string(),
+        ]
+    }
+
+    #[test]
+    fn second_run_reuses_the_key() {
+        let data_path = env::temp_dir().join(format!("axiom-keys-{}", std::process::id()));
+        fs::create_dir_all(&data_path).unwrap();
+
+        let first = KeyCache::from_args(&args("run", &data_path), 48, 8192, false).unwrap();
+        assert!(first.needs_keygen());
+
+        // What the keygen child process leaves behind
+        let keygen = KeyCache::from_args(&args("keygen", &data_path), 48, 8192, false).unwrap();
+        fs::write(data_path.join("circuit.pk"), b"key").unwrap();
+        keygen.store().unwrap();
+
+        let second = KeyCache::from_args(&args("run", &data_path), 48, 8192, false).unwrap();
+        assert!(!second.needs_keygen());
+        let resized = KeyCache::from_args(&args("run", &data_path), 48, 4096, false).unwrap();
+        assert!(resized.needs_keygen());
+        let reprecised = KeyCache::from_args(&args("prove", &data_path), 32, 8192, false).unwrap();
+        assert!(reprecised.needs_keygen());
+        let amounts = KeyCache::from_args(&args("run", &data_path), 48, 8192, true).unwrap();
+        assert!(amounts.needs_keygen());
+        let changed = KeyCache::with_version(
+            &args("run", &data_path),
+            CIRCUIT_VERSION + 1,
+            48,
+            8192,
+            false,
+        )
+        .unwrap();
+        assert!(changed.needs_keygen());
+
+        fs::remove_dir_all(&data_path).unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+#![feature(generic_arg_infer)]
+// Semiotic
+// Volatility circuit of the Axiom backend and its fixed point chips, used by the `axiom` binary.
+use axiom_sdk::{
+    axiom::{AxiomAPI, AxiomComputeFn, AxiomResult},
+    Fr,
+};
+use halo2_base::AssignedValue;
+use input::{VolatilityCircuitInput, VolatilityInput};
+use volatility::VolatilityChip;
+
+pub mod benchmark;
+pub mod fixed;
+pub mod fixedpoint;
+pub mod input;
+pub mod keys;
+pub mod utils;
+pub mod volatility;
+
+pub const PRECISION: u32 = 48;
+pub const SAMPLE_SIZE: usize = 8192;
+
+impl AxiomComputeFn for VolatilityInput<PRECISION,SAMPLE_SIZE> {
+    fn compute(
+        api: &mut AxiomAPI,
+        input: VolatilityCircuitInput<AssignedValue<Fr>,PRECISION,SAMPLE_SIZE>,
+    ) -> Vec<AxiomResult> {
+
+        let chip:VolatilityChip<Fr,PRECISION> = VolatilityChip::new(&api.builder.base);
+
+        let ctx = api.ctx();
+        
+        let volatility = chip.input_volatility(ctx, input);
+        
+        let value = chip.dequantization(*volatility.value());
+
+        println!("Axiom    : {}",value);
+
+        vec![
+            volatility.into()
+        ]
+    }
+}
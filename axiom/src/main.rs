@@ -1,56 +1,42 @@
 #![feature(generic_arg_infer)]
-use axiom_sdk::axiom::{AxiomAPI, AxiomComputeFn, AxiomResult};
+use axiom::{
+    benchmark,
+    input::VolatilityInput,
+    keys::KeyCache,
+    utils, PRECISION, SAMPLE_SIZE,
+};
 use axiom_sdk::cmd::run_cli;
-use axiom_sdk::Fr;
-use input::{VolatilityCircuitInput, VolatilityInput};
-use volatility::VolatilityChip;
-
-use halo2_base::AssignedValue;
+use std::env;
 use std::fs::File;
 use std::io::BufReader;
 
-mod fixed;
-mod volatility; 
-mod utils;
-mod input;
-
-const PRECISION: u32 = 48;
-const SAMPLE_SIZE: usize = 8192;
 const FILE:&str = "data/inputs.json";
 
-impl AxiomComputeFn for VolatilityInput<PRECISION,SAMPLE_SIZE> {
-    fn compute(
-        api: &mut AxiomAPI,
-        input: VolatilityCircuitInput<AssignedValue<Fr>,PRECISION,SAMPLE_SIZE>,
-    ) -> Vec<AxiomResult> {
-
-        let chip:VolatilityChip<Fr,PRECISION> = VolatilityChip::new(&api.builder.base);
-
-        let values =  input.0;
-
-        let ctx = api.ctx();
-        
-        let volatility = chip.volatility(ctx, values);
-        
-        let value = chip.dequantization(*volatility.value());
-
-        println!("Axiom    : {}",value);
-
-        vec![
-            volatility.into()
-        ]
-    }
-}
-
 fn main() {
 
     env_logger::init();
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some(benchmark::COMMAND) {
+        let sizes = benchmark::sizes(&args).expect("Invalid arguments");
+        benchmark::benchmark(&sizes).expect("Volatility regressed");
+        return;
+    }
+
     let input:VolatilityInput<PRECISION,SAMPLE_SIZE> = File::open(FILE)
-    .map(|file| BufReader::new(file))
+    .map(BufReader::new)
     .map(|reader| serde_json::from_reader(reader).expect("Invalid JSON"))
     .expect("Input file can not be read");
 
+    input.validate().expect("Invalid input");
+
+    let amounts = input.amounts().expect("Invalid input");
+    if let Some((amount0, amount1)) = amounts {
+        println!("\x1b[93mLog return variance of the swap prices:\x1b[0m");
+        println!("Reference: {}",utils::calculate_from_amounts(amount0, amount1));
+    }
+
+    let use_amounts = amounts.is_some();
     let ticks = input.ticks;
 
     println!("\x1b[93mNumber of ticks: {}\x1b[0m",ticks.len());
@@ -62,5 +48,10 @@ fn main() {
     println!("Reference: {}",volatility_original);
     println!("Optimized: {}",volatility_optmized);
 
+    let keys = KeyCache::from_args(&args, PRECISION, SAMPLE_SIZE, use_amounts).expect("Invalid arguments");
+    keys.ensure_keys(&args).expect("Proving key can not be generated");
+
     run_cli::<VolatilityInput<PRECISION,SAMPLE_SIZE> >();
+
+    keys.store().expect("Proving key fingerprint can not be saved");
 }
\ No newline at end of file
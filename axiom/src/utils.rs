@@ -1,20 +1,5 @@
-use halo2_base::utils::ScalarField;
-
-pub trait ScalarFieldExt {
-    /// Gets the least significant 128 bits of the field element.
-    fn get_lower_128(&self) -> u128;
-}
-
-impl<F: ScalarField> ScalarFieldExt for F {
-    fn get_lower_128(&self) -> u128 {
-        let bytes = self.to_bytes_le();
-        let mut lower_128 = 0u128;
-        for (i, byte) in bytes.into_iter().enumerate().take(16) {
-            lower_128 |= (byte as u128) << (i * 8);
-        }
-        lower_128
-    }
-}
+// Plain f64 reference implementations, free of circuit dependencies so they can be shared
+// with the other backends for cross-checking.
 
 /// As the name suggests, this function calculates the volatility of a series of ticks in
 /// an iterative manner.
@@ -33,7 +18,8 @@ impl State {
         // the number of operations and divisions.
 
         let delta = self.prev.unwrap_or_default() - self.first.unwrap_or_default();
-        (self.delta_sq_sum - ((delta * delta) / self.n)) / (self.n - 1f64)
+        // Clamped as cancellation can leave a tiny negative variance for constant ticks
+        ((self.delta_sq_sum - ((delta * delta) / self.n)) / (self.n - 1f64)).max(0f64)
     }
 
     /// Updates the state with a new tick
@@ -52,9 +38,7 @@ impl State {
 
 /// Calculates the volatility of a series of ticks in an optimized manner.
 pub fn calculate_optimized(ticks: &[f64]) -> f64 {
-    let state = ticks
-        .into_iter()
-        .fold(State::default(), |s, t| s.update(*t));
+    let state = ticks.iter().fold(State::default(), |s, t| s.update(*t));
 
     state.volatility()
 }
@@ -73,5 +57,75 @@ pub fn calculate_original(ticks: &[f64]) -> f64 {
             ticks_prev = *ticks_curr;
             (su + delta * n_inv_sqrt, su2 + delta * delta * n1_inv)
         });
-    sum_u2 - (sum_u * sum_u) * n1_inv
+    (sum_u2 - (sum_u * sum_u) * n1_inv).max(0f64)
+}
+
+/// Calculates the sample variance of the log returns of the swap prices |amount1| / |amount0|,
+/// the reference of the amount based circuit path.
+pub fn calculate_from_amounts(amount0: &[f64], amount1: &[f64]) -> f64 {
+    assert_eq!(
+        amount0.len(),
+        amount1.len(),
+        "amount0 and amount1 must have the same length"
+    );
+    let log_prices: Vec<f64> = amount0
+        .iter()
+        .zip(amount1)
+        .map(|(a0, a1)| (a1.abs() / a0.abs()).ln())
+        .collect();
+    let returns: Vec<f64> = log_prices
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .collect();
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    returns.iter().map(|r| (r - mean) * (r - mean)).sum::<f64>() / (n - 1f64)
+}
+
+/// Calculates the weighted variance of the tick returns, each return weighted by the weight of
+/// the tick that ends it, the reference of `VolatilityChip::weighted_volatility`. Uniform
+/// weights give the population variance of the returns.
+pub fn calculate_weighted(ticks: &[f64], weights: &[f64]) -> f64 {
+    assert_eq!(
+        ticks.len(),
+        weights.len(),
+        "ticks and weights must have the same length"
+    );
+    if ticks.len() < 2 {
+        return 0f64;
+    }
+    let (weight_sum, weighted_sum, weighted_sq_sum) = ticks.windows(2).zip(&weights[1..]).fold(
+        (0f64, 0f64, 0f64),
+        |(w, wu, wu2), (pair, weight)| {
+            let delta = pair[1] - pair[0];
+            (
+                w + weight,
+                wu + weight * delta,
+                wu2 + weight * delta * delta,
+            )
+        },
+    );
+    let mean = weighted_sum / weight_sum;
+    (weighted_sq_sum / weight_sum - mean * mean).max(0f64)
+}
+
+/// Calculates the `p`-th percentile (0..=100) of the values, interpolating linearly between
+/// the closest ranks.
+pub fn percentile(values: &[f64], p: f64) -> f64 {
+    assert!(!values.is_empty(), "percentile of an empty slice");
+    assert!(
+        (0f64..=100f64).contains(&p),
+        "percentile must be within [0, 100]"
+    );
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let rank = p / 100f64 * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// Calculates the median of the values.
+pub fn median(values: &[f64]) -> f64 {
+    percentile(values, 50f64)
 }
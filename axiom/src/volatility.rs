@@ -1,10 +1,16 @@
-use crate::fixed::FixedPointConstants;
+use crate::{
+    fixed::FixedPointConstants,
+    fixedpoint::{FixedPointChip, FixedPointInstructions},
+    input::VolatilityCircuitInput,
+    utils,
+};
 use halo2_base::{
-    gates::{circuit::builder::BaseCircuitBuilder, GateInstructions, RangeChip},
+    gates::{circuit::builder::BaseCircuitBuilder, GateInstructions, RangeChip, RangeInstructions},
     utils::{biguint_to_fe, fe_to_biguint, BigPrimeField},
     AssignedValue, Context, QuantumCell,
     QuantumCell::{Constant, Existing, Witness},
 };
+use num_bigint::BigUint;
 use num_integer::Integer;
 use std::iter;
 
@@ -15,8 +21,14 @@ pub struct VolatilityChip<F: BigPrimeField, const PRECISION_BITS: u32 = 32> {
 
 impl<F: BigPrimeField, const PRECISION_BITS: u32> VolatilityChip<F, PRECISION_BITS> {
     pub fn new(builder: &BaseCircuitBuilder<F>) -> Self {
+        Self::from_range_chip(builder.range_chip())
+    }
+
+    /// Builds the chip on an existing range chip, so that it can be shared with a
+    /// `FixedPointChip` in the same circuit.
+    pub fn from_range_chip(range: RangeChip<F>) -> Self {
         Self {
-            range: builder.range_chip(),
+            range,
             constants: FixedPointConstants::<F, PRECISION_BITS>::default(),
         }
     }
@@ -29,6 +41,16 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> VolatilityChip<F, PRECISION_BI
         self.constants.quantization(value)
     }
 
+    /// See `FixedPointConstants::dequantize_many`.
+    pub fn dequantize_many(&self, values: &[F]) -> Vec<f64> {
+        self.constants.dequantize_many(values)
+    }
+
+    /// See `FixedPointConstants::quantize_many`.
+    pub fn quantize_many(&self, values: &[f64]) -> Vec<F> {
+        self.constants.quantize_many(values)
+    }
+
     /// Optimized to scale a unsigned value to precision
     fn scale(
         &self,
@@ -82,12 +104,20 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> VolatilityChip<F, PRECISION_BI
         self.range.gate.sub(ctx, a, b)
     }
 
-    /// Calculates the volatility square of the provided values
-    pub fn volatility<QA>(
+    /// Sums of the deltas of the provided values, scaled to precision: the sum of their
+    /// squares and the square of their sum, with the number of values. `None` for fewer
+    /// than two values.
+    ///
+    /// Any number of values works, the offsets assume no parity or power of two. `n` values
+    /// take `1 + 3 * (n - 1)` cells in both regions, every gate starts on a multiple of 3 below
+    /// `len = 3 * (n - 1)` and its last cell starts the next one, so `n = len / 3 + 1` exactly.
+    /// The deltas are read back from `row_offset`, which requires converting the values into
+    /// cells not to assign anything in `ctx`.
+    fn delta_sums<QA>(
         &self,
         ctx: &mut Context<F>,
         a: impl IntoIterator<Item = QA>,
-    ) -> AssignedValue<F>
+    ) -> Option<(AssignedValue<F>, AssignedValue<F>, usize)>
     where
         QA: Into<QuantumCell<F>>,
     {
@@ -95,17 +125,9 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> VolatilityChip<F, PRECISION_BI
 
         let mut a = a.into_iter().peekable();
 
-        let previous = a.next();
+        let mut previous_value: QuantumCell<F> = a.next()?.into();
 
-        if previous.is_none() {
-            return ctx.load_zero();
-        }
-
-        let mut previous_value: QuantumCell<F> = previous.unwrap().into();
-
-        if a.peek().is_none() {
-            return ctx.load_zero();
-        }
+        a.peek()?;
 
         // Below iteration compresses deviation calculations into n-2 cells compared to
         // standard methods per step/item. [n0,1,n1-n0,n1,1,n2-n1,n2...] to comply axioms
@@ -179,7 +201,23 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> VolatilityChip<F, PRECISION_BI
 
         let delta_sum_sq = self.scale(ctx, delta_sum_sq).0;
 
-        let len = ((len / 3) + 1) as f64;
+        Some((delta_sq_sum, delta_sum_sq, (len / 3) + 1))
+    }
+
+    /// Calculates the volatility square of the provided values
+    pub fn volatility<QA>(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl IntoIterator<Item = QA>,
+    ) -> AssignedValue<F>
+    where
+        QA: Into<QuantumCell<F>>,
+    {
+        let Some((delta_sq_sum, delta_sum_sq, len)) = self.delta_sums(ctx, a) else {
+            return ctx.load_zero();
+        };
+
+        let len = len as f64;
 
         let n_inv = ctx.load_constant(self.quantization(1f64 / len));
         let n1_inv = ctx.load_constant(self.quantization(1f64 / (len - 1f64)));
@@ -187,8 +225,548 @@ impl<F: BigPrimeField, const PRECISION_BITS: u32> VolatilityChip<F, PRECISION_BI
         // Again all values are positive, we can safely use unsigned multiplication.
         let delta_sum_sq_div_n = self.mul(ctx, delta_sum_sq, n_inv);
 
+        let delta = self.sub(ctx, delta_sq_sum, delta_sum_sq_div_n);
+        // Rounding can leave the difference slightly negative for constant ticks, which would
+        // wrap around the field, so it is clamped to zero. Both are scaled below 2^{3p}.
+        let is_negative = self.range.is_less_than(
+            ctx,
+            delta_sq_sum,
+            delta_sum_sq_div_n,
+            (PRECISION_BITS * 3) as usize,
+        );
+        let delta = self
+            .range
+            .gate
+            .select(ctx, Constant(F::ZERO), delta, is_negative);
+
+        self.mul(ctx, delta, n1_inv)
+    }
+
+    /// Volatility of a circuit input: the volatility square of its ticks, or the sample variance
+    /// of the log returns of the swap prices |amount1| / |amount0| when `use_amounts` is one.
+    /// The path is picked from the witness and `use_amounts` constrained to it, so a key only
+    /// proves inputs of the kind it was generated with.
+    pub fn input_volatility<const N: usize>(
+        &self,
+        ctx: &mut Context<F>,
+        input: VolatilityCircuitInput<AssignedValue<F>, PRECISION_BITS, N>,
+    ) -> AssignedValue<F> {
+        if *input.use_amounts.value() == F::ONE {
+            self.range
+                .gate
+                .assert_is_const(ctx, &input.use_amounts, &F::ONE);
+            let fixed = FixedPointChip::<F, PRECISION_BITS>::from_range_chip(self.range.clone());
+            fixed.qlog_return_variance(ctx, input.amount0, input.amount1)
+        } else {
+            self.range
+                .gate
+                .assert_is_const(ctx, &input.use_amounts, &F::ZERO);
+            self.volatility(ctx, input.ticks)
+        }
+    }
+
+    /// Returns 1 if the volatility square of the provided values exceeds `threshold_sq`, the
+    /// square of the volatility threshold, and 0 otherwise. Comparing the variance skips the
+    /// in-circuit square root, for callers that only need to know if a bound is crossed.
+    pub fn exceeds_threshold<QA>(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl IntoIterator<Item = QA>,
+        threshold_sq: f64,
+    ) -> AssignedValue<F>
+    where
+        QA: Into<QuantumCell<F>>,
+    {
+        assert!(
+            threshold_sq >= 0f64 && self.constants.is_in_range(threshold_sq),
+            "Squared threshold {} must be non negative and representable",
+            threshold_sq
+        );
+        let variance = self.volatility(ctx, a);
+        // Both are non negative fixed point values, below 2^(2 * PRECISION_BITS)
+        let num_bits = (PRECISION_BITS * 2 + 1) as usize;
+        self.range.is_less_than(
+            ctx,
+            Constant(self.quantization(threshold_sq)),
+            variance,
+            num_bits,
+        )
+    }
+
+    /// Calculates the realized variance of the provided values, the sum of the squared tick
+    /// deltas without the mean subtraction or the `1/(n-1)` normalization of `volatility`.
+    pub fn realized_variance<QA>(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl IntoIterator<Item = QA>,
+    ) -> AssignedValue<F>
+    where
+        QA: Into<QuantumCell<F>>,
+    {
+        match self.delta_sums(ctx, a) {
+            Some((delta_sq_sum, _, _)) => delta_sq_sum,
+            None => ctx.load_zero(),
+        }
+    }
+
+    /// Calculates the volatility square after winsorizing the tick deltas: every delta is
+    /// clamped into its `[lower_pct, upper_pct]` percentile bounds before the variance sums.
+    /// Sorting in-circuit is too expensive, so the bounds are computed from the witness with
+    /// `utils::percentile` and loaded as witnesses; the circuit enforces that they are in range,
+    /// ordered, and applied to every delta, not that they are the exact percentiles.
+    pub fn winsorized_volatility<QA>(
+        &self,
+        ctx: &mut Context<F>,
+        a: impl IntoIterator<Item = QA>,
+        lower_pct: f64,
+        upper_pct: f64,
+    ) -> AssignedValue<F>
+    where
+        QA: Into<QuantumCell<F>>,
+    {
+        let ticks = a
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<QuantumCell<F>>>();
+
+        if ticks.len() < 2 {
+            return ctx.load_zero();
+        }
+
+        let gate = &self.range.gate;
+
+        let deltas = ticks
+            .windows(2)
+            .map(|pair| gate.sub(ctx, pair[1], pair[0]))
+            .collect::<Vec<AssignedValue<F>>>();
+
+        let delta_values = deltas
+            .iter()
+            .map(|delta| self.dequantization(*delta.value()))
+            .collect::<Vec<f64>>();
+        let lower =
+            ctx.load_witness(self.quantization(utils::percentile(&delta_values, lower_pct)));
+        let upper =
+            ctx.load_witness(self.quantization(utils::percentile(&delta_values, upper_pct)));
+
+        // Signed values are shifted by max_value = 2^{2p} so they can be compared as unsigned
+        // numbers in [0, 2^{2p+1}).
+        let num_bits = (PRECISION_BITS * 2 + 1) as usize;
+        let offset = Constant(biguint_to_fe(&BigUint::from(2u32).pow(PRECISION_BITS * 2)));
+        let shift = |ctx: &mut Context<F>, value: AssignedValue<F>| {
+            let shifted = gate.add(ctx, value, offset);
+            self.range.range_check(ctx, shifted, num_bits);
+            shifted
+        };
+
+        let lower_shifted = shift(ctx, lower);
+        let upper_shifted = shift(ctx, upper);
+        let inverted = self
+            .range
+            .is_less_than(ctx, upper_shifted, lower_shifted, num_bits);
+        gate.assert_is_const(ctx, &inverted, &F::ZERO);
+
+        let clamped = deltas
+            .into_iter()
+            .map(|delta| {
+                let delta_shifted = shift(ctx, delta);
+                let below = self
+                    .range
+                    .is_less_than(ctx, delta_shifted, lower_shifted, num_bits);
+                let delta = gate.select(ctx, lower, delta, below);
+                let above = self
+                    .range
+                    .is_less_than(ctx, upper_shifted, delta_shifted, num_bits);
+                gate.select(ctx, upper, delta, above)
+            })
+            .collect::<Vec<AssignedValue<F>>>();
+
+        // Clamped deltas no longer telescope to last - first, so both sums are explicit.
+        let delta_sum = gate.sum(ctx, clamped.iter().copied());
+        let delta_sq_sum = gate.inner_product(
+            ctx,
+            clamped.iter().copied(),
+            clamped.iter().copied().map(Existing),
+        );
+        let delta_sum_sq = gate.mul(ctx, delta_sum, delta_sum);
+
+        // Squares are non-negative, so they can be scaled with the unsigned path.
+        let delta_sq_sum = self.scale(ctx, delta_sq_sum).0;
+        let delta_sum_sq = self.scale(ctx, delta_sum_sq).0;
+
+        let len = ticks.len() as f64;
+
+        let n_inv = ctx.load_constant(self.quantization(1f64 / len));
+        let n1_inv = ctx.load_constant(self.quantization(1f64 / (len - 1f64)));
+
+        let delta_sum_sq_div_n = self.mul(ctx, delta_sum_sq, n_inv);
+
         let delta = self.sub(ctx, delta_sq_sum, delta_sum_sq_div_n);
 
         self.mul(ctx, delta, n1_inv)
     }
+
+    /// Constrains `a` to be non-zero, returning `a`, or one when the constraint fails so that
+    /// the witness generation of a division by it does not panic before the proof is rejected.
+    fn assert_non_zero(&self, ctx: &mut Context<F>, a: AssignedValue<F>) -> AssignedValue<F> {
+        let gate = &self.range.gate;
+        let is_zero = gate.is_zero(ctx, a);
+        gate.assert_is_const(ctx, &is_zero, &F::ZERO);
+        gate.select(ctx, Constant(self.constants.quantization_scale), a, is_zero)
+    }
+
+    /// Calculates the weighted variance of the tick returns, where `u_i` is the return ending at
+    /// tick `i` and `w_i` its weight, e.g. the liquidity of the swap:
+    /// `sum(w_i * u_i^2) / sum(w_i) - mean^2` with `mean = sum(w_i * u_i) / sum(w_i)`. Uniform
+    /// weights give the population variance of the returns, which is not `volatility`: that one
+    /// is the sample variance normalized by the number of ticks, `1 / (n - 1)` and `1 / n` for
+    /// the mean, rather than by the number of returns. The weight of the first tick starts no
+    /// return and is unused. Weights are constrained to be non-negative with a positive sum.
+    pub fn weighted_volatility<QA, QB>(
+        &self,
+        ctx: &mut Context<F>,
+        ticks: impl IntoIterator<Item = QA>,
+        weights: impl IntoIterator<Item = QB>,
+    ) -> AssignedValue<F>
+    where
+        QA: Into<QuantumCell<F>>,
+        QB: Into<QuantumCell<F>>,
+    {
+        let ticks = ticks
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<QuantumCell<F>>>();
+        let weights = weights
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<QuantumCell<F>>>();
+
+        assert_eq!(
+            ticks.len(),
+            weights.len(),
+            "ticks and weights must have the same length"
+        );
+
+        if ticks.len() < 2 {
+            return ctx.load_zero();
+        }
+
+        let fixed = FixedPointChip::<F, PRECISION_BITS>::from_range_chip(self.range.clone());
+        let gate = fixed.gate();
+
+        let weights = weights
+            .into_iter()
+            .skip(1)
+            .map(|weight| {
+                let weight = ctx.assign_region_last([weight], []);
+                let is_neg = fixed.is_neg(ctx, weight);
+                gate.assert_is_const(ctx, &is_neg, &F::ZERO);
+                Existing(weight)
+            })
+            .collect::<Vec<QuantumCell<F>>>();
+
+        let returns = ticks
+            .windows(2)
+            .map(|pair| Existing(fixed.qsub(ctx, pair[1], pair[0])))
+            .collect::<Vec<QuantumCell<F>>>();
+        let returns_sq = returns
+            .iter()
+            .map(|u| Existing(fixed.qmul(ctx, *u, *u)))
+            .collect::<Vec<QuantumCell<F>>>();
+
+        let weight_sum = fixed.qsum(ctx, weights.iter().copied());
+        let weight_sum = self.assert_non_zero(ctx, weight_sum);
+        let weighted_sum = fixed.inner_product(ctx, weights.iter().copied(), returns);
+        let weighted_sq_sum = fixed.inner_product(ctx, weights.iter().copied(), returns_sq);
+
+        let mean = fixed.qdiv(ctx, weighted_sum, weight_sum);
+        let mean_sq = fixed.qmul(ctx, mean, mean);
+        let mean_of_sq = fixed.qdiv(ctx, weighted_sq_sum, weight_sum);
+        let variance = fixed.qsub(ctx, mean_of_sq, mean_sq);
+        // Rounding can leave a tiny negative variance for constant returns
+        fixed.qmax(ctx, variance, Constant(F::ZERO))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::VolatilityInput, PRECISION};
+    use axiom_sdk::{axiom_circuit::input::flatten::InputFlatten, Fr};
+    use halo2_base::halo2_proofs::dev::MockProver;
+
+    const K: usize = 14;
+
+    type Chip = VolatilityChip<Fr, PRECISION>;
+
+    /// Dequantized output of `f` on a chip in the mock prover, and whether the constraints hold.
+    fn try_mock(f: impl FnOnce(&Chip, &mut Context<Fr>) -> AssignedValue<Fr>) -> (f64, bool) {
+        let mut builder = BaseCircuitBuilder::<Fr>::new(false)
+            .use_k(K)
+            .use_lookup_bits(K - 1);
+        let chip = Chip::new(&builder);
+        let output = f(&chip, builder.main(0));
+        let output = chip.dequantization(*output.value());
+        builder.calculate_params(Some(9));
+        let satisfied = MockProver::run(K as u32, &builder, vec![])
+            .unwrap()
+            .verify()
+            .is_ok();
+        (output, satisfied)
+    }
+
+    fn witnesses(chip: &Chip, values: &[f64]) -> Vec<QuantumCell<Fr>> {
+        chip.quantize_many(values)
+            .into_iter()
+            .map(Witness)
+            .collect()
+    }
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!(
+            (value - expected).abs() <= 1e-9 * expected.abs().max(1f64),
+            "{} differs from the expected {}",
+            value,
+            expected
+        );
+    }
+
+    const TICKS: [f64; 8] = [0.0, 12.0, 5.0, -30.0, -28.0, 40.0, 41.0, 35.0];
+
+    #[test]
+    fn constant_ticks_have_zero_volatility() {
+        let ticks = [-887272.0; 1000];
+        assert_eq!(utils::calculate_original(&ticks), 0f64);
+        assert_eq!(utils::calculate_optimized(&ticks), 0f64);
+        assert_eq!(utils::calculate_optimized(&ticks).sqrt(), 0f64);
+
+        let (output, satisfied) = try_mock(|chip, ctx| {
+            let ticks = witnesses(chip, &ticks);
+            chip.volatility(ctx, ticks)
+        });
+        assert!(satisfied);
+        assert_eq!(output, 0f64);
+        assert_eq!(output.sqrt(), 0f64);
+    }
+
+    /// `input_volatility` of the input, with its values assigned as `compute` receives them.
+    fn mock_input_volatility(input: VolatilityInput<PRECISION, 8>) -> (f64, bool) {
+        let input: VolatilityCircuitInput<Fr, PRECISION, 8> = input.into();
+        try_mock(|chip, ctx| {
+            let assigned = ctx.assign_witnesses(input.flatten_vec());
+            chip.input_volatility::<8>(ctx, VolatilityCircuitInput::unflatten(assigned).unwrap())
+        })
+    }
+
+    #[test]
+    fn input_volatility_proves_the_ticks_or_the_amounts() {
+        let amount0 = [
+            -1000.0, -2000.0, 1500.0, -800.0, -950.0, 1200.0, -3000.0, -10.0,
+        ];
+        let amount1 = [2.0, 4.5, -3.0, 1.7, 2.0, -2.6, 6.1, 0.02];
+        let input = VolatilityInput::<PRECISION, 8> {
+            ticks: TICKS.to_vec(),
+            ..Default::default()
+        };
+        let (output, satisfied) = mock_input_volatility(input.clone());
+        assert!(satisfied);
+        assert_close(output, utils::calculate_original(&TICKS));
+
+        let input = VolatilityInput {
+            amount0: Some(amount0.to_vec()),
+            amount1: Some(amount1.to_vec()),
+            ..input
+        };
+        input.validate().unwrap();
+        let (output, satisfied) = mock_input_volatility(input);
+        assert!(satisfied);
+        // qlog is accurate to about 1e-12, the returns are differences of logs
+        let expected = utils::calculate_from_amounts(&amount0, &amount1);
+        assert!(
+            (output - expected).abs() < 1e-9,
+            "{} differs from {}",
+            output,
+            expected
+        );
+    }
+
+    /// Volatility square of the ticks with every delta clamped into `[lower, upper]`.
+    fn winsorized_reference(ticks: &[f64], lower: f64, upper: f64) -> f64 {
+        let deltas: Vec<f64> = ticks
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).clamp(lower, upper))
+            .collect();
+        let n = ticks.len() as f64;
+        let delta_sum = deltas.iter().sum::<f64>();
+        let delta_sq_sum = deltas.iter().map(|delta| delta * delta).sum::<f64>();
+        (delta_sq_sum - delta_sum * delta_sum / n) / (n - 1f64)
+    }
+
+    #[test]
+    fn winsorized_volatility_clamps_the_outlier() {
+        // A single jump of 2000 among deltas within [-7, 6]
+        let ticks = [
+            0.0, 3.0, -2.0, 4.0, 1.0, 5.0, 2.0, 2002.0, 1999.0, 2005.0, 2001.0, 2003.0, 1998.0,
+            2004.0, 2000.0, 2001.0,
+        ];
+        let deltas: Vec<f64> = ticks.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        let (lower, upper) = (
+            utils::percentile(&deltas, 5f64),
+            utils::percentile(&deltas, 95f64),
+        );
+        assert_eq!(utils::median(&deltas), 1.0);
+        assert!(lower > -7.0 && upper < 2000.0 && upper > 6.0);
+
+        let (output, satisfied) = try_mock(|chip, ctx| {
+            let ticks = witnesses(chip, &ticks);
+            chip.winsorized_volatility(ctx, ticks, 5f64, 95f64)
+        });
+        assert!(satisfied);
+        let expected = winsorized_reference(&ticks, lower, upper);
+        assert_close(output, expected);
+        assert!(expected < utils::calculate_optimized(&ticks));
+
+        // The full range clamps nothing, every delta is kept as is
+        let (output, satisfied) = try_mock(|chip, ctx| {
+            let ticks = witnesses(chip, &ticks);
+            chip.winsorized_volatility(ctx, ticks, 0f64, 100f64)
+        });
+        assert!(satisfied);
+        assert_close(output, utils::calculate_original(&ticks));
+    }
+
+    #[test]
+    fn realized_variance_is_the_sum_of_squared_deltas() {
+        let expected = TICKS
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]) * (pair[1] - pair[0]))
+            .sum::<f64>();
+        let (output, satisfied) = try_mock(|chip, ctx| {
+            let ticks = witnesses(chip, &TICKS);
+            chip.realized_variance(ctx, ticks)
+        });
+        assert!(satisfied);
+        assert_close(output, expected);
+
+        let (output, satisfied) = try_mock(|chip, ctx| {
+            let ticks = witnesses(chip, &TICKS[..1]);
+            chip.realized_variance(ctx, ticks)
+        });
+        assert!(satisfied);
+        assert_eq!(output, 0f64);
+    }
+
+    #[test]
+    fn exceeds_threshold_flips_at_the_variance() {
+        // Small ticks keep the quantized variance exact in f64, so one quantum apart is testable
+        let ticks = [0.0, 1.0, 0.0, 2.0, 1.0, 3.0, 2.0, 4.0];
+        let quantum = 2f64.powi(-(PRECISION as i32));
+        let (variance, satisfied) = try_mock(|chip, ctx| {
+            let ticks = witnesses(chip, &ticks);
+            chip.volatility(ctx, ticks)
+        });
+        assert!(satisfied);
+        assert_close(variance, utils::calculate_original(&ticks));
+
+        let reference = utils::calculate_original(&ticks);
+        for (threshold_sq, exceeds) in [
+            (variance - quantum, true),
+            (variance, false),
+            (variance + quantum, false),
+            (reference * (1f64 - 1e-9), true),
+            (reference * (1f64 + 1e-9), false),
+            (0f64, true),
+        ] {
+            let (output, satisfied) = try_mock(|chip, ctx| {
+                let ticks = witnesses(chip, &ticks);
+                chip.exceeds_threshold(ctx, ticks, threshold_sq)
+            });
+            assert!(satisfied);
+            // Booleans are read back as a single quantum
+            assert_eq!(
+                output,
+                if exceeds { quantum } else { 0f64 },
+                "threshold {}",
+                threshold_sq
+            );
+        }
+    }
+
+    #[test]
+    fn one_range_chip_serves_both_chips() {
+        let mut builder = BaseCircuitBuilder::<Fr>::new(false)
+            .use_k(K)
+            .use_lookup_bits(K - 1);
+        let range = builder.range_chip();
+        let chip = Chip::from_range_chip(range.clone());
+        let fixed = FixedPointChip::<Fr, PRECISION>::from_range_chip(range);
+        assert!(std::sync::Arc::ptr_eq(
+            &chip.range.lookup_manager()[0].cells_to_lookup,
+            &fixed.gate.lookup_manager()[0].cells_to_lookup
+        ));
+
+        let ctx = builder.main(0);
+        let ticks = witnesses(&chip, &TICKS);
+        let variance = chip.volatility(ctx, ticks);
+        let std_dev = fixed.qsqrt(ctx, variance);
+        let std_dev = fixed.dequantization(*std_dev.value());
+
+        builder.calculate_params(Some(9));
+        MockProver::run(K as u32, &builder, vec![])
+            .unwrap()
+            .assert_satisfied();
+        let expected = utils::calculate_original(&TICKS).sqrt();
+        assert!(
+            (std_dev - expected).abs() <= 1e-6 * expected,
+            "{} differs from {}",
+            std_dev,
+            expected
+        );
+    }
+
+    #[test]
+    fn weighted_volatility_matches_the_reference() {
+        let weights = [3.0, 1.0, 0.5, 2.0, 0.0, 4.25, 1.0, 7.0];
+        let (output, satisfied) = try_mock(|chip, ctx| {
+            let ticks = witnesses(chip, &TICKS);
+            let weights = witnesses(chip, &weights);
+            chip.weighted_volatility(ctx, ticks, weights)
+        });
+        assert!(satisfied);
+        assert_close(output, utils::calculate_weighted(&TICKS, &weights));
+    }
+
+    #[test]
+    fn uniform_weights_give_the_plain_variance() {
+        let returns: Vec<f64> = TICKS.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        let variance =
+            returns.iter().map(|u| (u - mean) * (u - mean)).sum::<f64>() / returns.len() as f64;
+
+        let weights = [2.5; TICKS.len()];
+        assert_close(utils::calculate_weighted(&TICKS, &weights), variance);
+        let (output, satisfied) = try_mock(|chip, ctx| {
+            let ticks = witnesses(chip, &TICKS);
+            let weights = witnesses(chip, &weights);
+            chip.weighted_volatility(ctx, ticks, weights)
+        });
+        assert!(satisfied);
+        assert_close(output, variance);
+    }
+
+    #[test]
+    fn weighted_volatility_rejects_invalid_weights() {
+        let mut first = [0.0; TICKS.len()];
+        first[0] = 1.0;
+        let mut negative = [1.0; TICKS.len()];
+        negative[5] = -1.0;
+        for weights in [[0.0; TICKS.len()], first, negative] {
+            let (_, satisfied) = try_mock(|chip, ctx| {
+                let ticks = witnesses(chip, &TICKS);
+                let weights = witnesses(chip, &weights);
+                chip.weighted_volatility(ctx, ticks, weights)
+            });
+            assert!(!satisfied, "weights {:?}", weights);
+        }
+    }
 }
@@ -0,0 +1,52 @@
+//! TOML file of defaults for the command line, so long runs don't have to repeat every flag.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+/// Mirrors the command line arguments, every field is optional and named like its flag with
+/// underscores, e.g. `max_files = 4`. Flags given on the command line override these values.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub ticks: Option<String>,
+    pub ticks_file: Option<Vec<String>>,
+    pub watch: Option<String>,
+    pub proof: Option<bool>,
+    pub verify: Option<bool>,
+    pub memory: Option<usize>,
+    pub sample: Option<usize>,
+    pub mu: Option<f32>,
+    pub sigma: Option<f32>,
+    pub vol: Option<f32>,
+    pub drift: Option<f32>,
+    pub dt: Option<f32>,
+    pub seed: Option<u64>,
+    pub verify_in: Option<String>,
+    pub digest: Option<String>,
+    pub results: Option<String>,
+    pub sqlite: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub collapse_same_block: Option<bool>,
+    pub sort_ticks: Option<bool>,
+    pub dry_run: Option<bool>,
+    pub max_files: Option<NonZeroUsize>,
+    pub output_json: Option<String>,
+    pub block_range: Option<(u64, u64)>,
+    pub state: Option<String>,
+    pub batch: Option<NonZeroUsize>,
+    pub concurrency: Option<usize>,
+    pub quiet: Option<bool>,
+    pub verbose: Option<u8>,
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+}
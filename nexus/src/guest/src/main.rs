@@ -1,15 +1,28 @@
 #![cfg_attr(target_arch = "riscv32", no_std, no_main)]
 
-use nexus_rt::write_output;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use nexus_rt::{read_private_input, write_output};
 
 include!("data.rs"); // Include the data module
 include!("../../volatility.rs"); // Include the types module
 
 #[nexus_rt::main]
 pub fn main() {
-    
-    let v = Volatility::new(DATA);
+
+    // The ticks are compiled in, or passed as private input when DATA is empty, as by
+    // `prover::prove_batch` so that concurrent jobs share one build
+    let input: Vec<f32>;
+    let ticks = if DATA.is_empty() {
+        input = read_private_input::<Vec<f32>>().expect("Failed to read the ticks input");
+        input.as_slice()
+    } else {
+        DATA
+    };
+
+    let v = Volatility::new(ticks);
 
     write_output(&v);
     
-}
\ No newline at end of file
+}
@@ -0,0 +1,104 @@
+//! Realized volatility of uniswap ticks, computed natively or proven with the Nexus zkVM. The
+//! `host` binary is the command line over this library, which other programs can depend on.
+
+pub mod prover;
+pub mod results;
+pub mod rolling;
+pub mod state;
+pub mod swap;
+pub mod ticks;
+pub mod volatility;
+pub mod watcher;
+
+use anyhow::{bail, Result};
+use nexus_sdk::nova::seq::{Proof, PP};
+
+pub use volatility::Volatility;
+
+/// Options of `prove_volatility`.
+#[derive(Default)]
+pub struct ProveOpts<'a> {
+    /// Public parameters, loaded from or generated into `public_params.bin` when `None`
+    pub public_params: Option<&'a PP>,
+    /// zkVM memory limit in MB, the prover default when `None`
+    pub memlimit: Option<usize>,
+    /// Verify the proof, and that it commits to the ticks, before returning it
+    pub verify: bool,
+}
+
+/// Computes the volatility of the ticks natively, as the guest does, without the zkVM.
+pub fn realized_volatility(ticks: &[f32]) -> Result<Volatility> {
+    // A single tick has no delta
+    if ticks.len() < 2 {
+        bail!("At least 2 ticks are required, got {}", ticks.len());
+    }
+    Ok(Volatility::new(ticks))
+}
+
+/// Proves the volatility of the ticks. The guest is compiled with the ticks, so this takes the
+/// `src/guest` build of this crate's directory, like the command line.
+pub fn prove_volatility(ticks: &[f32], opts: ProveOpts) -> Result<Proof> {
+    realized_volatility(ticks)?;
+    let loaded;
+    let public_params = match opts.public_params {
+        Some(public_params) => public_params,
+        None => {
+            loaded = prover::get_public_parameters()?;
+            &loaded
+        }
+    };
+    prover::prove(public_params, ticks, opts.memlimit, opts.verify)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn realized_volatility_is_normalized_by_n_minus_one() {
+        let volatility = realized_volatility(&[1.0, 4.0, 2.0]).unwrap();
+
+        assert_eq!(volatility.n1_inv, 0.5);
+        // (9 + 4) / 2 - (3 - 2)^2 / 3 / 2, normalizing by 1/n gives 38/9 instead
+        assert!((volatility.s2 - 19.0 / 3.0).abs() < 1e-5, "{}", volatility.s2);
+    }
+
+    #[test]
+    fn realized_volatility_rejects_a_single_tick() {
+        assert!(realized_volatility(&[1.0]).is_err());
+    }
+
+    #[test]
+    fn volatility_matches_the_fixed_point_reference() {
+        use fixed::types::I24F40 as Fixed;
+        let ticks = [
+            200000.0, 199990.0, 200005.0, 199952.0, 199957.0, 199961.0, 199940.0,
+        ];
+        // The computation of the SP1 guest and `common::tick_volatility`, with an exact Fixed::sqrt
+        let fixed = ticks.map(Fixed::from_num::<f32>);
+        let n = Fixed::from_num(ticks.len());
+        let (n_inv_sqrt, n1_inv) = (Fixed::ONE / n.sqrt(), Fixed::ONE / (n - Fixed::ONE));
+        let (sum_u, sum_u2) =
+            fixed
+                .windows(2)
+                .fold((Fixed::ZERO, Fixed::ZERO), |(su, su2), pair| {
+                    let delta = pair[1] - pair[0];
+                    (su + delta * n_inv_sqrt, su2 + delta * delta * n1_inv)
+                });
+        let s2 = (sum_u2 - sum_u * sum_u * n1_inv).max(Fixed::ZERO);
+
+        let volatility = realized_volatility(&ticks).unwrap();
+
+        assert!((volatility.n_inv_sqrt as f64 - n_inv_sqrt.to_num::<f64>()).abs() < 1e-7);
+        let relative = volatility.s2 as f64 / s2.to_num::<f64>() - 1.0;
+        assert!(relative.abs() < 1e-5, "{} {}", volatility.s2, s2);
+        for n in [2, 3, 8192, 10000] {
+            let n_inv_sqrt = Volatility::new(&vec![0.0; n]).n_inv_sqrt as f64;
+            assert!(
+                (n_inv_sqrt * (n as f64).sqrt() - 1.0).abs() <= f32::EPSILON as f64,
+                "{}",
+                n
+            );
+        }
+    }
+}
@@ -1,24 +1,38 @@
 use clap::Parser;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
 
-mod volatility;
-mod prover;
-mod ticks;
-mod watcher;
+mod config;
 
-use ticks::TickSource;
-use prover::{get_public_parameters, run};
-use watcher::watch_directory;
+use config::Config;
+use host::state;
+use host::ticks::{time_range, SwapOptions, TickGenerator, TickSource, TimeRange, DEFAULT_MU, DEFAULT_SIGMA, STDIN_PATH};
+use host::prover::{batch_proof_path, dry_run, get_public_parameters, parse_digest, proof_output, prove_batch, run, save_proof, verify_saved_proof, RunSummary};
+use host::watcher::{prove_window, read_block_range, watch_directory};
 
 const DEFAULT_SAMPLE_SIZE:usize = 8192;
+const DEFAULT_CONCURRENCY:usize = 2;
+
+// Set by Ctrl-C, the watch loop stops once the window in flight is proven
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// A flag to specify ticks TickSource
+    /// A flag to specify ticks TickSource, `-` reads jsonl from stdin
     #[arg(short, long)]
     ticks: Option<String>,
 
+    /// Jsonl or csv file of ticks, repeat it to concatenate several files in the given order
+    #[arg(long, conflicts_with = "ticks")]
+    ticks_file: Vec<String>,
+
     /// A flag to trigger watch mode
     #[arg(short, long)]
     watch: Option<String>,
@@ -36,44 +50,623 @@ struct Args {
     memory:Option<usize>,
 
     #[arg(short, long)]
-    /// Number of ticks to sample
+    /// Number of ticks to sample, or to keep from the end of a csv file
     sample:Option<usize>,
+
+    /// Mean of the random ticks
+    #[arg(long)]
+    mu:Option<f32>,
+
+    /// Standard deviation of the random ticks, must be positive
+    #[arg(long)]
+    sigma:Option<f32>,
+
+    /// Generate random ticks as a geometric brownian motion with this volatility per unit of time
+    #[arg(long)]
+    vol:Option<f32>,
+
+    /// Drift per unit of time of the geometric brownian motion
+    #[arg(long, requires = "vol")]
+    drift:Option<f32>,
+
+    /// Time step between geometric brownian motion ticks
+    #[arg(long, requires = "vol")]
+    dt:Option<f32>,
+
+    /// Seed making random ticks reproducible
+    #[arg(long)]
+    seed:Option<u64>,
+
+    /// Verify a previously saved proof file, without compiling or executing the guest
+    #[arg(long)]
+    verify_in: Option<String>,
+
+    /// Hex digest of the ticks the proof passed to --verify-in must commit to
+    #[arg(long, requires = "verify_in")]
+    digest: Option<String>,
+
+    /// Append the volatility of each window processed in watch mode to this CSV file
+    #[arg(long)]
+    results: Option<String>,
+
+    /// Insert the volatility of each window processed in watch mode into this SQLite database
+    #[arg(long)]
+    sqlite: Option<String>,
+
+    /// Only use swaps at or after this RFC3339 time (jsonl sources)
+    #[arg(long)]
+    from: Option<String>,
+
+    /// Only use swaps before this RFC3339 time (jsonl sources)
+    #[arg(long)]
+    to: Option<String>,
+
+    /// Keep only the last of consecutive swaps in the same block (jsonl sources), all of them by default
+    #[arg(long)]
+    collapse_same_block: bool,
+
+    /// Sort the swaps by block and index before computing the deltas (jsonl sources). Out of
+    /// order swaps are reported either way
+    #[arg(long)]
+    sort_ticks: bool,
+
+    /// Compile and execute the guest without proving, to check the ticks fit in the memory limit
+    #[arg(long, conflicts_with_all = ["watch", "proof", "verify_in"])]
+    dry_run: bool,
+
+    /// Maximum number of files read into one window in watch mode, even if they cover fewer than 8192 blocks
+    #[arg(long)]
+    max_files: Option<NonZeroUsize>,
+
+    /// Write a JSON summary of the run to this file
+    #[arg(long, conflicts_with = "watch")]
+    output_json: Option<String>,
+
+    /// Prove once the swaps of blocks START to END inclusive, read from the --watch directory
+    #[arg(long, num_args = 2, value_names = ["START", "END"], requires = "watch")]
+    block_range: Option<Vec<u64>>,
+
+    /// File keeping the latest block proven in watch mode, so that a restart resumes from it
+    #[arg(long, requires = "watch")]
+    state: Option<String>,
+
+    /// Split the ticks into consecutive windows of this many ticks and prove them concurrently,
+    /// saving one proof file per window
+    #[arg(long, conflicts_with_all = ["watch", "dry_run", "verify_in"])]
+    batch: Option<NonZeroUsize>,
+
+    /// Number of windows of --batch proven at a time, 2 by default
+    #[arg(long, requires = "batch")]
+    concurrency: Option<usize>,
+
+    /// TOML file of defaults for the other flags, which override its values when given
+    #[arg(long)]
+    config: Option<String>,
+
+    /// Only log errors, printing the JSON summary of the run unless --output-json is given
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Log more, debug once and trace twice. Long only, -v is --verify
+    #[arg(long, action = clap::ArgAction::Count)]
+    verbose: u8,
+}
+
+impl Args {
+    // Fills the flags absent from the command line with the config values. A boolean flag can
+    // only be turned on from the command line, so either source enabling it enables it. A tick
+    // source or mode given on the command line replaces the config's, see `clear_overridden`.
+    // The merged flags are parsed again, so conflicting values within the config are rejected.
+    fn with_config(self, mut config: Config) -> Result<Self, clap::Error> {
+        self.clear_overridden(&mut config);
+        let args = Self {
+            ticks: self.ticks.or(config.ticks),
+            ticks_file: if self.ticks_file.is_empty() { config.ticks_file.unwrap_or_default() } else { self.ticks_file },
+            watch: self.watch.or(config.watch),
+            proof: self.proof || config.proof.unwrap_or(false),
+            verify: self.verify || config.verify.unwrap_or(false),
+            memory: self.memory.or(config.memory),
+            sample: self.sample.or(config.sample),
+            mu: self.mu.or(config.mu),
+            sigma: self.sigma.or(config.sigma),
+            vol: self.vol.or(config.vol),
+            drift: self.drift.or(config.drift),
+            dt: self.dt.or(config.dt),
+            seed: self.seed.or(config.seed),
+            verify_in: self.verify_in.or(config.verify_in),
+            digest: self.digest.or(config.digest),
+            results: self.results.or(config.results),
+            sqlite: self.sqlite.or(config.sqlite),
+            from: self.from.or(config.from),
+            to: self.to.or(config.to),
+            collapse_same_block: self.collapse_same_block || config.collapse_same_block.unwrap_or(false),
+            sort_ticks: self.sort_ticks || config.sort_ticks.unwrap_or(false),
+            dry_run: self.dry_run || config.dry_run.unwrap_or(false),
+            max_files: self.max_files.or(config.max_files),
+            output_json: self.output_json.or(config.output_json),
+            block_range: self.block_range.or(config.block_range.map(|(start, end)| vec![start, end])),
+            state: self.state.or(config.state),
+            batch: self.batch.or(config.batch),
+            concurrency: self.concurrency.or(config.concurrency),
+            config: self.config,
+            quiet: self.quiet || config.quiet.unwrap_or(false),
+            verbose: self.verbose.max(config.verbose.unwrap_or(0)),
+        };
+        Self::try_parse_from(args.to_argv())
+    }
+
+    // Drops the config values competing with the command line: its tick source when one is
+    // given, and its mode (watch, dry run, verify-in or batch) along with the values only that
+    // mode takes when another is, e.g. the state file of a watch replaced by --dry-run
+    fn clear_overridden(&self, config:&mut Config) {
+        if self.ticks.is_some() || !self.ticks_file.is_empty() {
+            config.ticks = None;
+            config.ticks_file = None;
+        }
+        if self.watch.is_some() || self.dry_run || self.verify_in.is_some() || self.batch.is_some() {
+            if self.watch.is_none() {
+                config.watch = None;
+                config.block_range = None;
+                config.state = None;
+            }
+            else {
+                config.output_json = None;
+            }
+            if !self.dry_run {
+                config.dry_run = None;
+            }
+            if self.verify_in.is_none() {
+                config.verify_in = None;
+                config.digest = None;
+            }
+            if self.batch.is_none() {
+                config.batch = None;
+                config.concurrency = None;
+            }
+        }
+        // A dry run never proves
+        if self.dry_run {
+            config.proof = None;
+        }
+        if self.quiet || self.verbose > 0 {
+            config.quiet = None;
+            config.verbose = None;
+        }
+    }
+
+    // The command line giving these arguments
+    fn to_argv(&self) -> Vec<String> {
+        let mut argv = vec![env!("CARGO_PKG_NAME").to_string()];
+        push_option(&mut argv, "ticks", &self.ticks);
+        for file in &self.ticks_file {
+            push_option(&mut argv, "ticks-file", &Some(file));
+        }
+        push_option(&mut argv, "watch", &self.watch);
+        push_flag(&mut argv, "proof", self.proof);
+        push_flag(&mut argv, "verify", self.verify);
+        push_option(&mut argv, "memory", &self.memory);
+        push_option(&mut argv, "sample", &self.sample);
+        push_option(&mut argv, "mu", &self.mu);
+        push_option(&mut argv, "sigma", &self.sigma);
+        push_option(&mut argv, "vol", &self.vol);
+        push_option(&mut argv, "drift", &self.drift);
+        push_option(&mut argv, "dt", &self.dt);
+        push_option(&mut argv, "seed", &self.seed);
+        push_option(&mut argv, "verify-in", &self.verify_in);
+        push_option(&mut argv, "digest", &self.digest);
+        push_option(&mut argv, "results", &self.results);
+        push_option(&mut argv, "sqlite", &self.sqlite);
+        push_option(&mut argv, "from", &self.from);
+        push_option(&mut argv, "to", &self.to);
+        push_flag(&mut argv, "collapse-same-block", self.collapse_same_block);
+        push_flag(&mut argv, "sort-ticks", self.sort_ticks);
+        push_flag(&mut argv, "dry-run", self.dry_run);
+        push_option(&mut argv, "max-files", &self.max_files);
+        push_option(&mut argv, "output-json", &self.output_json);
+        if let Some(block_range) = &self.block_range {
+            argv.push("--block-range".to_string());
+            argv.extend(block_range.iter().map(u64::to_string));
+        }
+        push_option(&mut argv, "state", &self.state);
+        push_option(&mut argv, "batch", &self.batch);
+        push_option(&mut argv, "concurrency", &self.concurrency);
+        push_option(&mut argv, "config", &self.config);
+        push_flag(&mut argv, "quiet", self.quiet);
+        argv.extend((0..self.verbose).map(|_| "--verbose".to_string()));
+        argv
+    }
+}
+
+// `--name=value`, so that negative numbers are not read as flags
+fn push_option<T: std::fmt::Display>(argv:&mut Vec<String>, name:&str, value:&Option<T>) {
+    if let Some(value) = value {
+        argv.push(format!("--{}={}", name, value));
+    }
+}
+
+fn push_flag(argv:&mut Vec<String>, name:&str, value:bool) {
+    if value {
+        argv.push(format!("--{}", name));
+    }
+}
+
+
+
+fn ticks_source(args:&Args, time_range:Option<TimeRange>) -> TickSource {
+    let options = SwapOptions {
+        time_range,
+        collapse_same_block: args.collapse_same_block,
+        sort: args.sort_ticks,
+    };
+    if !args.ticks_file.is_empty() {
+        let files = args.ticks_file.iter().map(PathBuf::from).collect();
+        return TickSource::Multiple(files, options);
+    }
+    match &args.ticks {
+        Some(ticks) if ticks == STDIN_PATH => TickSource::Stdin(options),
+        Some(ticks) => TickSource::Csv(ticks.into(), args.sample),
+        None => {
+            let generator = match args.vol {
+                Some(vol) => TickGenerator::Gbm {
+                    drift: args.drift.unwrap_or(0.0),
+                    vol,
+                    dt: args.dt.unwrap_or(1.0),
+                },
+                None => TickGenerator::Normal {
+                    mu: args.mu.unwrap_or(DEFAULT_MU),
+                    sigma: args.sigma.unwrap_or(DEFAULT_SIGMA),
+                },
+            };
+            TickSource::Random {
+                size: args.sample.unwrap_or(DEFAULT_SAMPLE_SIZE),
+                generator,
+                seed: args.seed,
+            }
+        }
+    }
+}
+
+// A first Ctrl-C lets the current window finish, a second one exits right away
+fn install_shutdown_handler() {
+    let result = ctrlc::set_handler(|| {
+        if SHUTDOWN.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        info!("shutting down after the current window, press Ctrl-C again to exit now");
+    });
+    if let Err(error) = result {
+        error!(%error, "failed to install the Ctrl-C handler");
+    }
+}
+
+// The block to resume watching from, 0 when there is no state or it can't be read
+fn saved_latest_block(state_path:Option<&str>) -> u64 {
+    let Some(path) = state_path else {
+        return 0;
+    };
+    match state::load_latest_block(path) {
+        Ok(Some(block)) => {
+            info!(block, path, "resuming from the saved latest block");
+            block
+        }
+        Ok(None) => 0,
+        Err(error) => {
+            warn!(error = format!("{:#}", error), "starting from block 0");
+            0
+        }
+    }
 }
 
+// Watches from `latest_block` until `shutdown` is set, checked between iterations so that the
+// window in flight is always finished. Every new latest block is saved to the state file when
+// given, errors are logged and the directory watched again. Returns the latest block.
+fn watch_until_shutdown(
+    shutdown:&AtomicBool,
+    mut latest_block:u64,
+    state_path:Option<&str>,
+    mut watch:impl FnMut(u64) -> anyhow::Result<u64>,
+) -> u64 {
+    while !shutdown.load(Ordering::SeqCst) {
+        match watch(latest_block) {
+            Ok(block) => {
+                latest_block = block;
+                info!(block, "latest block");
+                if let Some(path) = state_path {
+                    if let Err(error) = state::save_latest_block(path, block) {
+                        error!(error = format!("{:#}", error), "failed to save the latest block");
+                    }
+                }
+            }
+            Err(error) => error!(%error, "error loading and proving"),
+        }
+    }
+    latest_block
+}
+
+// Default log level of the --quiet and --verbose flags
+fn log_level(quiet:bool, verbose:u8) -> &'static str {
+    match (quiet, verbose) {
+        (true, _) => "error",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    }
+}
 
+// Writes the summary to --output-json when given. Otherwise --quiet prints it as a JSON line on
+// `stdout`, where the logs filtered down to errors leave it the only output.
+fn write_summary<W:Write>(summary:&RunSummary, output_json:Option<&str>, quiet:bool, stdout:&mut W) -> anyhow::Result<()> {
+    match output_json {
+        Some(path) => summary.write(path),
+        None if quiet => Ok(writeln!(stdout, "{}", serde_json::to_string(summary)?)?),
+        None => Ok(()),
+    }
+}
 
 fn main() {
     let args = Args::parse();
+    let args = match args.config.as_deref().map(Config::load).transpose().unwrap() {
+        Some(config) => args.with_config(config).unwrap_or_else(|error| error.exit()),
+        None => args,
+    };
+
+    // RUST_LOG selects the levels to print, e.g. RUST_LOG=host=debug, overriding --quiet and --verbose
+    let level = log_level(args.quiet, args.verbose);
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level)))
+        .init();
+
+    let time_range = time_range(args.from.as_deref(), args.to.as_deref()).unwrap();
+
+    // No public parameters are needed when nothing is proven
+    if args.dry_run {
+        let ticks = ticks_source(&args, time_range).get_ticks().unwrap();
+        match dry_run(&ticks, args.memory) {
+            Ok(report) => info!(steps = report.steps, estimated_bytes = report.estimated_bytes, "dry run completed"),
+            Err(error) => {
+                error!(%error, "dry run failed");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
     let pp = get_public_parameters().unwrap();
 
-    match args.watch {
+    if let Some(path) = &args.verify_in {
+        let expected_digest = args.digest.as_deref().map(parse_digest).transpose().unwrap();
+        if let Err(error) = verify_saved_proof(&pp, path, expected_digest.as_ref()) {
+            error!(path = %path, %error, "proof is invalid");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    match &args.watch {
 
         // Continually read files from a dir.
         // When there are new files, load the ticks and generate a new proof using those ticks.
         // Start from the latest available block and load backwards until there are >= 8192 values for the proof.
         
+        Some(path) if args.block_range.is_some() => {
+            let block_range = args.block_range.as_deref().map(|range| (range[0], range[1])).unwrap();
+            let result = read_block_range(path, block_range, time_range, args.collapse_same_block)
+                .and_then(|ticks| prove_window(&pp, &ticks, block_range, args.memory, args.proof, args.verify, args.results.as_deref(), args.sqlite.as_deref()));
+            if let Err(error) = result {
+                error!(%error, "error loading and proving the block range");
+                std::process::exit(1);
+            }
+        }
         Some(path) => {
-            let mut latest_block = 0;
-            loop {
-                match watch_directory(&pp, &path, latest_block, args.memory,args.proof,args.verify) {
-                    Ok(block) => {
-                        latest_block = block;
-                        println!("Latest block: {}", block);
-                    }
-                    Err(error) => println!("Error loading and proving {}", error),
-                }
+            install_shutdown_handler();
+            let latest_block = saved_latest_block(args.state.as_deref());
+            let latest_block = watch_until_shutdown(&SHUTDOWN, latest_block, args.state.as_deref(), |latest_block| {
+                watch_directory(&pp, path, latest_block, args.memory,args.proof,args.verify,args.results.as_deref(),args.sqlite.as_deref(),time_range,args.max_files,args.collapse_same_block)
+            });
+            info!(latest_block, "watcher stopped");
+        }
+        None if args.batch.is_some() => {
+            let ticks = ticks_source(&args, time_range).get_ticks().unwrap();
+            let windows = ticks.chunks(args.batch.unwrap().get()).map(<[f32]>::to_vec).collect();
+            let concurrency = args.concurrency.unwrap_or(DEFAULT_CONCURRENCY);
+
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let proofs = runtime.block_on(prove_batch(Arc::new(pp), windows, concurrency, args.memory, args.verify)).unwrap();
+            for (index, proof) in proofs.iter().enumerate() {
+                let path = batch_proof_path(index);
+                save_proof(proof, &path).unwrap();
+                let output = proof_output(proof).unwrap();
+                info!(window = index, n = output.n, s2 = output.s2, path, "window proven");
             }
         }
         None => {
-            let ticks_source = match args.ticks {
-                Some(ticks) => TickSource::Csv(ticks.into()),
-                None => TickSource::Random(args.sample.unwrap_or(DEFAULT_SAMPLE_SIZE)),
-            };
+            let ticks = ticks_source(&args, time_range).get_ticks().unwrap();
 
-            let ticks = ticks_source.get_ticks().unwrap();
+            let summary = run(&pp,&ticks,args.memory,args.proof,args.verify).unwrap();
+            write_summary(&summary, args.output_json.as_deref(), args.quiet, &mut std::io::stdout()).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(argv: &[&str], config: &str) -> Result<Args, clap::Error> {
+        let args =
+            Args::try_parse_from(std::iter::once("host").chain(argv.iter().copied())).unwrap();
+        args.with_config(toml::from_str(config).unwrap())
+    }
+
+    #[test]
+    fn config_values_fill_the_absent_flags() {
+        let args = parse(
+            &["--proof"],
+            "memory = 16\nmu = -2.5\nblock_range = [10, 20]\nwatch = \"swaps\"",
+        )
+        .unwrap();
+
+        assert_eq!(args.memory, Some(16));
+        assert_eq!(args.mu, Some(-2.5));
+        assert_eq!(args.block_range, Some(vec![10, 20]));
+        assert_eq!(args.watch.as_deref(), Some("swaps"));
+        assert!(args.proof);
+    }
+
+    #[test]
+    fn flags_override_config_values() {
+        let args = parse(
+            &["--memory", "32", "--ticks", "ticks.csv"],
+            "memory = 16\nticks = \"other.csv\"",
+        )
+        .unwrap();
+
+        assert_eq!(args.memory, Some(32));
+        assert_eq!(args.ticks.as_deref(), Some("ticks.csv"));
+    }
+
+    #[test]
+    fn flags_replace_the_conflicting_config_values() {
+        let args = parse(
+            &["--dry-run"],
+            "watch = \"swaps\"\nstate = \"state.json\"\nproof = true\nmemory = 16",
+        )
+        .unwrap();
+        assert!(args.dry_run && !args.proof);
+        assert!(args.watch.is_none() && args.state.is_none());
+        assert_eq!(args.memory, Some(16));
+
+        let args = parse(&["--ticks-file", "a.jsonl"], "ticks = \"b.csv\"").unwrap();
+        assert!(args.ticks.is_none());
+        assert_eq!(args.ticks_file, ["a.jsonl"]);
+        let args = parse(&["--ticks", "b.csv"], "ticks_file = [\"a.jsonl\"]").unwrap();
+        assert_eq!(args.ticks.as_deref(), Some("b.csv"));
+        assert!(args.ticks_file.is_empty());
+
+        let config = "batch = 4\nconcurrency = 3\nverify_in = \"proof.json\"\ndigest = \"00\"\noutput_json = \"run.json\"";
+        let args = parse(&["--watch", "swaps"], config).unwrap();
+        assert_eq!(args.watch.as_deref(), Some("swaps"));
+        assert!(args.batch.is_none() && args.concurrency.is_none());
+        assert!(args.verify_in.is_none() && args.digest.is_none());
+        assert!(args.output_json.is_none());
+
+        let args = parse(
+            &["--verify-in", "proof.json"],
+            "dry_run = true\nblock_range = [10, 20]",
+        )
+        .unwrap();
+        assert!(!args.dry_run && args.block_range.is_none());
+
+        let args = parse(&["--quiet"], "verbose = 2").unwrap();
+        assert_eq!((args.quiet, args.verbose), (true, 0));
+    }
 
-            run(&pp,&ticks,args.memory,args.proof,args.verify).unwrap();
+    #[test]
+    fn conflicting_config_values_are_rejected() {
+        assert!(parse(&[], "state = \"state.json\"").is_err());
+        assert!(parse(&[], "dry_run = true\nwatch = \"swaps\"").is_err());
+        assert!(parse(
+            &["--memory", "32"],
+            "ticks = \"b.csv\"\nticks_file = [\"a.jsonl\"]"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn shutdown_flag_breaks_the_watch_loop_after_the_current_window() {
+        let shutdown = AtomicBool::new(false);
+        let state_path = std::env::temp_dir().join(format!("watch_state_{}", std::process::id()));
+        let mut windows = 0;
+
+        let latest_block =
+            watch_until_shutdown(&shutdown, 100, state_path.to_str(), |latest_block| {
+                windows += 1;
+                match windows {
+                    2 => anyhow::bail!("no new blocks"),
+                    3 => shutdown.store(true, Ordering::SeqCst),
+                    _ => {}
+                }
+                Ok(latest_block + 10)
+            });
+        let saved = state::load_latest_block(&state_path).unwrap();
+        std::fs::remove_file(&state_path).unwrap();
+
+        // The failed window is watched again, the one during which the flag is set still finishes
+        assert_eq!(windows, 3);
+        assert_eq!(latest_block, 120);
+        assert_eq!(saved, Some(120));
+        assert_eq!(
+            watch_until_shutdown(&shutdown, 7, None, |_| unreachable!()),
+            7
+        );
+    }
+
+    // Log output captured by a test subscriber
+    #[derive(Clone, Default)]
+    struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
         }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // The lines logged by a run at the level of the flags, as the prover logs its phases
+    fn logged(quiet: bool, verbose: u8) -> String {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::new(log_level(quiet, verbose)))
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            info!(elapsed_ms = 12, "prover built");
+            tracing::debug!(ticks = 3, "estimated memory");
+            error!("proof is invalid");
+        });
+        let bytes = captured.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn quiet_suppresses_the_timing_lines_but_not_the_result() {
+        let summary = RunSummary {
+            version: 1,
+            ticks: 3,
+            digest: "aa".to_string(),
+            volatility_squared: 2.25,
+            std_dev: 1.5,
+            build_ms: 12,
+            execution_ms: Some(3),
+            prove_ms: None,
+            verify_ms: None,
+            proof_path: None,
+        };
+        let printed = |quiet| {
+            let mut stdout = Vec::new();
+            write_summary(&summary, None, quiet, &mut stdout).unwrap();
+            String::from_utf8(stdout).unwrap()
+        };
+
+        let quiet = logged(true, 0);
+        assert!(!quiet.contains("prover built"), "{}", quiet);
+        assert!(quiet.contains("proof is invalid"), "{}", quiet);
+        let result: serde_json::Value = serde_json::from_str(&printed(true)).unwrap();
+        assert_eq!(result["volatility_squared"], 2.25);
+
+        let default = logged(false, 0);
+        assert!(
+            default.contains("prover built") && default.contains("elapsed_ms=12"),
+            "{}",
+            default
+        );
+        assert!(!default.contains("estimated memory"), "{}", default);
+        assert!(logged(false, 1).contains("estimated memory"));
+        assert!(printed(false).is_empty());
     }
 }
@@ -1,13 +1,19 @@
 
-use anyhow::{Result, anyhow, Context};
+use anyhow::{Result, anyhow, bail, Context};
 use nexus_sdk::compile::CompileOpts;
 use nexus_sdk::nova::seq::*;
 use nexus_sdk::*;
 use views::UncheckedView;
 
+use serde::Serialize;
+use std::sync::Arc;
 use std::time::Instant;
-use std::{fs::File, path::Path};
-use std::io::Write;
+use std::{fs::File, path::{Path, PathBuf}};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use tokio::sync::Semaphore;
+use tracing::{info, info_span, warn};
+
+use crate::volatility::{digest, Volatility, VOLATILITY_VERSION};
 
 const PACKAGE_NAME: &str = "guest";
 
@@ -18,26 +24,52 @@ const DEFAULT_MEMORY_LIMIT:usize = 8;
 
 const PUBLIC_PARAMETERS_FILE: &str = "public_params.bin";
 
+pub const PROOF_FILE: &str = "proof-with-io.json";
+
+// Loads the public parameters, generating and saving them when the file is missing. A file that
+// fails to load, e.g. truncated by an interrupted save, is removed and regenerated once; if the
+// regenerated parameters can't be saved the error is returned rather than trying again.
 pub fn get_public_parameters() -> Result<PP> {
+    load_or_generate_public_parameters(Path::new(PUBLIC_PARAMETERS_FILE))
+}
 
-    println!("Setting up Nova public parameters...");
+fn load_or_generate_public_parameters(public_params_path:&Path) -> Result<PP> {
 
-    let public_params_path = Path::new(PUBLIC_PARAMETERS_FILE);
+    let _span = info_span!("setup").entered();
+
+    let path = public_params_path.display().to_string();
 
     if public_params_path.exists() {
-        println!("Public parameters file found. Loading...");
-        PP::load(public_params_path).context("failed to load parameters")
+        info!(path, "loading public parameters");
+        match PP::load(public_params_path) {
+            Ok(pp) => return Ok(pp),
+            Err(error) => {
+                warn!(path, %error, "public parameters file is corrupt, regenerating");
+                std::fs::remove_file(public_params_path).context("failed to remove corrupt parameters")?;
+            }
+        }
     }
     else {
-        println!("Public parameters file not found. Generating...");
-        let pp = PP::generate().context("failed to generate parameters")?;
-        PP::save(&pp,public_params_path).context("failed to save parameters")?;
-        Ok(pp)
+        info!(path, "public parameters file not found, generating");
     }
+    let pp = PP::generate().context("failed to generate parameters")?;
+    save_public_parameters(&pp, public_params_path)?;
+    Ok(pp)
 }
 
+// Saves to a temporary file renamed into place, so an interrupted save never leaves a truncated file
+fn save_public_parameters(pp:&PP, path:&Path) -> Result<()> {
+    let tmp_path = path.with_extension("bin.tmp");
+    PP::save(pp, &tmp_path).context("failed to save parameters")?;
+    std::fs::rename(&tmp_path, path).context("failed to move saved parameters into place")?;
+    Ok(())
+}
+
+// Written to a temporary file renamed into place, so that an interrupted run never leaves a
+// truncated data.rs breaking the next guest build
 fn write_data(ticks: &[f32]) -> Result<()> {
-    let mut f = File::create(DATA_FILE)
+    let tmp_path = format!("{}.tmp", DATA_FILE);
+    let mut f = File::create(&tmp_path)
         .map_err(|_| anyhow!("Failed to create file"))?;
 
     writeln!(f, "const DATA: &[ f32 ] = &[\n").with_context(|| format!("Failed to write ticks to file, {:?}", f))?;
@@ -46,14 +78,49 @@ fn write_data(ticks: &[f32]) -> Result<()> {
         writeln!(f,"    {:.1}f32,\n",record).with_context(|| format!("Failed to write ticks to file, {:?}", f))?;
     }
     writeln!(f, "];").with_context(|| format!("Failed to write ticks to file, {:?}", f))?;
+    f.sync_all().context("Failed to flush the ticks file")?;
+    std::fs::rename(&tmp_path, DATA_FILE).context("Failed to move the ticks file into place")?;
 
     Ok(())
 }
 
+// Puts the previous contents of a file back when dropped, or removes the file if it had none
+struct RestoreOnDrop {
+    path: PathBuf,
+    contents: Option<Vec<u8>>,
+}
+
+impl RestoreOnDrop {
+    fn save(path:&Path) -> Result<Self> {
+        let contents = match std::fs::read(path) {
+            Ok(contents) => Some(contents),
+            Err(error) if error.kind() == ErrorKind::NotFound => None,
+            Err(error) => return Err(error).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        Ok(Self { path: path.to_path_buf(), contents })
+    }
+}
+
+impl Drop for RestoreOnDrop {
+    fn drop(&mut self) {
+        let result = match &self.contents {
+            Some(contents) => {
+                let tmp_path = self.path.with_extension("restore.tmp");
+                std::fs::write(&tmp_path, contents).and_then(|_| std::fs::rename(&tmp_path, &self.path))
+            }
+            None => std::fs::remove_file(&self.path),
+        };
+        if let Err(error) = result {
+            warn!(path = %self.path.display(), %error, "failed to restore file");
+        }
+    }
+}
+
 fn compile(memlimit:Option<usize>) -> Result<Nova<Local>>{
-    println!("Compiling program {}...",PACKAGE_NAME);
-    let mut opts = CompileOpts::new(PACKAGE_NAME);
     let memlimit = memlimit.unwrap_or(DEFAULT_MEMORY_LIMIT);
+    let _span = info_span!("compile", package = PACKAGE_NAME, memlimit).entered();
+    info!("compiling guest");
+    let mut opts = CompileOpts::new(PACKAGE_NAME);
     opts.set_memlimit(memlimit); 
     let nova = nexus_sdk::nova::seq::Nova::compile(&opts)?;
     Ok(nova)
@@ -64,54 +131,492 @@ fn build(
     memlimit:Option<usize>
 ) ->  Result<Nova<Local>> {
     // Define the output directory relative to the build script's location
+    let _span = info_span!("build", ticks = ticks.len()).entered();
     write_data(ticks)?;
     compile(memlimit)
 }
 
 fn execute_and_prove(prover:Nova<Local>, public_parameters:&PP) -> Result<Proof> {
-    println!("Proving execution of vm...");
+    let _span = info_span!("prove").entered();
+    info!("proving execution of vm");
     let proof = prover.prove(public_parameters)?;
     Ok(proof)
 }
 
 fn execute(prover:Nova<Local>) -> Result<UncheckedView> {
-    println!("Executing vm...");
+    let _span = info_span!("execute").entered();
+    info!("executing vm");
     let view = prover.run()?;
     Ok(view)
 }
 
-fn verify_proof(proof:&Proof, public_parameters:&PP) -> Result<()> {
-    println!("Validating proof...");
+// The ticks are compiled into the guest as a static f32 array, which dominates its memory use
+fn estimated_memory(ticks:&[f32]) -> usize {
+    std::mem::size_of_val(ticks)
+}
+
+/// What a dry run found out about the ticks, without proving them.
+#[derive(Debug)]
+pub struct DryRunReport {
+    /// Bytes of the ticks compiled into the guest
+    pub estimated_bytes: usize,
+    /// Steps of the guest loop, one per tick delta. The SDK does not expose the cycle count of
+    /// the zkVM, which grows with these steps
+    pub steps: usize,
+    pub execution_ms: u64,
+    pub s2: f32,
+}
+
+/// Compiles the guest and executes it without proving, as a quick check that the ticks fit in
+/// the zkVM memory limit and run to completion. Fails before compiling if the ticks alone
+/// would exceed `memlimit`.
+pub fn dry_run(ticks:&[f32], memlimit:Option<usize>) -> Result<DryRunReport> {
+    let _span = info_span!("dry_run", ticks = ticks.len()).entered();
+    let memlimit = memlimit.unwrap_or(DEFAULT_MEMORY_LIMIT);
+    let estimated = estimated_memory(ticks);
+    info!(ticks = ticks.len(), estimated_bytes = estimated, memlimit_mb = memlimit, "estimated memory");
+    if estimated > memlimit * 1024 * 1024 {
+        bail!("{} ticks need about {} bytes, more than the {}MB memory limit, raise it with --memory", ticks.len(), estimated, memlimit);
+    }
+
+    let now = Instant::now();
+    let prover = build(ticks, Some(memlimit))?;
+    info!(elapsed_ms = now.elapsed().as_millis() as u64, "prover built");
+
+    let now = Instant::now();
+    let view = execute(prover)?;
+    let execution_ms = now.elapsed().as_millis() as u64;
+    info!(elapsed_ms = execution_ms, "execution completed");
+
+    let output = view.output::<Volatility>().context("failed to read execution output")?;
+    let steps = output.n.saturating_sub(1);
+    info!(n = output.n, s2 = output.s2, steps, "dry run output");
+    Ok(DryRunReport { estimated_bytes: estimated, steps, execution_ms, s2: output.s2 })
+}
+
+pub fn save_proof(proof:&Proof, path:&str) -> Result<()> {
+    let f = File::create(path).with_context(|| format!("Failed to create proof file {}", path))?;
+    serde_json::to_writer(BufWriter::new(f), proof).context("Failed to write proof")?;
+    Ok(())
+}
+
+fn load_proof(path:&str) -> Result<Proof> {
+    let f = File::open(path).with_context(|| format!("Failed to open proof file {}", path))?;
+    serde_json::from_reader(BufReader::new(f)).context("Invalid proof file")
+}
+
+// The output must be of the current VOLATILITY_VERSION. When `expected_digest` is given, the
+// digest committed by the guest must match it, binding the proof to a specific set of ticks.
+fn verify_proof(proof:&Proof, public_parameters:&PP, expected_digest:Option<&[u8; 32]>) -> Result<()> {
+    let _span = info_span!("verify").entered();
+    info!("validating proof");
     proof.verify(public_parameters).context("failed to verify proof")?;
-    println!("  Succeeded!");
+    check_output(&proof_output(proof)?, expected_digest)?;
+    info!("proof valid");
     Ok(())
 }
 
+// Checks the output committed by the guest, see `verify_proof`
+fn check_output(output:&Volatility, expected_digest:Option<&[u8; 32]>) -> Result<()> {
+    if output.version != VOLATILITY_VERSION {
+        bail!("unsupported output version {}, expected {}", output.version, VOLATILITY_VERSION);
+    }
+    if let Some(expected) = expected_digest {
+        if output.digest != *expected {
+            bail!("digest mismatch: proof commits to {}, expected {}", to_hex(&output.digest), to_hex(expected));
+        }
+    }
+    Ok(())
+}
+
+pub fn to_hex(bytes:&[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parses a 32 byte digest from hex, with or without a `0x` prefix.
+pub fn parse_digest(hex:&str) -> Result<[u8; 32]> {
+    let hex = hex.trim_start_matches("0x");
+    if hex.len() != 64 || !hex.is_ascii() {
+        bail!("digest must be 32 bytes of hex, got {:?}", hex);
+    }
+    let mut digest = [0u8; 32];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)?;
+    }
+    Ok(digest)
+}
+
+
+/// Summary of a successful run, written as JSON with `--output-json`.
+#[derive(Debug, Serialize)]
+pub struct RunSummary {
+    pub version: u8,
+    pub ticks: usize,
+    pub digest: String,
+    pub volatility_squared: f32,
+    /// Volatility, the standard deviation of the tick deltas
+    pub std_dev: f32,
+    pub build_ms: u64,
+    pub execution_ms: Option<u64>,
+    pub prove_ms: Option<u64>,
+    pub verify_ms: Option<u64>,
+    pub proof_path: Option<String>,
+}
+
+impl RunSummary {
+    fn new(ticks:usize, output:&Volatility, build_ms:u64) -> Self {
+        Self {
+            version: output.version,
+            ticks,
+            digest: to_hex(&output.digest),
+            volatility_squared: output.s2,
+            std_dev: output.s2.sqrt(),
+            build_ms,
+            execution_ms: None,
+            prove_ms: None,
+            verify_ms: None,
+            proof_path: None,
+        }
+    }
+
+    pub fn write(&self, path:&str) -> Result<()> {
+        let f = File::create(path).with_context(|| format!("Failed to create summary file {}", path))?;
+        serde_json::to_writer_pretty(BufWriter::new(f), self).context("Failed to write summary")?;
+        Ok(())
+    }
+}
 
-pub fn run(pp:&PP,ticks:&[f32],memlimit:Option<usize>,proof:bool,verify:bool) -> Result<()> {
+pub fn run(pp:&PP,ticks:&[f32],memlimit:Option<usize>,proof:bool,verify:bool) -> Result<RunSummary> {
+
+    let _span = info_span!("run", ticks = ticks.len(), proof, verify).entered();
 
     let now = Instant::now();
 
     let prover = build(ticks, memlimit)?;
 
-    println!("Prover built in {}sec.", now.elapsed().as_secs());
-
-    //let vol = Volatility::new(&ticks);
+    let build_ms = now.elapsed().as_millis() as u64;
+    info!(elapsed_ms = build_ms, "prover built");
 
     if !proof {
         let now = Instant::now();
-        let _ = execute(prover).unwrap();
-        println!("Execution completed in {}sec.", now.elapsed().as_secs());
+        let view = execute(prover)?;
+        let execution_ms = now.elapsed().as_millis() as u64;
+        info!(elapsed_ms = execution_ms, "execution completed");
+        let output = view.output::<Volatility>().context("failed to read execution output")?;
+        let mut summary = RunSummary::new(ticks.len(), &output, build_ms);
+        summary.execution_ms = Some(execution_ms);
+        Ok(summary)
     }
     else {
         let now = Instant::now();
-        let proof = execute_and_prove(prover, &pp).unwrap();
-        println!("Execution and proof generated in {}sec.", now.elapsed().as_secs());
-          if verify {
+        let proof = execute_and_prove(prover, pp)?;
+        let prove_ms = now.elapsed().as_millis() as u64;
+        info!(elapsed_ms = prove_ms, "execution and proof generated");
+        save_proof(&proof, PROOF_FILE)?;
+        let output = proof.output::<Volatility>().context("failed to read proof output")?;
+        let mut summary = RunSummary::new(ticks.len(), &output, build_ms);
+        summary.prove_ms = Some(prove_ms);
+        summary.proof_path = Some(PROOF_FILE.to_string());
+        if verify {
             let now = Instant::now();
-            verify_proof(&proof, &pp).unwrap();
-            println!("Proof verified in {}sec.", now.elapsed().as_secs());
+            verify_proof(&proof, pp, Some(&digest(ticks)))?;
+            let verify_ms = now.elapsed().as_millis() as u64;
+            info!(elapsed_ms = verify_ms, "proof verified");
+            summary.verify_ms = Some(verify_ms);
         }
+        Ok(summary)
     }
+}
+
+/// Builds the guest with the ticks and proves it, verifying the proof against their digest
+/// when `verify` is set. Unlike `run`, the proof is returned rather than saved.
+pub fn prove(pp:&PP, ticks:&[f32], memlimit:Option<usize>, verify:bool) -> Result<Proof> {
+    let _span = info_span!("prove_ticks", ticks = ticks.len(), verify).entered();
+    let prover = build(ticks, memlimit)?;
+    let proof = execute_and_prove(prover, pp)?;
+    if verify {
+        verify_proof(&proof, pp, Some(&digest(ticks)))?;
+    }
+    Ok(proof)
+}
+
+/// Verifies a previously saved proof without compiling or executing the guest,
+/// optionally checking that it was generated over ticks with the given digest.
+pub fn verify_saved_proof(pp:&PP, path:&str, expected_digest:Option<&[u8; 32]>) -> Result<()> {
+    let proof = load_proof(path)?;
+    let now = Instant::now();
+    verify_proof(&proof, pp, expected_digest)?;
+    info!(elapsed_ms = now.elapsed().as_millis() as u64, "proof verified");
     Ok(())
 }
+
+/// Proves several tick windows concurrently, running at most `concurrency` jobs at a time.
+/// Rather than building an isolated copy of the guest per job, the guest is built once with an
+/// empty `DATA_FILE` and every job passes its ticks as private input, so jobs share no generated
+/// file; each job compiles that build, a cached no-op after the first, and proves in parallel.
+/// `DATA_FILE` is restored once the batch is done, failed or not, so that it still holds the
+/// ticks of the last `run`. With `verify` every proof is checked against the digest of its
+/// window. Proofs are returned in the same order as `windows`.
+pub async fn prove_batch(
+    pp: Arc<PP>,
+    windows: Vec<Vec<f32>>,
+    concurrency: usize,
+    memlimit: Option<usize>,
+    verify: bool,
+) -> Result<Vec<Proof>> {
+    if let Some((index, window)) = windows.iter().enumerate().find(|(_, window)| window.len() < 2) {
+        bail!("Window {} has {} ticks, at least 2 are required", index, window.len());
+    }
+    let _restore_data = RestoreOnDrop::save(Path::new(DATA_FILE))?;
+    write_data(&[])?;
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+
+    let jobs: Vec<_> = windows
+        .into_iter()
+        .map(|ticks| {
+            let pp = pp.clone();
+            let semaphore = semaphore.clone();
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await?;
+                tokio::task::spawn_blocking(move || {
+                    let _span = info_span!("batch_job", ticks = ticks.len()).entered();
+                    let prover = compile(memlimit)?;
+                    info!("proving execution of vm");
+                    let proof = prover.prove_with_input::<Vec<f32>>(&pp, &ticks)?;
+                    if verify {
+                        verify_proof(&proof, &pp, Some(&digest(&ticks)))?;
+                    }
+                    Ok::<_, anyhow::Error>(proof)
+                })
+                .await?
+            })
+        })
+        .collect();
+
+    let mut proofs = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        proofs.push(job.await??);
+    }
+    Ok(proofs)
+}
+
+/// Volatility committed by the guest in the proof.
+pub fn proof_output(proof:&Proof) -> Result<Volatility> {
+    proof.output::<Volatility>().context("failed to read proof output")
+}
+
+// JSON of the guest output, here rather than in volatility.rs as the no_std guest includes it
+impl Volatility {
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    // Rejects payloads written with another VOLATILITY_VERSION, before reading their fields
+    pub fn from_json(json:&str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let version = value.get("version").and_then(serde_json::Value::as_u64);
+        if version != Some(VOLATILITY_VERSION as u64) {
+            bail!("unsupported Volatility version {:?}, expected {}", version, VOLATILITY_VERSION);
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+/// Path of the proof of window `index` of a batch.
+pub fn batch_proof_path(index:usize) -> String {
+    format!("proof-with-io-{}.json", index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volatility_json_round_trips() {
+        let volatility = Volatility::new(&[1.0, 4.0, 2.0]);
+
+        let parsed = Volatility::from_json(&volatility.to_json().unwrap()).unwrap();
+
+        assert_eq!(parsed.version, VOLATILITY_VERSION);
+        assert_eq!(parsed.n, volatility.n);
+        assert_eq!(parsed.n_inv_sqrt, volatility.n_inv_sqrt);
+        assert_eq!(parsed.n1_inv, volatility.n1_inv);
+        assert_eq!(parsed.s2, volatility.s2);
+        assert_eq!(parsed.digest, volatility.digest);
+    }
+
+    #[test]
+    fn volatility_json_rejects_other_versions() {
+        let json = Volatility::new(&[1.0, 4.0, 2.0]).to_json().unwrap();
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["version"] = (VOLATILITY_VERSION + 1).into();
+
+        let error = Volatility::from_json(&value.to_string()).err().unwrap();
+
+        assert!(error.to_string().starts_with("unsupported Volatility version"), "{}", error);
+        value.as_object_mut().unwrap().remove("version");
+        assert!(Volatility::from_json(&value.to_string()).is_err());
+    }
+
+    #[test]
+    fn output_must_commit_to_the_expected_digest() {
+        let ticks = [1.0, 4.0, 2.0];
+        let output = Volatility::new(&ticks);
+
+        check_output(&output, None).unwrap();
+        check_output(&output, Some(&digest(&ticks))).unwrap();
+        let error = check_output(&output, Some(&digest(&[1.0, 4.0]))).unwrap_err();
+        assert!(error.to_string().starts_with("digest mismatch: proof commits to"), "{}", error);
+
+        let mut other_version = Volatility::new(&ticks);
+        other_version.version += 1;
+        assert!(check_output(&other_version, Some(&digest(&ticks))).is_err());
+    }
+
+    #[test]
+    fn written_summary_holds_the_volatility() {
+        let ticks = [1.0, 4.0, 2.0];
+        let path = std::env::temp_dir().join(format!("summary_{}.json", std::process::id()));
+        let mut summary = RunSummary::new(ticks.len(), &Volatility::new(&ticks), 5);
+        summary.execution_ms = Some(7);
+
+        summary.write(path.to_str().unwrap()).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // (9 + 4) / 2 - (3 - 2)^2 / 3 / 2, as in `realized_volatility_is_normalized_by_n_minus_one`
+        let volatility = written["volatility_squared"].as_f64().unwrap();
+        assert!((volatility - 19.0 / 3.0).abs() < 1e-5, "{}", volatility);
+        let std_dev = written["std_dev"].as_f64().unwrap();
+        assert!((std_dev - (19.0f64 / 3.0).sqrt()).abs() < 1e-5, "{}", std_dev);
+        assert_eq!(written["ticks"], 3);
+        assert_eq!(written["digest"], to_hex(&digest(&ticks)));
+        assert_eq!(written["version"], VOLATILITY_VERSION);
+        assert_eq!(written["execution_ms"], 7);
+        assert!(written["proof_path"].is_null());
+    }
+
+    #[test]
+    fn dry_run_rejects_ticks_over_the_memory_limit_before_compiling() {
+        // 4 bytes per tick, over 1MB
+        let ticks = vec![0.0; 300_000];
+
+        let error = dry_run(&ticks, Some(1)).unwrap_err();
+
+        assert!(error.to_string().contains("more than the 1MB memory limit"), "{}", error);
+    }
+
+    #[test]
+    fn files_are_restored_when_dropped() {
+        let directory = std::env::temp_dir().join(format!("restore_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let existing = directory.join("data.rs");
+        let missing = directory.join("missing.rs");
+        std::fs::write(&existing, "const DATA: &[ f32 ] = &[ 1.0f32 ];").unwrap();
+
+        {
+            let _existing = RestoreOnDrop::save(&existing).unwrap();
+            let _missing = RestoreOnDrop::save(&missing).unwrap();
+            std::fs::write(&existing, "const DATA: &[ f32 ] = &[ ];").unwrap();
+            std::fs::write(&missing, "const DATA: &[ f32 ] = &[ ];").unwrap();
+        }
+        let restored = std::fs::read_to_string(&existing).unwrap();
+        let removed = !missing.exists();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(restored, "const DATA: &[ f32 ] = &[ 1.0f32 ];");
+        assert!(removed);
+    }
+
+    #[test]
+    #[ignore = "slow, compiles the guest"]
+    fn dry_run_reports_the_steps_of_a_small_window() {
+        let ticks = [1.0, 4.0, 2.0, 3.0];
+
+        let report = dry_run(&ticks, None).unwrap();
+
+        assert_eq!(report.steps, 3);
+        assert_eq!(report.estimated_bytes, 16);
+        assert_eq!(report.s2, Volatility::new(&ticks).s2);
+    }
+
+    #[test]
+    #[ignore = "slow, generates the public parameters"]
+    fn truncated_public_parameters_are_regenerated() {
+        let directory = std::env::temp_dir().join(format!("public_params_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(PUBLIC_PARAMETERS_FILE);
+        PP::save(&PP::generate().unwrap(), &path).unwrap();
+        let saved = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &saved[..saved.len() / 2]).unwrap();
+        assert!(PP::load(&path).is_err());
+
+        let regenerated = load_or_generate_public_parameters(&path);
+        let reloaded = PP::load(&path);
+        let tmp_left = path.with_extension("bin.tmp").exists();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        regenerated.unwrap();
+        reloaded.unwrap();
+        assert!(!tmp_left);
+    }
+
+    #[test]
+    #[ignore = "slow, generates the public parameters and compiles the guest"]
+    fn proving_records_a_prove_span() {
+        use std::sync::Mutex;
+        use tracing::span::{Attributes, Id};
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+        // Names of the spans created while it is the subscriber
+        struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+        impl<S: tracing::Subscriber> Layer<S> for SpanNames {
+            fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+                self.0.lock().unwrap().push(attrs.metadata().name());
+            }
+        }
+
+        let pp = PP::generate().unwrap();
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(SpanNames(names.clone()));
+
+        tracing::subscriber::with_default(subscriber, || prove(&pp, &[1.0, 4.0, 2.0], None, false)).unwrap();
+
+        assert_eq!(*names.lock().unwrap(), ["prove_ticks", "build", "compile", "prove"]);
+    }
+
+    #[test]
+    #[ignore = "slow, generates the public parameters and compiles the guest"]
+    fn proof_saved_by_run_verifies_from_its_file() {
+        let pp = PP::generate().unwrap();
+        let ticks = [1.0, 4.0, 2.0];
+
+        let summary = run(&pp, &ticks, None, true, false).unwrap();
+
+        let path = summary.proof_path.unwrap();
+        verify_saved_proof(&pp, &path, Some(&digest(&ticks))).unwrap();
+        assert!(verify_saved_proof(&pp, &path, Some(&digest(&[1.0, 4.0]))).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "slow, generates the public parameters and compiles the guest"]
+    async fn prove_batch_proves_every_window() {
+        let pp = Arc::new(PP::generate().unwrap());
+        let windows = vec![vec![1.0, 4.0, 2.0], vec![-3.0, 5.0]];
+
+        let data = std::fs::read(DATA_FILE).ok();
+
+        let proofs = prove_batch(pp.clone(), windows.clone(), 2, None, false).await.unwrap();
+
+        // The guest was built without ticks, then given back those of the last run
+        assert_eq!(std::fs::read(DATA_FILE).ok(), data);
+        assert_eq!(proofs.len(), windows.len());
+        for (proof, ticks) in proofs.iter().zip(&windows) {
+            verify_proof(proof, &pp, Some(&digest(ticks))).unwrap();
+            assert_eq!(proof_output(proof).unwrap().n, ticks.len());
+        }
+    }
+}
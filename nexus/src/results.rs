@@ -0,0 +1,215 @@
+//! Append-only CSV log and SQLite table of the volatility computed for each processed window,
+//! so repeated watcher runs build up a time series.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, ErrorCode};
+use serde::Serialize;
+use std::{fs::OpenOptions, path::Path, thread, time::Duration};
+use tracing::warn;
+
+// How long SQLite itself waits on a lock, then how many times the insert is tried again
+const SQLITE_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+const SQLITE_MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Serialize)]
+struct VolatilityRecord<'a> {
+    block_start: u64,
+    block_end: u64,
+    volatility: f32,
+    stddev: f32,
+    proof_path: Option<&'a str>,
+    digest: Option<&'a str>,
+}
+
+/// Appends one row to the results CSV, writing the header first if the file is new or empty.
+/// `volatility` is the squared volatility, `stddev` its root, as in the SQLite table.
+pub fn append_result<P: AsRef<Path>>(
+    path: P,
+    block_range: (u64, u64),
+    volatility: f32,
+    proof_path: Option<&str>,
+    digest: Option<&str>,
+) -> Result<()> {
+    let path = path.as_ref();
+    let is_new = std::fs::metadata(path).map_or(true, |metadata| metadata.len() == 0);
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open results file {:?}", path))?;
+
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(is_new)
+        .from_writer(file);
+    writer
+        .serialize(VolatilityRecord {
+            block_start: block_range.0,
+            block_end: block_range.1,
+            volatility,
+            stddev: volatility.sqrt(),
+            proof_path,
+            digest,
+        })
+        .context("Failed to write result")?;
+    writer.flush().context("Failed to flush results file")?;
+
+    Ok(())
+}
+
+/// Inserts one row into the `volatility` table of the SQLite database at `path`, creating the
+/// database and the table if absent. `volatility` is the squared volatility, `stddev` its root.
+/// A database still locked by another connection after the busy timeout is retried a few times.
+pub fn insert_result_sqlite<P: AsRef<Path>>(
+    path: P,
+    block_range: (u64, u64),
+    n: usize,
+    volatility: f32,
+    digest: &str,
+) -> Result<()> {
+    let path = path.as_ref();
+    let mut attempt = 1;
+    loop {
+        match try_insert_sqlite(path, block_range, n, volatility, digest) {
+            Err(error) if attempt < SQLITE_MAX_ATTEMPTS && is_locked(&error) => {
+                warn!(path = %path.display(), attempt, %error, "database locked, retrying");
+                thread::sleep(SQLITE_BUSY_TIMEOUT);
+                attempt += 1;
+            }
+            result => return result.with_context(|| format!("Failed to insert result into {:?}", path)),
+        }
+    }
+}
+
+fn is_locked(error: &rusqlite::Error) -> bool {
+    matches!(error.sqlite_error_code(), Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked))
+}
+
+fn try_insert_sqlite(
+    path: &Path,
+    block_range: (u64, u64),
+    n: usize,
+    volatility: f32,
+    digest: &str,
+) -> rusqlite::Result<()> {
+    let mut connection = Connection::open(path)?;
+    connection.busy_timeout(SQLITE_BUSY_TIMEOUT)?;
+    let transaction = connection.transaction()?;
+    transaction.execute(
+        "CREATE TABLE IF NOT EXISTS volatility (
+            block_start INTEGER NOT NULL,
+            block_end INTEGER NOT NULL,
+            n INTEGER NOT NULL,
+            volatility REAL NOT NULL,
+            stddev REAL NOT NULL,
+            digest TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    transaction.execute(
+        "INSERT INTO volatility (block_start, block_end, n, volatility, stddev, digest, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            block_range.0 as i64,
+            block_range.1 as i64,
+            n as i64,
+            volatility as f64,
+            volatility.sqrt() as f64,
+            digest,
+            chrono::Utc::now().to_rfc3339(),
+        ],
+    )?;
+    transaction.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_appends_give_a_two_row_csv() {
+        let directory = std::env::temp_dir().join(format!("results_csv_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("results.csv");
+        // An empty file left behind by an earlier run still gets the header
+        std::fs::write(&path, "").unwrap();
+
+        append_result(&path, (1, 100), 4.0, Some("proof_1.json"), Some("aa")).unwrap();
+        append_result(&path, (101, 200), 2.25, Some("proof_2.json"), Some("bb")).unwrap();
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let headers = reader.headers().unwrap().clone();
+        let rows = reader.records().collect::<csv::Result<Vec<_>>>().unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            headers,
+            vec![
+                "block_start",
+                "block_end",
+                "volatility",
+                "stddev",
+                "proof_path",
+                "digest"
+            ]
+        );
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0],
+            vec!["1", "100", "4.0", "2.0", "proof_1.json", "aa"]
+        );
+        assert_eq!(
+            rows[1],
+            vec!["101", "200", "2.25", "1.5", "proof_2.json", "bb"]
+        );
+    }
+
+    #[test]
+    fn two_windows_give_two_rows() {
+        let directory = std::env::temp_dir().join(format!("results_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let csv_path = directory.join("results.csv");
+        let sqlite_path = directory.join("results.db");
+
+        for (block_range, n, volatility, digest) in
+            [((1, 100), 8, 4.0, "aa"), ((101, 200), 9, 2.25, "bb")]
+        {
+            append_result(&csv_path, block_range, volatility, None, Some(digest)).unwrap();
+            insert_result_sqlite(&sqlite_path, block_range, n, volatility, digest).unwrap();
+        }
+
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        let connection = Connection::open(&sqlite_path).unwrap();
+        let mut statement = connection
+            .prepare("SELECT block_start, block_end, n, volatility, stddev, digest FROM volatility ORDER BY block_start")
+            .unwrap();
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<(i64, i64, i64, f64, f64, String)>>>()
+            .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            csv,
+            "block_start,block_end,volatility,stddev,proof_path,digest\n1,100,4.0,2.0,,aa\n101,200,2.25,1.5,,bb\n"
+        );
+        assert_eq!(
+            rows,
+            [
+                (1, 100, 8, 4.0, 2.0, "aa".to_string()),
+                (101, 200, 9, 2.25, 1.5, "bb".to_string())
+            ]
+        );
+    }
+}
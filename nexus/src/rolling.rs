@@ -0,0 +1,108 @@
+//! Volatility over a sliding window of ticks, updated as new swaps arrive instead of
+//! recomputed over the whole window.
+
+use std::collections::VecDeque;
+
+// Same statistic as Volatility::new over the ticks of the window, but the sums of the deltas
+// are kept in f64: they are updated by adding and removing, and f32 rounding errors would pile up
+// over a long running watcher instead of being reset on every window.
+pub struct RollingVolatility {
+    ticks: VecDeque<f32>,
+    capacity: usize,
+    sum_u: f64,
+    sum_u2: f64,
+}
+
+impl RollingVolatility {
+
+    // Window of the latest `capacity` ticks, at least 2 for one delta
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity >= 2, "a window needs at least 2 ticks, got {}", capacity);
+        Self {
+            ticks: VecDeque::with_capacity(capacity + 1),
+            capacity,
+            sum_u: 0.0,
+            sum_u2: 0.0,
+        }
+    }
+
+    // Number of ticks in the window
+    pub fn n(&self) -> usize {
+        self.ticks.len()
+    }
+
+    // Appends the ticks in order, dropping the oldest ones beyond the capacity
+    pub fn add_ticks(&mut self, new: &[f32]) {
+        for &tick in new {
+            if let Some(&last) = self.ticks.back() {
+                let delta = (tick - last) as f64;
+                self.sum_u += delta;
+                self.sum_u2 += delta * delta;
+            }
+            self.ticks.push_back(tick);
+
+            if self.ticks.len() > self.capacity {
+                let oldest = self.ticks.pop_front().unwrap();
+                let delta = (self.ticks[0] - oldest) as f64;
+                self.sum_u -= delta;
+                self.sum_u2 -= delta * delta;
+            }
+        }
+    }
+
+    // Squared volatility of the window, None until it holds 2 ticks
+    pub fn current_s2(&self) -> Option<f32> {
+        let n = self.ticks.len();
+        if n < 2 {
+            return None;
+        }
+        // As Volatility::new, sum_u is scaled by 1/sqrt(n) and sum_u2 by 1/(n-1)
+        let n = n as f64;
+        let s2 = (self.sum_u2 - self.sum_u * self.sum_u / n) / (n - 1.0);
+        Some(s2.max(0.0) as f32)
+    }
+
+    // Volatility of the window, the standard deviation of the tick deltas
+    pub fn current_volatility(&self) -> Option<f32> {
+        self.current_s2().map(f32::sqrt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::volatility::Volatility;
+
+    #[test]
+    fn rolling_updates_match_a_full_recompute() {
+        let ticks: Vec<f32> = (0..200)
+            .map(|i| ((i * 37) % 101) as f32 - 50.0 + (i / 10) as f32)
+            .collect();
+        let capacity = 64;
+        let mut rolling = RollingVolatility::new(capacity);
+        assert_eq!(rolling.current_s2(), None);
+
+        // Batches of every size, some larger than the window
+        let mut added = 0;
+        for size in [1, 3, 7, 64, 70, 55] {
+            rolling.add_ticks(&ticks[added..added + size]);
+            added += size;
+
+            let window = &ticks[added.saturating_sub(capacity)..added];
+            assert_eq!(rolling.n(), window.len());
+            if window.len() >= 2 {
+                let expected = Volatility::new(window).s2;
+                let s2 = rolling.current_s2().unwrap();
+                assert!(
+                    (s2 - expected).abs() <= 1e-4 * expected.max(1.0),
+                    "{} vs {} after {} ticks",
+                    s2,
+                    expected,
+                    added
+                );
+                assert_eq!(rolling.current_volatility(), Some(s2.sqrt()));
+            }
+        }
+        assert_eq!(added, ticks.len());
+    }
+}
@@ -0,0 +1,61 @@
+//! Latest block proven by the watcher, persisted with `--state` so that a restart resumes where it
+//! left off. The SP1 script includes this file by path, like `swap.rs`.
+
+use anyhow::{Context, Result};
+use std::{fs, io::ErrorKind, path::Path};
+
+/// Reads the latest block saved at `path`, `None` if no state was saved yet.
+pub fn load_latest_block<P: AsRef<Path>>(path: P) -> Result<Option<u64>> {
+    let path = path.as_ref();
+    let state = match fs::read_to_string(path) {
+        Ok(state) => state,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(error).with_context(|| format!("Failed to read {}", path.display()))
+        }
+    };
+    let block = state
+        .trim()
+        .parse()
+        .with_context(|| format!("Corrupt state file {}: {:?}", path.display(), state))?;
+    Ok(Some(block))
+}
+
+/// Saves the latest block to a temporary file renamed into place, so that an interrupted save
+/// never leaves a truncated state.
+pub fn save_latest_block<P: AsRef<Path>>(path: P, block: u64) -> Result<()> {
+    let path = path.as_ref();
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, format!("{}\n", block))
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to save {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_block_is_reloaded() {
+        let directory = std::env::temp_dir().join(format!("state_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("state.txt");
+
+        let missing = load_latest_block(&path).unwrap();
+        save_latest_block(&path, 20_000_000).unwrap();
+        let saved = load_latest_block(&path).unwrap();
+        save_latest_block(&path, 20_000_100).unwrap();
+        let overwritten = load_latest_block(&path).unwrap();
+        fs::write(&path, "20000").unwrap();
+        let hand_written = load_latest_block(&path).unwrap();
+        fs::write(&path, "not a block").unwrap();
+        let corrupt = load_latest_block(&path);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(missing, None);
+        assert_eq!(saved, Some(20_000_000));
+        assert_eq!(overwritten, Some(20_000_100));
+        assert_eq!(hand_written, Some(20_000));
+        assert!(corrupt.is_err());
+    }
+}
@@ -0,0 +1,109 @@
+//! Swap event written by the realized volatility substream, shared by the jsonl readers of the
+//! nexus and SP1 hosts. The SP1 script includes this file by path, the crates share no workspace.
+
+use anyhow::{Context, Result};
+use csv::StringRecord;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+/// Longest part of an offending row quoted in an error
+const MAX_QUOTED_CHARS: usize = 120;
+
+/// (evt_block_num, evt_index), unique for every swap event
+pub type SwapKey = (u64, u32);
+
+/// Row of a substream jsonl file. Rows have no headers and are read by position, so the
+/// columns the hosts don't use are skipped in place rather than parsed.
+#[derive(Debug, Deserialize)]
+pub struct Swap {
+    _evt_tx_hash: IgnoredAny,
+    pub evt_index: u32,
+    pub evt_block_time: String,
+    pub evt_block_num: u64,
+    _sender: [IgnoredAny; 20],
+    _recipient: [IgnoredAny; 20],
+    _amount0: IgnoredAny,
+    _amount1: IgnoredAny,
+    _sqrt_price_x96: IgnoredAny,
+    _liquidity: IgnoredAny,
+    pub tick: i64,
+}
+
+impl Swap {
+    /// Position of the swap in the chain
+    pub fn key(&self) -> SwapKey {
+        (self.evt_block_num, self.evt_index)
+    }
+
+    /// Deserializes a row of a substream jsonl file, naming its line and content on failure
+    pub fn from_record(record: &StringRecord) -> Result<Self> {
+        record.deserialize(None).with_context(|| {
+            let content = record.iter().collect::<Vec<_>>().join(",");
+            format!(
+                "Invalid swap on line {}: {}",
+                record_line(record),
+                quote(&content)
+            )
+        })
+    }
+}
+
+/// Line of the record in its file, counting from 1
+pub fn record_line(record: &StringRecord) -> u64 {
+    record.position().map_or(0, |position| position.line())
+}
+
+/// Debug quote of `content`, cut after `MAX_QUOTED_CHARS` characters
+pub fn quote(content: &str) -> String {
+    match content.char_indices().nth(MAX_QUOTED_CHARS) {
+        Some((end, _)) => format!("{:?}...", &content[..end]),
+        None => format!("{:?}", content),
+    }
+}
+
+/// Substream jsonl row of a swap, the fields of `Swap` without headers
+#[cfg(test)]
+pub fn jsonl_row(block: u64, index: u32, tick: i64) -> String {
+    let address = vec!["0"; 20].join(",");
+    format!(
+        "0x01,{},2024-06-01 00:00:00,{},{},{},-1000,2,1,1,{}\n",
+        index, block, address, address, tick
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(row: &str) -> StringRecord {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(row.as_bytes());
+        reader.records().next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn substream_row_deserializes_into_the_shared_swap() {
+        let sender = (1..=20).map(|byte| byte.to_string()).collect::<Vec<_>>();
+        let recipient = (236..=255).map(|byte| byte.to_string()).collect::<Vec<_>>();
+        let row = format!(
+            "0x9f1c6e7c3d2a5b4f8e0d1c2b3a495867f5e4d3c2b1a0f9e8d7c6b5a493827160,7,2024-06-01 12:34:56,20000001,{},{},-1500000000000000000000,3921551893,1771595571142957166518320255467520,22131443285744397311,-195340\n",
+            sender.join(","),
+            recipient.join(",")
+        );
+
+        let swap = Swap::from_record(&record(&row)).unwrap();
+
+        assert_eq!(swap.key(), (20000001, 7));
+        assert_eq!(swap.evt_block_time, "2024-06-01 12:34:56");
+        assert_eq!(swap.tick, -195340);
+
+        let short_sender = row.replacen(",1,2,", ",2,", 1);
+        let error = Swap::from_record(&record(&short_sender)).unwrap_err();
+        assert!(
+            error.to_string().starts_with("Invalid swap on line 1"),
+            "{}",
+            error
+        );
+    }
+}
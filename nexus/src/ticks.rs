@@ -1,100 +1,568 @@
-use std::{io::BufRead, path::{Path, PathBuf}};
+use std::{collections::VecDeque, io::{BufRead, Read}, mem, path::{Path, PathBuf}};
 
 use anyhow::{bail, Context, Result};
-use rand::thread_rng;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use rand_distr::{Distribution, Normal};
-use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::swap::{quote, record_line, Swap, SwapKey};
+
+/// Path given to `--ticks` to read jsonl swaps from standard input.
+pub const STDIN_PATH: &str = "-";
+
+/// Half-open `[from, to)` window of swap block times.
+pub type TimeRange = (DateTime<Utc>, DateTime<Utc>);
+
+/// Default mean of the random tick generator
+pub const DEFAULT_MU: f32 = 0.0;
+/// Default standard deviation of the random tick generator, 2^24
+pub const DEFAULT_SIGMA: f32 = 16777216.0;
+
+/// ln(1.0001), a uniswap tick is log base 1.0001 of the price
+const LN_TICK_BASE: f64 = 0.000099995000333308;
+
+/// Selection and ordering of the swaps of the jsonl sources
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SwapOptions {
+    /// Only swaps whose block time falls in the range
+    pub time_range: Option<TimeRange>,
+    /// Keep the last of consecutive swaps in the same block
+    pub collapse_same_block: bool,
+    /// Order the swaps by block then index before computing deltas, kept as read otherwise
+    pub sort: bool,
+}
+
+/// Distribution of randomly generated ticks
+#[derive(Clone, Copy, Debug)]
+pub enum TickGenerator {
+    /// Independent ticks drawn from a normal distribution
+    Normal { mu: f32, sigma: f32 },
+    /// Geometric brownian motion of the price: ticks are the cumulative sum of normal
+    /// log-returns with mean (drift - vol^2 / 2) * dt and standard deviation vol * sqrt(dt)
+    Gbm { drift: f32, vol: f32, dt: f32 },
+}
 
 pub enum TickSource {
-    Random(usize),
-    Jsonl(PathBuf),
-    Csv(PathBuf),
+    /// Randomly generated ticks, reproducible when a seed is given
+    Random { size: usize, generator: TickGenerator, seed: Option<u64> },
+    Jsonl(PathBuf, SwapOptions),
+    /// CSV ticks, only the last ones when a count is given
+    Csv(PathBuf, Option<usize>),
+    Stdin(SwapOptions),
+    /// Jsonl and csv files, by extension, concatenated in the given order. The swaps of
+    /// consecutive jsonl files are collapsed and sorted together
+    Multiple(Vec<PathBuf>, SwapOptions),
 }
 
 impl TickSource {
     pub fn get_ticks(&self) -> Result<Vec<f32>> {
         match &self {
-            TickSource::Random(size) => Ok(random_ticks(*size)),
-            TickSource::Jsonl(file) => read_ticks_from_jsonl(file),
-            TickSource::Csv(file) => read_ticks_from_csv(file)
+            TickSource::Random { size, generator, seed } => {
+                let mut rng = match seed {
+                    Some(seed) => StdRng::seed_from_u64(*seed),
+                    None => StdRng::from_entropy(),
+                };
+                match *generator {
+                    TickGenerator::Normal { mu, sigma } => random_ticks(&mut rng, *size, mu, sigma),
+                    TickGenerator::Gbm { drift, vol, dt } => gbm_ticks(&mut rng, *size, drift, vol, dt),
+                }
+            }
+            TickSource::Jsonl(file, options) => read_ticks_from_jsonl(file, options),
+            TickSource::Csv(file, None) => read_ticks_from_csv(file),
+            TickSource::Csv(file, Some(n)) => take_last_n(stream_ticks_csv(file)?, *n),
+            TickSource::Stdin(options) => read_ticks_from_stdin(std::io::stdin().lock(), options),
+            TickSource::Multiple(files, options) => read_ticks_from_files(files, options),
         }
     }
 }
 
-/// Generates random ticks with a normal distribution
-fn random_ticks(size:usize) -> Vec<f32> {
+/// Generates random ticks with a normal distribution of mean `mu` and standard deviation `sigma`
+fn random_ticks<R:Rng>(rng:&mut R, size:usize, mu:f32, sigma:f32) -> Result<Vec<f32>> {
 
-    println!("Generating random ticks");
+    info!(size, mu, sigma, "generating random ticks");
 
-    // Create a random number generator
-    let mut rng = thread_rng();
-
-    // Define the mean (mu) and standard deviation (sigma)
-    let mu = 0.0f32;
-    let sigma = 2.0f32.powf(24.0);
+    if sigma.is_nan() || sigma <= 0.0 {
+        bail!("sigma must be positive, got {}", sigma);
+    }
 
     // Create a Normal distribution with the specified mean and standard deviation
-    let normal = Normal::new(mu, sigma).unwrap();
-    (0..size).map(|_| normal.sample(&mut rng).round()).collect()
-}
-
-#[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-struct Swap {
-    evt_tx_hash: String,
-    evt_index: u32,
-    evt_block_time: String,
-    evt_block_num: u64,
-    sender: [u8; 20],
-    recipient: [u8; 20],
-    amount0: String,
-    amount1: String,
-    sqrt_price_x96: String,
-    liquidity: String,
-    pub tick: i64,
+    let normal = Normal::new(mu, sigma).context("Invalid normal distribution")?;
+    Ok((0..size).map(|_| normal.sample(rng).round()).collect())
+}
+
+/// Generates ticks following a geometric brownian motion of the price, starting at tick 0
+fn gbm_ticks<R:Rng>(rng:&mut R, size:usize, drift:f32, vol:f32, dt:f32) -> Result<Vec<f32>> {
+
+    info!(size, drift, vol, dt, "generating geometric brownian motion ticks");
+
+    if vol.is_nan() || vol <= 0.0 {
+        bail!("vol must be positive, got {}", vol);
+    }
+    if dt.is_nan() || dt <= 0.0 {
+        bail!("dt must be positive, got {}", dt);
+    }
+
+    let (drift, vol, dt) = (drift as f64, vol as f64, dt as f64);
+    let log_returns = Normal::new((drift - vol * vol / 2.0) * dt, vol * dt.sqrt())
+        .context("Invalid log-return distribution")?;
+
+    // Accumulate in f64 so rounding to integer ticks does not drift the walk
+    let mut log_price = 0f64;
+    Ok((0..size)
+        .map(|_| {
+            let tick = (log_price / LN_TICK_BASE).round() as f32;
+            log_price += log_returns.sample(rng);
+            tick
+        })
+        .collect())
+}
+
+/// Builds a time range from optional RFC3339 bounds, open ends extend to the earliest/latest time
+pub fn time_range(from:Option<&str>, to:Option<&str>) -> Result<Option<TimeRange>> {
+    if from.is_none() && to.is_none() {
+        return Ok(None);
+    }
+    let parse = |time:&str| -> Result<DateTime<Utc>> {
+        Ok(DateTime::parse_from_rfc3339(time)
+            .with_context(|| format!("Invalid RFC3339 time {:?}", time))?
+            .with_timezone(&Utc))
+    };
+    let from = from.map(parse).transpose()?.unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let to = to.map(parse).transpose()?.unwrap_or(DateTime::<Utc>::MAX_UTC);
+    if from >= to {
+        bail!("Empty time range, {} is not before {}", from, to);
+    }
+    Ok(Some((from, to)))
+}
+
+/// Parses `evt_block_time`, either RFC3339 or `YYYY-MM-DD HH:MM:SS[.fff][ UTC]`
+fn parse_block_time(time:&str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let naive = time.trim_end_matches(" UTC");
+    NaiveDateTime::parse_from_str(naive, "%Y-%m-%d %H:%M:%S%.f")
+        .map(|time| time.and_utc())
+        .with_context(|| format!("Unparseable evt_block_time {:?}", time))
 }
 
 /// Reads ticks from a jsonl file containing uniswap Swap events
-fn read_ticks_from_jsonl<P:AsRef<Path>>(file:P) -> Result<Vec<f32>> {
-    let file = std::fs::File::open(file)
-        .context("Failed to open jsonl file.")?;
+fn read_ticks_from_jsonl<P:AsRef<Path>>(file:P, options:&SwapOptions) -> Result<Vec<f32>> {
+    let path = file.as_ref();
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open jsonl file {}", path.display()))?;
+
+    read_ticks_from_jsonl_reader(std::io::BufReader::new(file), options)
+        .with_context(|| format!("Failed to read {}", path.display()))
+}
 
-    let reader = std::io::BufReader::new(file);
+/// Reads ticks from jsonl piped on stdin, which unlike a file must not be empty
+fn read_ticks_from_stdin<R:Read>(reader:R, options:&SwapOptions) -> Result<Vec<f32>> {
+    let ticks = read_ticks_from_jsonl_reader(reader, options).context("Failed to read ticks from stdin")?;
+    if ticks.is_empty() {
+        bail!("No ticks received on stdin");
+    }
+    Ok(ticks)
+}
 
+/// Reads ticks from any source of jsonl uniswap Swap events
+fn read_ticks_from_jsonl_reader<R:Read>(reader:R, options:&SwapOptions) -> Result<Vec<f32>> {
+    let swaps = read_swaps_from_jsonl_reader(reader, options.time_range)?;
+    let swaps = swaps.iter().map(|swap| (swap.key(), swap.tick as f32)).collect();
+    Ok(swap_ticks(swaps, options))
+}
+
+/// Reads the files in order, jsonl or csv by their extension, into one sequence of ticks.
+/// The swaps of consecutive jsonl files are collapsed together, so that with `collapse_same_block`
+/// the swaps repeated where two files overlap are kept once. The ticks of a csv file have no block.
+fn read_ticks_from_files(files:&[PathBuf], options:&SwapOptions) -> Result<Vec<f32>> {
     let mut ticks = Vec::new();
+    let mut swaps: Vec<(SwapKey, f32)> = Vec::new();
+    for file in files {
+        match file.extension().and_then(|extension| extension.to_str()) {
+            Some("jsonl") => {
+                let file_swaps = read_keyed_ticks_from_jsonl(file, options.time_range)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                swaps.extend(file_swaps);
+            }
+            Some("csv") => {
+                ticks.extend(swap_ticks(mem::take(&mut swaps), options));
+                let file_ticks = read_ticks_from_csv(file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                ticks.extend(file_ticks);
+            }
+            _ => bail!("Unknown format of {}, expected a .jsonl or .csv file", file.display()),
+        }
+    }
+    ticks.extend(swap_ticks(swaps, options));
+    Ok(ticks)
+}
+
+// Deltas are only meaningful between consecutive swaps, an out of order input is reported and
+// sorted when asked to. The sort is stable, swaps repeated by overlapping files stay adjacent.
+fn swap_ticks(mut swaps:Vec<(SwapKey, f32)>, options:&SwapOptions) -> Vec<f32> {
+    let misplaced = swaps.windows(2).filter(|pair| pair[0].0 > pair[1].0).count();
+    if misplaced > 0 {
+        if options.sort {
+            warn!(misplaced, "swaps out of chronological order, sorting them by block and index");
+            swaps.sort_by_key(|(key, _)| *key);
+        } else {
+            warn!(misplaced, "swaps out of chronological order, the volatility is unreliable, see --sort-ticks");
+        }
+    }
+    if options.collapse_same_block {
+        return collapse_same_block(swaps);
+    }
+    swaps.into_iter().map(|(_, tick)| tick).collect()
+}
+
+/// Keeps the last tick of every run of consecutive ticks from the same block. Simultaneous swaps
+/// would otherwise add zero or tiny deltas diluting the volatility. Ticks of a block that are not
+/// consecutive, e.g. unordered input, stay separate.
+pub fn collapse_same_block(ticks:impl IntoIterator<Item = (SwapKey, f32)>) -> Vec<f32> {
+    let mut collapsed: Vec<(u64, f32)> = Vec::new();
+    for ((block, _), tick) in ticks {
+        match collapsed.last_mut() {
+            Some(last) if last.0 == block => last.1 = tick,
+            _ => collapsed.push((block, tick)),
+        }
+    }
+    collapsed.into_iter().map(|(_, tick)| tick).collect()
+}
+
+/// Reads the ticks of a jsonl file keyed by the position of their swap in the chain,
+/// so that swaps repeated across overlapping files can be deduplicated
+pub fn read_keyed_ticks_from_jsonl<P:AsRef<Path>>(file:P, time_range:Option<TimeRange>) -> Result<Vec<(SwapKey, f32)>> {
+    let path = file.as_ref();
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open jsonl file {}", path.display()))?;
+
+    let swaps = read_swaps_from_jsonl_reader(std::io::BufReader::new(file), time_range)?;
+    Ok(swaps.iter().map(|swap| (swap.key(), swap.tick as f32)).collect())
+}
+
+// Only swaps whose block time falls in `time_range` are kept, when one is given.
+// Errors name the line of the offending row.
+fn read_swaps_from_jsonl_reader<R:Read>(reader:R, time_range:Option<TimeRange>) -> Result<Vec<Swap>> {
+    let mut swaps = Vec::new();
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(false)
         .from_reader(reader);
-    for result in rdr.deserialize() {
-        let swap: Swap = result.context("Invalid swap format in jsonl")?;
-        ticks.push(swap.tick as f32);
+    for record in rdr.records() {
+        let record = record.context("Failed to read jsonl row")?;
+        let swap = Swap::from_record(&record)?;
+        if let Some((from, to)) = time_range {
+            let time = parse_block_time(&swap.evt_block_time)
+                .with_context(|| format!("Invalid swap on line {}", record_line(&record)))?;
+            if time < from || time >= to {
+                continue;
+            }
+        }
+        swaps.push(swap);
     }
-    Ok(ticks)
+    Ok(swaps)
 }
 
 
 /// Read ticks from a CSV file with a single column of numbers and a header
 fn read_ticks_from_csv<P:AsRef<Path>>(file:P) -> Result<Vec<f32>> {
-    let file = std::fs::File::open(file)
-        .context("Failed to open csv file.")?;
+    stream_ticks_csv(file)?.collect()
+}
 
-    let mut reader = std::io::BufReader::new(file);
+/// Streams the ticks of a CSV file with a single column of numbers and a header,
+/// without holding more than a line of the file in memory
+pub fn stream_ticks_csv<P:AsRef<Path>>(file:P) -> Result<impl Iterator<Item = Result<f32>>> {
+    let path = file.as_ref().to_path_buf();
+    let file = std::fs::File::open(&path)
+        .with_context(|| format!("Failed to open csv file {}", path.display()))?;
 
-    let mut ticks = Vec::new();
-    let mut line = String::new();
-    // Skip the header line
-    let _ = reader.read_line(&mut line).context("Failed to skip csv header line")?;
-    line.clear();
-    while reader.read_line(&mut line).context("Failed to read csv line")? > 0 
-    {
-        if let Ok(value) = line.trim().parse::<f32>() {
-            ticks.push(value);
-        } else {
-            bail!("Invalid number in CSV");
+    // Skip the header line, line numbers count it
+    Ok(std::io::BufReader::new(file).lines().enumerate().skip(1).map(move |(index, line)| {
+        let number = index + 1;
+        let line = line.with_context(|| format!("Failed to read line {} of {}", number, path.display()))?;
+        line.trim().parse::<f32>()
+            .with_context(|| format!("Invalid number on line {} of {}: {}", number, path.display(), quote(line.trim())))
+    }))
+}
+
+/// Collects the last `n` ticks of a stream, keeping at most `n` of them in memory
+pub fn take_last_n<I:IntoIterator<Item = Result<f32>>>(ticks:I, n:usize) -> Result<Vec<f32>> {
+    let mut last = VecDeque::with_capacity(n);
+    for tick in ticks {
+        let tick = tick?;
+        if n == 0 {
+            continue;
         }
-        line.clear();
+        if last.len() == n {
+            last.pop_front();
+        }
+        last.push_back(tick);
     }
-    Ok(ticks)
+    Ok(last.into())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swap::jsonl_row as row;
+
+    // Jsonl row of the only swap of `block`, at `time`
+    fn row_at(block: u64, tick: i64, time: &str) -> String {
+        row(block, 0, tick).replace("2024-06-01 00:00:00", time)
+    }
+
+    // Writes a fixture file of the test, which removes it
+    fn fixture(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ticks_{}_{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn stdin_reads_piped_jsonl() {
+        let input = [row(1, 0, 10), row(1, 1, 12), row(2, 0, 9)].concat();
+
+        let ticks = read_ticks_from_stdin(input.as_bytes(), &SwapOptions::default()).unwrap();
+
+        assert_eq!(ticks, [10.0, 12.0, 9.0]);
+    }
+
+    #[test]
+    fn stdin_rejects_empty_input() {
+        let error = read_ticks_from_stdin(&b""[..], &SwapOptions::default()).unwrap_err();
+
+        assert_eq!(error.to_string(), "No ticks received on stdin");
+    }
+
+    #[test]
+    fn stdin_names_the_line_of_an_invalid_row() {
+        let input = [row(1, 0, 10), row(2, 0, 9).replace(",9\n", ",nine\n")].concat();
+
+        let error = read_ticks_from_stdin(input.as_bytes(), &SwapOptions::default()).unwrap_err();
+
+        assert!(format!("{:#}", error).contains("line 2"), "{:#}", error);
+    }
+
+    #[test]
+    fn from_and_to_keep_the_swaps_in_the_time_range() {
+        let input = [
+            row_at(1, 10, "2024-05-31 23:59:59"),
+            row_at(2, 11, "2024-06-01 00:00:00"),
+            row_at(3, 12, "2024-06-01T06:00:00Z"),
+            row_at(4, 13, "2024-06-01 11:59:59.999 UTC"),
+            row_at(5, 14, "2024-06-01 12:00:00"),
+        ]
+        .concat();
+        let path = fixture("time_range.jsonl", &input);
+        // Half-open, the end is 12:00 UTC
+        let range = time_range(
+            Some("2024-06-01T00:00:00Z"),
+            Some("2024-06-01T14:00:00+02:00"),
+        )
+        .unwrap();
+        let from = time_range(Some("2024-06-01T06:00:00Z"), None).unwrap();
+
+        let in_range = TickSource::Jsonl(
+            path.clone(),
+            SwapOptions {
+                time_range: range,
+                ..Default::default()
+            },
+        )
+        .get_ticks();
+        let after = TickSource::Jsonl(
+            path.clone(),
+            SwapOptions {
+                time_range: from,
+                ..Default::default()
+            },
+        )
+        .get_ticks();
+        let all = TickSource::Jsonl(path.clone(), SwapOptions::default()).get_ticks();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(in_range.unwrap(), [11.0, 12.0, 13.0]);
+        assert_eq!(after.unwrap(), [12.0, 13.0, 14.0]);
+        assert_eq!(all.unwrap().len(), 5);
+        assert_eq!(time_range(None, None).unwrap(), None);
+        assert!(time_range(Some("2024-06-01T12:00:00Z"), Some("2024-06-01T12:00:00Z")).is_err());
+        assert!(time_range(Some("2024-06-01"), None).is_err());
+    }
+
+    #[test]
+    fn random_ticks_need_a_positive_sigma() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for sigma in [0.0, -1.0, f32::NAN] {
+            let error = random_ticks(&mut rng, 10, 0.0, sigma).unwrap_err();
+            assert!(
+                error.to_string().starts_with("sigma must be positive"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn seeded_random_ticks_are_centered_on_mu() {
+        let source = TickSource::Random {
+            size: 10_000,
+            generator: TickGenerator::Normal {
+                mu: -250.0,
+                sigma: 100.0,
+            },
+            seed: Some(7),
+        };
+
+        let ticks = source.get_ticks().unwrap();
+
+        // The standard error of the mean is 1
+        let mean = ticks.iter().map(|tick| *tick as f64).sum::<f64>() / ticks.len() as f64;
+        assert!((mean + 250.0).abs() < 5.0, "{}", mean);
+        assert_eq!(ticks, source.get_ticks().unwrap());
+    }
+
+    #[test]
+    fn seeded_gbm_ticks_have_the_given_volatility() {
+        let (vol, dt) = (0.02, 0.25);
+        let source = TickSource::Random {
+            size: 10_000,
+            generator: TickGenerator::Gbm {
+                drift: 0.01,
+                vol,
+                dt,
+            },
+            seed: Some(3),
+        };
+
+        let ticks = source.get_ticks().unwrap();
+
+        // The tick deltas are log-returns in units of ln(1.0001), of standard deviation vol * sqrt(dt)
+        assert_eq!(ticks[0], 0.0);
+        let s2 = crate::realized_volatility(&ticks).unwrap().s2 as f64;
+        let realized = s2.sqrt() * LN_TICK_BASE / (dt as f64).sqrt();
+        assert!((realized / vol as f64 - 1.0).abs() < 0.03, "{}", realized);
+        assert!(gbm_ticks(&mut StdRng::seed_from_u64(3), 10, 0.0, 0.0, dt).is_err());
+        assert!(gbm_ticks(&mut StdRng::seed_from_u64(3), 10, 0.0, vol, 0.0).is_err());
+    }
+
+    #[test]
+    fn last_ticks_of_a_large_csv_are_streamed() {
+        let mut csv = String::from("tick\n");
+        for tick in 0..100_000 {
+            csv.push_str(&format!("{}\n", tick));
+        }
+        let path = fixture("large.csv", &csv);
+        // Tick 500 is on line 502, after the header
+        let invalid = fixture(
+            "large_invalid.csv",
+            &csv.replacen("\n500\n", "\nfive hundred\n", 1),
+        );
+
+        let last = TickSource::Csv(path.clone(), Some(3)).get_ticks();
+        let none = take_last_n(stream_ticks_csv(&path).unwrap(), 0);
+        let error = TickSource::Csv(invalid.clone(), Some(3))
+            .get_ticks()
+            .unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&invalid).unwrap();
+
+        assert_eq!(last.unwrap(), [99997.0, 99998.0, 99999.0]);
+        assert!(none.unwrap().is_empty());
+        assert!(
+            error.to_string().starts_with("Invalid number on line 502"),
+            "{}",
+            error
+        );
+    }
 
+    #[test]
+    fn collapsing_same_block_swaps_drops_their_deltas() {
+        let input = [
+            row(1, 0, 10),
+            row(1, 1, 12),
+            row(1, 2, 11),
+            row(2, 0, 15),
+            row(3, 0, 14),
+            row(3, 1, 20),
+        ]
+        .concat();
+        let options = SwapOptions {
+            collapse_same_block: true,
+            ..Default::default()
+        };
+
+        let ticks = read_ticks_from_stdin(input.as_bytes(), &options).unwrap();
+
+        // Three blocks give two deltas, the six swaps five
+        assert_eq!(ticks, [11.0, 15.0, 20.0]);
+        assert_eq!(ticks.windows(2).count(), 2);
+        assert_eq!(
+            read_ticks_from_stdin(input.as_bytes(), &SwapOptions::default())
+                .unwrap()
+                .windows(2)
+                .count(),
+            5
+        );
+        // Swaps of a block that are not consecutive stay separate
+        assert_eq!(
+            collapse_same_block([((1, 0), 1.0), ((2, 0), 2.0), ((1, 1), 3.0)]),
+            [1.0, 2.0, 3.0]
+        );
+    }
+
+    #[test]
+    fn multiple_files_are_concatenated_in_the_given_order() {
+        let first = fixture(
+            "multiple_first.jsonl",
+            &[row(1, 0, 10), row(2, 0, 11)].concat(),
+        );
+        let second = fixture("multiple_second.csv", "tick\n20\n21\n");
+        let third = fixture("multiple_third.jsonl", &[row(3, 0, 30)].concat());
+        let unknown = fixture("multiple_unknown.txt", "tick\n1\n");
+        let source = |files: &[&PathBuf]| {
+            let files = files.iter().map(|file| file.to_path_buf()).collect();
+            TickSource::Multiple(files, SwapOptions::default()).get_ticks()
+        };
+
+        let in_order = source(&[&first, &second, &third]);
+        let reversed = source(&[&third, &second, &first]);
+        let error = source(&[&first, &unknown]).unwrap_err();
+        for file in [&first, &second, &third, &unknown] {
+            std::fs::remove_file(file).unwrap();
+        }
+
+        assert_eq!(in_order.unwrap(), [10.0, 11.0, 20.0, 21.0, 30.0]);
+        assert_eq!(reversed.unwrap(), [30.0, 20.0, 21.0, 10.0, 11.0]);
+        assert!(
+            error.to_string().starts_with("Unknown format of"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn sorting_shuffled_swaps_restores_the_volatility() {
+        use rand::seq::SliceRandom;
+        let swaps: Vec<String> = (0..30)
+            .map(|i| row(100 + i / 3, (i % 3) as u32, (i * 37 % 11) as i64))
+            .collect();
+        let mut shuffled = swaps.clone();
+        shuffled.shuffle(&mut StdRng::seed_from_u64(5));
+        let volatility = |swaps: &[String], sort: bool| {
+            let options = SwapOptions {
+                sort,
+                ..Default::default()
+            };
+            let ticks = read_ticks_from_stdin(swaps.concat().as_bytes(), &options).unwrap();
+            crate::realized_volatility(&ticks).unwrap()
+        };
+
+        let expected = volatility(&swaps, false);
+        let sorted = volatility(&shuffled, true);
+
+        assert_eq!(sorted.s2, expected.s2);
+        assert_eq!(sorted.digest, expected.digest);
+        assert_ne!(volatility(&shuffled, false).s2, expected.s2);
+    }
+}
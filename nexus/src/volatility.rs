@@ -1,47 +1,39 @@
+use tiny_keccak::{Hasher, Sha3};
+
+// Bumped whenever the fields below change, so outputs of older guests are rejected
+// instead of deserializing into the wrong fields
+pub const VOLATILITY_VERSION: u8 = 1;
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct Volatility {
+    pub version: u8,
     pub n: usize,
     pub n_inv_sqrt: f32,
     pub n1_inv: f32,
     pub s2: f32,
+    // SHA3-256 of the ticks, binds the output to the input data
+    pub digest: [u8; 32],
 }
 
-const THREE_HALFS: f32 = 1.5;
-const WTF: u32 = 0x5f3759df;
-
-
-// See https://en.wikipedia.org/wiki/Fast_inverse_square_root
-// Originally implemented by game developer legend John Carmack in Quake III Arena
-// TT: Left the original comments for respect to the original author
-fn q_inv_sqrt(value:f32) -> f32 {
-    let mut y = value;
-    let mut i: u32;
-    let x2: f32 = value * 0.5;
-
-    // Evil bit hack
-    i = y.to_bits();
-
-    // What the f*ck
-    i = WTF - (i >> 1);
-
-    y = f32::from_bits(i);
-
-    // Newton iteration
-    y = y * (THREE_HALFS - (x2 * y * y));
-
-    y = y * (THREE_HALFS - (x2 * y * y)); // 2nd iteration, this can be removed
-    
-    y
-}
-
-
 impl Volatility {
 
+    // Builds a Volatility stamped with the current VOLATILITY_VERSION
+    pub fn current(n: usize, n_inv_sqrt: f32, n1_inv: f32, s2: f32, digest: [u8; 32]) -> Self {
+        Self {
+            version: VOLATILITY_VERSION,
+            n,
+            n_inv_sqrt,
+            n1_inv,
+            s2,
+            digest,
+        }
+    }
+
      pub fn new(ticks: &[f32]) -> Self {
         let n = ticks.len();
-        let n_inv_sqrt = q_inv_sqrt(n as f32);
-        let n1_inv = 1.0f32 / n as f32;
+        // libm as the guest is no_std, exact to f32 like the Fixed::sqrt of the SP1 and Axiom paths
+        let n_inv_sqrt = 1.0f32 / libm::sqrtf(n as f32);
+        let n1_inv = 1.0f32 / (n - 1) as f32;
         let mut ticks_prev = ticks[0];
         
         let mut sum_u = 0f32;
@@ -54,13 +46,18 @@ impl Volatility {
             sum_u2 += delta * delta * n1_inv;
         }
         
-        let s2 = sum_u2 - (sum_u * sum_u) * n1_inv;
+        // Rounding can leave a tiny negative variance for constant ticks, which sqrt turns into NaN
+        let s2 = (sum_u2 - (sum_u * sum_u) * n1_inv).max(0.0);
     
-        Self {
-            n,
-            n_inv_sqrt,
-            n1_inv,
-            s2,
-        }
+        Self::current(n, n_inv_sqrt, n1_inv, s2, digest(ticks))
     }
 }
+
+// SHA3-256 over the big-endian bytes of each tick
+pub fn digest(ticks: &[f32]) -> [u8; 32] {
+    let mut sha3 = Sha3::v256();
+    let mut output = [0u8; 32];
+    ticks.iter().for_each(|x| sha3.update(&x.to_be_bytes()));
+    sha3.finalize(&mut output);
+    output
+}
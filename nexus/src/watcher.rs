@@ -1,10 +1,16 @@
-use crate::prover::run;
-use crate::ticks::TickSource;
-use anyhow::Result;
+use crate::prover::{run, to_hex, PROOF_FILE};
+use crate::realized_volatility;
+use crate::results::{append_result, insert_result_sqlite};
+use crate::swap::SwapKey;
+use crate::ticks::{collapse_same_block, read_keyed_ticks_from_jsonl, TimeRange};
+use anyhow::{anyhow, bail, Context, Result};
 use regex::Regex;
 use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::fs;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use tracing::{info, warn};
 use nexus_sdk::nova::seq::PP;
 
 // Given a the path to a directory:
@@ -18,16 +24,52 @@ pub fn watch_directory(
     memlimit: Option<usize>,
     proof:bool,
     verify:bool,
+    results:Option<&str>,
+    sqlite:Option<&str>,
+    time_range:Option<TimeRange>,
+    max_files:Option<NonZeroUsize>,
+    collapse:bool,
 ) -> Result<u64> {
 
-    let (ticks, latest_block) = match read_latest_ticks(path, latest_block) {
+    let (ticks, block_range) = match read_latest_ticks(path, latest_block, time_range, max_files, collapse) {
         Ok(ticks) => ticks,
         Err(error) => return Err(error),
     };
+    prove_window(public_params, &ticks, block_range, memlimit, proof, verify, results, sqlite)?;
 
-    run(public_params, &ticks, memlimit, proof, verify)?;
+    Ok(block_range.1)
+}
+
+// Proves the ticks of the blocks in `block_range`, appending the volatility to `results` and
+// inserting it into the `sqlite` database when given.
+pub fn prove_window(
+    public_params:&PP,
+    ticks:&[f32],
+    block_range:(u64, u64),
+    memlimit: Option<usize>,
+    proof:bool,
+    verify:bool,
+    results:Option<&str>,
+    sqlite:Option<&str>,
+) -> Result<()> {
+    // A short window may hold too few swaps for a return
+    let volatility = realized_volatility(ticks)
+        .with_context(|| format!("No volatility for blocks {}-{}", block_range.0, block_range.1))?;
+
+    run(public_params, ticks, memlimit, proof, verify)?;
 
-    Ok(latest_block)
+    if results.is_some() || sqlite.is_some() {
+        let digest = to_hex(&volatility.digest);
+        if let Some(results) = results {
+            let proof_path = if proof { Some(PROOF_FILE) } else { None };
+            append_result(results, block_range, volatility.s2, proof_path, Some(&digest))?;
+        }
+        if let Some(sqlite) = sqlite {
+            insert_result_sqlite(sqlite, block_range, volatility.n, volatility.s2, &digest)?;
+        }
+    }
+
+    Ok(())
 }
 
 // A function to parse the .jsonl files output by the realized_volatility_substream.
@@ -46,33 +88,211 @@ fn parse_filename(filename: &str) -> Result<(u64, u64)> {
     }
 }
 
-fn read_latest_ticks(directory: &str, latest_block: u64) -> Result<(Vec<f32>, u64)> {
-    let mut files: Vec<PathBuf> = fs::read_dir(directory)?
-        .filter_map(Result::ok)
-        .map(|entry| entry.path())
-        .filter(|path| path.is_file())
-        .collect();
-
-    files.sort_by_key(|name| {
-        let (_, end_block) = parse_filename(name.to_str().expect("bad file name")).unwrap();
-        Reverse(end_block)
-    });
-    let (_, new_latest_block) = parse_filename(files[0].to_str().expect("bad file name"))?;
+// Lists the files of the directory named by their block range, latest first. Files that can't
+// be listed or don't match the substream naming are skipped with a warning.
+fn list_block_files(directory: &str) -> Result<Vec<(PathBuf, u64, u64)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(error) => {
+                warn!(%error, "skipping unreadable directory entry");
+                continue;
+            }
+        };
+        if !path.is_file() {
+            continue;
+        }
+        let range = path.to_str().ok_or_else(|| anyhow!("non UTF-8 file name")).and_then(parse_filename);
+        match range {
+            Ok((start_block, end_block)) => files.push((path, start_block, end_block)),
+            Err(error) => warn!(path = %path.display(), %error, "skipping file"),
+        }
+    }
+    files.sort_by_key(|(_, _, end_block)| Reverse(*end_block));
+    Ok(files)
+}
+
+// Reads the ticks of the swaps in blocks `start` to `end` inclusive, from the files whose block
+// range overlaps it. Unlike the latest window, a file that fails to read fails the selection.
+pub fn read_block_range(directory: &str, (start, end): (u64, u64), time_range: Option<TimeRange>, collapse: bool) -> Result<Vec<f32>> {
+    if start > end {
+        bail!("Empty block range {}-{}", start, end);
+    }
+    let mut ticks: BTreeMap<SwapKey, f32> = BTreeMap::new();
+    for (file, start_block, end_block) in list_block_files(directory)? {
+        if end_block < start || start_block > end {
+            continue;
+        }
+        let file_ticks = read_keyed_ticks_from_jsonl(&file, time_range)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        ticks.extend(file_ticks.into_iter().filter(|((block, _), _)| (start..=end).contains(block)));
+    }
+    if ticks.is_empty() {
+        bail!("No swaps in blocks {}-{} of {}", start, end, directory);
+    }
+    info!(start, end, ticks = ticks.len(), "read block range");
+    Ok(window_ticks(ticks, collapse))
+}
+
+// The tick sequence of the keyed ticks, one per block when `collapse` is set.
+fn window_ticks(ticks: BTreeMap<SwapKey, f32>, collapse: bool) -> Vec<f32> {
+    if collapse {
+        return collapse_same_block(ticks);
+    }
+    ticks.into_values().collect()
+}
+
+// Returns the ticks together with the (first, latest) block range they were read from.
+// Reads at most `max_files` files when given, even if they cover fewer than 8192 blocks.
+// Files that fail to read are skipped, it only fails when none could be read.
+fn read_latest_ticks(
+    directory: &str,
+    latest_block: u64,
+    time_range: Option<TimeRange>,
+    max_files: Option<NonZeroUsize>,
+    collapse: bool,
+) -> Result<(Vec<f32>, (u64, u64))> {
+    let files = list_block_files(directory)?;
+    let Some(&(_, _, new_latest_block)) = files.first() else {
+        bail!("No block files in {}", directory);
+    };
     if new_latest_block <= latest_block {
         return Err(anyhow::anyhow!("No new blocks"));
     }
-    println!("Latest block: {}", new_latest_block);
-    let mut ticks: Vec<f32> = Vec::new();
-    for file in files {
-        let (start_block, _) = parse_filename(file.to_str().expect("bad file name"))?;
-
-        let ticksource = TickSource::Jsonl(file);
-        let new_ticks = ticksource.get_ticks()?;
-        ticks.extend(new_ticks.into_iter());
+    info!(block = new_latest_block, "new latest block");
+    // Consecutive files can overlap at their boundaries, keying by swap position counts each
+    // swap once and keeps the ticks ordered by block then index.
+    let mut ticks: BTreeMap<SwapKey, f32> = BTreeMap::new();
+    let mut first_block = new_latest_block;
+    let mut read = 0;
+    for (file, start_block, _) in files {
+        match read_keyed_ticks_from_jsonl(&file, time_range) {
+            Ok(file_ticks) => ticks.extend(file_ticks),
+            Err(error) => {
+                warn!(path = %file.display(), error = format!("{:#}", error), "skipping unreadable file");
+                continue;
+            }
+        }
+        read += 1;
+        first_block = first_block.min(start_block);
+
         let num_blocks = new_latest_block - start_block;
         if num_blocks >= 8192 {
             break;
         };
+        if max_files.is_some_and(|max_files| read >= max_files.get()) {
+            warn!(files = read, blocks = num_blocks, "reached --max-files, the window is short");
+            break;
+        }
+    }
+    if read == 0 {
+        bail!("None of the block files in {} could be read", directory);
+    }
+    Ok((window_ticks(ticks, collapse), (first_block, new_latest_block)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swap::jsonl_row as row;
+
+    #[test]
+    fn overlapping_files_count_shared_swaps_once() {
+        let directory = std::env::temp_dir().join(format!("watcher_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        // Both files hold the two swaps of block 110
+        let earlier = [
+            row(100, 0, 1),
+            row(105, 0, 2),
+            row(110, 0, 3),
+            row(110, 1, 4),
+        ];
+        let later = [
+            row(110, 0, 3),
+            row(110, 1, 4),
+            row(115, 0, 5),
+            row(120, 0, 6),
+        ];
+        fs::write(directory.join("100-110.jsonl"), earlier.concat()).unwrap();
+        fs::write(directory.join("110-120.jsonl"), later.concat()).unwrap();
+        let path = directory.to_str().unwrap();
+
+        let latest = read_latest_ticks(path, 0, None, None, false);
+        let range = read_block_range(path, (105, 115), None, false);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            latest.unwrap(),
+            (vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0], (100, 120))
+        );
+        assert_eq!(range.unwrap(), [2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn max_files_limits_the_window_to_the_latest_files() {
+        let directory =
+            std::env::temp_dir().join(format!("watcher_max_files_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("100-109.jsonl"),
+            [row(100, 0, 1), row(105, 0, 2)].concat(),
+        )
+        .unwrap();
+        fs::write(
+            directory.join("110-119.jsonl"),
+            [row(110, 0, 3), row(115, 0, 4)].concat(),
+        )
+        .unwrap();
+        fs::write(
+            directory.join("120-129.jsonl"),
+            [row(120, 0, 5), row(125, 0, 6)].concat(),
+        )
+        .unwrap();
+        let path = directory.to_str().unwrap();
+
+        let limited = read_latest_ticks(path, 0, None, NonZeroUsize::new(2), false);
+        let all = read_latest_ticks(path, 0, None, None, false);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(limited.unwrap(), (vec![3.0, 4.0, 5.0, 6.0], (110, 129)));
+        assert_eq!(
+            all.unwrap(),
+            (vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0], (100, 129))
+        );
+    }
+
+    #[test]
+    fn malformed_files_are_skipped_by_the_latest_window() {
+        let directory =
+            std::env::temp_dir().join(format!("watcher_malformed_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("100-109.jsonl"),
+            [row(100, 0, 1), row(105, 0, 2)].concat(),
+        )
+        .unwrap();
+        // The latest file, cut in the middle of its second row
+        let malformed = [row(110, 0, 3), row(115, 0, 4)].concat();
+        fs::write(
+            directory.join("110-119.jsonl"),
+            &malformed[..malformed.len() - 20],
+        )
+        .unwrap();
+        let path = directory.to_str().unwrap();
+
+        let latest = read_latest_ticks(path, 0, None, None, false);
+        let range = read_block_range(path, (100, 119), None, false);
+        fs::remove_file(directory.join("100-109.jsonl")).unwrap();
+        let none_readable = read_latest_ticks(path, 0, None, None, false);
+        fs::remove_dir_all(&directory).unwrap();
+
+        // The window still ends at the latest block, as the files name it
+        assert_eq!(latest.unwrap(), (vec![1.0, 2.0], (100, 119)));
+        assert!(format!("{:#}", range.unwrap_err()).contains("110-119.jsonl"));
+        assert!(none_readable
+            .unwrap_err()
+            .to_string()
+            .starts_with("None of the block files"));
     }
-    Ok((ticks, new_latest_block))
 }
@@ -0,0 +1,148 @@
+// Loading of the swap rows proved by snt.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+pub const DEFAULT_POOL: &str = "usdc-weth";
+
+/// Ticks along with the pool of each.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rows {
+    pub pools: Vec<String>,
+    pub ticks: Vec<i64>,
+}
+
+impl Rows {
+    /// Whether these rows begin with all of `prefix`, as after rows are appended to a file.
+    pub fn starts_with(&self, prefix: &Rows) -> bool {
+        self.pools.starts_with(&prefix.pools) && self.ticks.starts_with(&prefix.ticks)
+    }
+
+    /// The rows after the first `start`.
+    pub fn skip(&self, start: usize) -> Rows {
+        Rows {
+            pools: self.pools[start..].to_vec(),
+            ticks: self.ticks[start..].to_vec(),
+        }
+    }
+}
+
+/// Line of a jsonl file, e.g. `{"pool": "usdc-weth", "tick": -2403526}`.
+#[derive(Deserialize)]
+struct JsonRow {
+    pool: Option<String>,
+    #[serde(alias = "ticks")]
+    tick: i64,
+}
+
+/// Reads the rows of a `.jsonl` file, of a CSV file otherwise. Rows without a pool belong to
+/// `default_pool`.
+pub fn read_rows(path: &str, default_pool: &str) -> Result<Rows> {
+    if path.ends_with(".jsonl") {
+        read_jsonl_rows(path, default_pool)
+    } else {
+        read_csv_rows(path, default_pool)
+    }
+}
+
+/// Reads a jsonl file of objects with a `tick`, or `ticks`, field and an optional `pool` field.
+/// Blank lines are skipped.
+fn read_jsonl_rows(path: &str, default_pool: &str) -> Result<Rows> {
+    let file = File::open(path).with_context(|| format!("Can not read {}", path))?;
+    let mut rows = Rows {
+        pools: Vec::new(),
+        ticks: Vec::new(),
+    };
+    for (line, content) in BufReader::new(file).lines().enumerate() {
+        let content = content?;
+        if content.trim().is_empty() {
+            continue;
+        }
+        let row: JsonRow = serde_json::from_str(&content)
+            .with_context(|| format!("Can not parse row on line {}", line + 1))?;
+        rows.ticks.push(row.tick);
+        rows.pools
+            .push(row.pool.unwrap_or_else(|| default_pool.to_string()));
+    }
+    Ok(rows)
+}
+
+/// Reads a CSV file with a header. The `pool` column is used when present, otherwise every
+/// row belongs to `default_pool`. Ticks come from the `ticks` or `tick` column, falling back
+/// to the first other column as in single column files.
+fn read_csv_rows(path: &str, default_pool: &str) -> Result<Rows> {
+    let mut reader =
+        csv::Reader::from_path(path).with_context(|| format!("Can not read {}", path))?;
+    let headers = reader.headers()?.clone();
+    let pool_index = headers.iter().position(|header| header == "pool");
+    let tick_index = headers
+        .iter()
+        .position(|header| header == "ticks" || header == "tick")
+        .or_else(|| (0..headers.len()).find(|index| Some(*index) != pool_index))
+        .ok_or_else(|| anyhow!("No tick column in {}", path))?;
+
+    let mut rows = Rows {
+        pools: Vec::new(),
+        ticks: Vec::new(),
+    };
+    for (line, record) in reader.records().enumerate() {
+        let record = record?;
+        let tick = record[tick_index]
+            .trim()
+            .parse::<i64>()
+            .with_context(|| format!("Can not parse tick on row {}", line + 1))?;
+        let pool = pool_index.map_or(default_pool, |index| &record[index]);
+        rows.ticks.push(tick);
+        rows.pools.push(pool.to_string());
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    fn read(name: &str, content: &str) -> Result<Rows> {
+        let path = env::temp_dir().join(format!("snt_{}_{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        let rows = read_rows(path.to_str().unwrap(), DEFAULT_POOL);
+        fs::remove_file(&path).unwrap();
+        rows
+    }
+
+    #[test]
+    fn csv_and_jsonl_give_the_same_pools() {
+        let csv = read("pools.csv", "pool,ticks\nusdc-weth,-3\nwbtc-weth,8\n").unwrap();
+        let jsonl = read(
+            "pools.jsonl",
+            "{\"pool\": \"usdc-weth\", \"tick\": -3}\n\n{\"pool\": \"wbtc-weth\", \"ticks\": 8}\n",
+        )
+        .unwrap();
+
+        assert_eq!(csv.pools, ["usdc-weth", "wbtc-weth"]);
+        assert_eq!(csv.ticks, [-3, 8]);
+        assert_eq!(csv, jsonl);
+    }
+
+    #[test]
+    fn rows_without_a_pool_belong_to_the_default_pool() {
+        let csv = read("default.csv", "RandomUint32\n-3\n8\n").unwrap();
+        let jsonl = read("default.jsonl", "{\"tick\": -3}\n{\"tick\": 8}\n").unwrap();
+
+        assert_eq!(csv.pools, [DEFAULT_POOL, DEFAULT_POOL]);
+        assert_eq!(csv.ticks, [-3, 8]);
+        assert_eq!(csv, jsonl);
+    }
+
+    #[test]
+    fn invalid_jsonl_names_the_line() {
+        let error = read("invalid.jsonl", "{\"tick\": -3}\n{\"pool\": \"x\"}\n").unwrap_err();
+
+        assert_eq!(error.to_string(), "Can not parse row on line 2");
+    }
+}
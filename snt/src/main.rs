@@ -1,78 +1,702 @@
+mod input;
+mod output;
+mod tail;
+
+use anyhow::Result;
 use blitzar::{compute::init_backend, proof::InnerProductProof};
+use input::{read_rows, Rows, DEFAULT_POOL};
+use output::{Format, OutputOptions, DEFAULT_MAX_WIDTH};
 use proof_of_sql::{
-    base::database::{owned_table_utility::*, OwnedTableTestAccessor, TestAccessor},
-    sql::{parse::QueryExpr, proof::QueryProof},
+    base::{
+        commitment::{CommitmentEvaluationProof, QueryCommitments, TableCommitment},
+        database::{owned_table_utility::*, OwnedTable, OwnedTableTestAccessor, TestAccessor},
+        scalar::Scalar,
+    },
+    proof_primitive::dory::{
+        DoryEvaluationProof, DoryProverPublicSetup, DoryVerifierPublicSetup, ProverSetup,
+        PublicParameters, VerifierSetup,
+    },
+    sql::{parse::QueryExpr, postprocessing::apply_postprocessing_steps, proof::QueryProof},
 };
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
-    env::args,
-    fs::File,
-    io::{stdout, BufRead, BufReader, Write},
+    env::{self, args},
+    fs::{self, File},
+    io::{stdout, BufReader, BufWriter, Write},
     iter,
-    time::Instant,
+    ops::ControlFlow,
+    path::Path,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Once,
+    },
+    time::{Instant, UNIX_EPOCH},
 };
 
+// Ticks file read without --file
 const FILE: &str = "ticks_8192.csv";
+// Table the rows are loaded into, `table` in queries
+const TABLE: &str = "sxt.table";
+
+// Dory commits to 2^nu x 2^nu matrices, 2^(2 * 7) = 16384 covers the 8192 ticks
+const DORY_MAX_NU: usize = 7;
+const DORY_SIGMA: usize = 7;
+// Fixed so that the setup, and the commitments cached against it, are the same on every run
+const DORY_SETUP_SEED: u64 = 0;
+
+// 0 with --quiet, only errors and the query result are printed, 1 by default adds the progress
+// and timings, and every -v one more level of detail
+static VERBOSITY: AtomicU8 = AtomicU8::new(1);
+
+fn verbosity() -> u8 {
+    VERBOSITY.load(Ordering::Relaxed)
+}
+
+// Device nodes of the NVIDIA driver, the only GPUs blitzar supports
+const GPU_DEVICES: [&str; 2] = ["/dev/nvidiactl", "/dev/nvidia0"];
+
+static BACKEND: Once = Once::new();
+
+/// Initializes the blitzar backend once, on the first computation that needs it rather than at
+/// startup. Without `gpu` the CPU backend is selected and blitzar initializes itself on first
+/// use, so the GPU is never touched. A GPU requested on a machine without one is reported and
+/// the process exits, instead of failing in the native library.
+fn ensure_backend(gpu: bool) {
+    BACKEND.call_once(|| {
+        if !gpu {
+            env::set_var("BLITZAR_BACKEND", "cpu");
+            return;
+        }
+        if !GPU_DEVICES.iter().any(|device| Path::new(device).exists()) {
+            eprintln!("Error: no GPU found, run with --no-gpu to use the CPU backend");
+            std::process::exit(1);
+        }
+        let timer = start_timer("Warming up GPU");
+        init_backend();
+        end_timer(timer);
+    });
+}
+
+enum Backend {
+    InnerProduct,
+    Dory,
+}
+
+impl Backend {
+    fn parse(name: &str) -> Self {
+        match name {
+            "inner-product" => Backend::InnerProduct,
+            "dory" => Backend::Dory,
+            _ => panic!("Unknown backend {}, expected inner-product or dory", name),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Backend::InnerProduct => "inner-product",
+            Backend::Dory => "dory",
+        }
+    }
+}
+
+// Version of the table layout in the commitment cache, caches of another layout are recomputed
+const CACHE_VERSION: u32 = 2;
+
+/// Table commitment saved to disk along with what identifies the data it was computed from.
+#[derive(Serialize, Deserialize)]
+struct CommitmentCache<C> {
+    version: u32,
+    modified: u128,
+    rows: usize,
+    default_pool: String,
+    commitment: C,
+}
+
+/// Loads the table commitment from `cache_path`, or computes and saves it when the cache is
+/// missing or the `source` file's modification time, row count or default pool changed since it
+/// was written.
+fn cached_table_commitment<'a, CP: CommitmentEvaluationProof>(
+    cache_path: &str,
+    source: &str,
+    table: &OwnedTable<CP::Scalar>,
+    default_pool: &str,
+    setup: &CP::ProverPublicSetup<'a>,
+) -> TableCommitment<CP::Commitment> {
+    let modified = modified_nanos(source);
+    let rows = table.num_rows();
+
+    if let Some(commitment) = load_cached_commitment(cache_path, modified, rows, default_pool) {
+        if verbosity() >= 1 {
+            println!("Using cached commitments from {}", cache_path);
+        }
+        return commitment;
+    }
+
+    let commitment = TableCommitment::from_owned_table_with_offset(table, 0, setup);
+    let cache = CommitmentCache {
+        version: CACHE_VERSION,
+        modified,
+        rows,
+        default_pool: default_pool.to_string(),
+        commitment,
+    };
+    let file = File::create(cache_path).expect("Commitment cache can not be created");
+    serde_json::to_writer(BufWriter::new(file), &cache)
+        .expect("Commitment cache can not be written");
+    cache.commitment
+}
+
+fn modified_nanos(path: &str) -> u128 {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .expect("Ticks file modification time can not be read")
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+}
+
+/// The cached commitment, when one was written for the same source and default pool.
+fn load_cached_commitment<C: DeserializeOwned>(
+    cache_path: &str,
+    modified: u128,
+    rows: usize,
+    default_pool: &str,
+) -> Option<C> {
+    let file = File::open(cache_path).ok()?;
+    serde_json::from_reader::<_, CommitmentCache<C>>(BufReader::new(file))
+        .ok()
+        .filter(|cache| {
+            cache.version == CACHE_VERSION
+                && cache.modified == modified
+                && cache.rows == rows
+                && cache.default_pool == default_pool
+        })
+        .map(|cache| cache.commitment)
+}
+
+// SUM keeps the type of its column and fails on overflow, an int128 column lets the ticks of
+// large windows be summed
+fn rows_table<S: Scalar>(rows: Rows) -> OwnedTable<S> {
+    let ticks = rows.ticks.into_iter().map(i128::from);
+    owned_table([varchar("pool", rows.pools), int128("ticks", ticks)])
+}
+
+/// Commitment to `rows` for `--tail`. When they extend the previously committed rows only the
+/// appended ones are committed to, otherwise the file was truncated or replaced and all are.
+fn tail_commitment<'a, CP: CommitmentEvaluationProof>(
+    previous: Option<(Rows, TableCommitment<CP::Commitment>)>,
+    rows: &Rows,
+    setup: &CP::ProverPublicSetup<'a>,
+) -> TableCommitment<CP::Commitment> {
+    match previous {
+        Some((committed, commitment)) if rows.starts_with(&committed) => {
+            let offset = committed.ticks.len();
+            let appended = rows_table::<CP::Scalar>(rows.skip(offset));
+            if verbosity() >= 1 {
+                println!("{} rows appended", rows.ticks.len() - offset);
+            }
+            commitment
+                .try_add(TableCommitment::from_owned_table_with_offset(
+                    &appended, offset, setup,
+                ))
+                .expect("Appended rows do not follow the committed ones")
+        }
+        previous => {
+            if previous.is_some() && verbosity() >= 1 {
+                println!("Ticks file truncated or replaced, committing to all rows");
+            }
+            TableCommitment::from_owned_table_with_offset(
+                &rows_table::<CP::Scalar>(rows.clone()),
+                0,
+                setup,
+            )
+        }
+    }
+}
 
 fn start_timer(message: &str) -> Instant {
-    print!("{}...", message);
-    stdout().flush().unwrap();
+    if verbosity() >= 1 {
+        print!("{}...", message);
+        stdout().flush().unwrap();
+    }
     Instant::now()
 }
 fn end_timer(instant: Instant) {
-    println!(" {:?}", instant.elapsed());
+    if verbosity() >= 1 {
+        println!(" {:?}", instant.elapsed());
+    }
 }
 
-fn main() {
-    let querystr = args().nth(1).expect("No arguments");
-
-    let ticks = File::open(FILE)
-        .map(|file| BufReader::new(file))
-        .map(|reader| reader.lines())
-        .expect("Ticks file can not be read")
-        .skip(1)
-        .map(|line| {
-            line.map(|value| str::parse::<i64>(&value).expect("Can not parse value"))
-                .expect("Can not read line")
+/// Command line options other than the query and the backend.
+struct Options {
+    /// CSV or jsonl file of the ticks.
+    file: String,
+    default_pool: String,
+    cache_commitments: bool,
+    tail: bool,
+    gpu: bool,
+    output: OutputOptions,
+}
+
+// With a `cache_path` the proof is verified against table commitments cached on disk instead of
+// commitments recomputed from the data. With `tail` the query is proved again on every change
+// of the ticks file, verifying against commitments extended with the appended rows.
+fn run<'a, CP: CommitmentEvaluationProof>(
+    querystr: &str,
+    options: &Options,
+    cache_path: Option<&str>,
+    prover_setup: CP::ProverPublicSetup<'a>,
+    verifier_setup: CP::VerifierPublicSetup<'a>,
+) {
+    let Options {
+        file,
+        default_pool,
+        output,
+        ..
+    } = options;
+    ensure_backend(options.gpu);
+    if options.tail {
+        let mut previous = None;
+        tail::tail(file, default_pool, |rows| {
+            let timer = start_timer("Updating commitments");
+            let commitment = tail_commitment::<CP>(previous.take(), &rows, &prover_setup);
+            end_timer(timer);
+            let table = rows_table(rows.clone());
+            prove_and_print::<CP>(
+                querystr,
+                table,
+                Some(commitment.clone()),
+                prover_setup,
+                verifier_setup,
+                output,
+            );
+            previous = Some((rows, commitment));
+            ControlFlow::Continue(())
         })
-        .collect::<Vec<_>>();
+        .expect("Ticks file can not be watched");
+        return;
+    }
 
-    let timer = start_timer("Warming up GPU");
-    init_backend();
-    end_timer(timer);
+    // Rows without a pool column in the file belong to the default pool
+    let rows = read_rows(file, default_pool).expect("Ticks file can not be read");
+    let table = rows_table(rows);
+    if verbosity() >= 2 {
+        println!("{} rows read from {}", table.num_rows(), file);
+    }
+    let commitment = cache_path.map(|cache_path| {
+        let timer = start_timer("Loading commitments");
+        let commitment =
+            cached_table_commitment::<CP>(cache_path, file, &table, default_pool, &prover_setup);
+        end_timer(timer);
+        commitment
+    });
+    prove_and_print::<CP>(
+        querystr,
+        table,
+        commitment,
+        prover_setup,
+        verifier_setup,
+        output,
+    );
+}
+
+/// Accessor holding `table` as `TABLE`, and the query parsed against it. Neither computes a
+/// commitment, so the backend is not needed yet.
+fn load_and_parse<'a, CP: CommitmentEvaluationProof>(
+    querystr: &str,
+    table: OwnedTable<CP::Scalar>,
+    prover_setup: CP::ProverPublicSetup<'a>,
+) -> (OwnedTableTestAccessor<'a, CP>, QueryExpr<CP::Commitment>) {
     let timer = start_timer("Loading data");
 
-    let mut accessor = OwnedTableTestAccessor::<InnerProductProof>::new_empty_with_setup(());
-    accessor.add_table(
-        "sxt.table".parse().unwrap(),
-        owned_table([
-            varchar("pool", iter::repeat("usdc-weth").take(8192)),
-            bigint("ticks", ticks),
-        ]),
-        0,
-    );
+    let mut accessor = OwnedTableTestAccessor::<CP>::new_empty_with_setup(prover_setup);
+    accessor.add_table(TABLE.parse().unwrap(), table, 0);
     end_timer(timer);
+
     let timer = start_timer("Parsing Query");
 
-    let mut query =
+    let query =
         QueryExpr::try_new(querystr.parse().unwrap(), "sxt".parse().unwrap(), &accessor).unwrap();
     end_timer(timer);
+    (accessor, query)
+}
+
+// With a `commitment` the proof is verified against it instead of the commitments of the
+// accessor, computed from the data.
+fn prove_and_verify<'a, CP: CommitmentEvaluationProof>(
+    querystr: &str,
+    table: OwnedTable<CP::Scalar>,
+    commitment: Option<TableCommitment<CP::Commitment>>,
+    prover_setup: CP::ProverPublicSetup<'a>,
+    verifier_setup: CP::VerifierPublicSetup<'a>,
+) -> Result<OwnedTable<CP::Scalar>> {
+    let (accessor, query) = load_and_parse::<CP>(querystr, table, prover_setup);
+    let commitments: Option<QueryCommitments<CP::Commitment>> =
+        commitment.map(|commitment| iter::once((TABLE.parse().unwrap(), commitment)).collect());
+
     let timer = start_timer("Generating Proof");
 
     let (proof, serialized_result) =
-        QueryProof::<InnerProductProof>::new(query.proof_expr(), &accessor, &());
+        QueryProof::<CP>::new(query.proof_expr(), &accessor, &prover_setup);
     end_timer(timer);
     let timer = start_timer("Verifying Proof");
 
-    let result = proof.verify(query.proof_expr(), &accessor, &serialized_result, &());
+    let result = match &commitments {
+        Some(commitments) => proof.verify(
+            query.proof_expr(),
+            commitments,
+            &serialized_result,
+            &verifier_setup,
+        ),
+        None => proof.verify(
+            query.proof_expr(),
+            &accessor,
+            &serialized_result,
+            &verifier_setup,
+        ),
+    };
     end_timer(timer);
-    match result {
-        Ok(result) => {
-            println!("Valid proof!");
-            println!("Query: {}", querystr);
-            println!("Query result: {:?}", result.table);
+    // Aggregates and orderings the proof does not cover are computed from the verified rows
+    let table = apply_postprocessing_steps(result?.table, query.postprocessing())?;
+    Ok(table)
+}
+
+fn prove_and_print<'a, CP: CommitmentEvaluationProof>(
+    querystr: &str,
+    table: OwnedTable<CP::Scalar>,
+    commitment: Option<TableCommitment<CP::Commitment>>,
+    prover_setup: CP::ProverPublicSetup<'a>,
+    verifier_setup: CP::VerifierPublicSetup<'a>,
+    output: &OutputOptions,
+) {
+    match prove_and_verify::<CP>(querystr, table, commitment, prover_setup, verifier_setup) {
+        Ok(table) => {
+            let table = output::render(&table, output).expect("Can not format result");
+            if verbosity() >= 1 {
+                println!("Valid proof!");
+                println!("Query: {}", querystr);
+                println!("Query result:\n{}", table);
+            } else {
+                println!("{}", table);
+            }
         }
         Err(e) => {
-            println!("Error: {:?}", e);
+            eprintln!("Error: {:?}", e);
+        }
+    }
+}
+
+/// Seeded random Dory parameters. Unlike the inner product proof, Dory needs a public setup, a
+/// random one is only suitable for testing, a deployment would load the output of a trusted setup.
+fn dory_public_parameters() -> PublicParameters {
+    let mut rng = StdRng::seed_from_u64(DORY_SETUP_SEED);
+    PublicParameters::rand(DORY_MAX_NU, &mut rng)
+}
+
+fn main() {
+    // Usage: snt [--backend inner-product|dory] [--file path] [--cache-commitments] [--pool name]
+    //            [--format table|pretty|csv|json] [--pretty] [--max-width n] [--columns a,b]
+    //            [--tail] [--no-gpu] [-q|--quiet] [-v...] <query>
+    let mut querystr = None;
+    let mut backend = Backend::InnerProduct;
+    let mut options = Options {
+        file: FILE.to_string(),
+        default_pool: DEFAULT_POOL.to_string(),
+        cache_commitments: false,
+        tail: false,
+        gpu: true,
+        output: OutputOptions {
+            format: Format::Table,
+            columns: None,
+            max_width: DEFAULT_MAX_WIDTH,
+        },
+    };
+    let output = &mut options.output;
+    let mut arguments = args().skip(1);
+    while let Some(arg) = arguments.next() {
+        if arg == "--backend" {
+            backend = Backend::parse(&arguments.next().expect("No backend given"));
+        } else if arg == "--file" {
+            options.file = arguments.next().expect("No file given");
+        } else if arg == "--format" {
+            output.format = Format::parse(&arguments.next().expect("No format given"));
+        } else if arg == "--pretty" {
+            output.format = Format::Pretty;
+        } else if arg == "--max-width" {
+            output.max_width = arguments
+                .next()
+                .expect("No width given")
+                .parse()
+                .expect("Width must be a number");
+        } else if arg == "--columns" {
+            let columns = arguments.next().expect("No columns given");
+            output.columns = Some(columns.split(',').map(|c| c.trim().to_string()).collect());
+        } else if arg == "--pool" {
+            options.default_pool = arguments.next().expect("No pool given");
+        } else if arg == "--cache-commitments" {
+            options.cache_commitments = true;
+        } else if arg == "--tail" {
+            options.tail = true;
+        } else if arg == "--no-gpu" {
+            options.gpu = false;
+        } else if arg == "-q" || arg == "--quiet" {
+            VERBOSITY.store(0, Ordering::Relaxed);
+        } else if arg == "-v" || arg == "--verbose" {
+            VERBOSITY.fetch_add(1, Ordering::Relaxed);
+        } else {
+            querystr = Some(arg);
+        }
+    }
+    let querystr = querystr.expect("No arguments");
+    let cache_path = format!("{}.{}.commitments.json", options.file, backend.name());
+    let cache_path = options.cache_commitments.then_some(cache_path.as_str());
+
+    match backend {
+        Backend::InnerProduct => run::<InnerProductProof>(&querystr, &options, cache_path, (), ()),
+        Backend::Dory => {
+            // The prover setup is built on the blitzar backend as well
+            ensure_backend(options.gpu);
+            let timer = start_timer("Generating Dory setup");
+            let public_parameters = dory_public_parameters();
+            let prover_setup = ProverSetup::from(&public_parameters);
+            let verifier_setup = VerifierSetup::from(&public_parameters);
+            end_timer(timer);
+            run::<DoryEvaluationProof>(
+                &querystr,
+                &options,
+                cache_path,
+                DoryProverPublicSetup::new(&prover_setup, DORY_SIGMA),
+                DoryVerifierPublicSetup::new(&verifier_setup, DORY_SIGMA),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proof_of_sql::base::database::MetadataAccessor;
+
+    type InnerProductCommitment =
+        TableCommitment<<InnerProductProof as CommitmentEvaluationProof>::Commitment>;
+
+    fn test_rows() -> Rows {
+        Rows {
+            pools: ["usdc-weth", "usdc-weth", "wbtc-weth", "usdc-weth"]
+                .map(String::from)
+                .to_vec(),
+            ticks: vec![-3, 5, 8, -1],
+        }
+    }
+
+    fn csv_output() -> OutputOptions {
+        OutputOptions {
+            format: Format::Csv,
+            columns: None,
+            max_width: DEFAULT_MAX_WIDTH,
         }
     }
+
+    /// Verified result of the query over `rows`, rendered with `output`.
+    fn prove_inner_product(querystr: &str, rows: Rows, output: &OutputOptions) -> String {
+        let table = prove_and_verify::<InnerProductProof>(querystr, rows_table(rows), None, (), ())
+            .unwrap();
+        output::render(&table, output).unwrap()
+    }
+
+    fn prove_dory(querystr: &str, rows: Rows, output: &OutputOptions) -> String {
+        let public_parameters = dory_public_parameters();
+        let prover_setup = ProverSetup::from(&public_parameters);
+        let verifier_setup = VerifierSetup::from(&public_parameters);
+        let table = prove_and_verify::<DoryEvaluationProof>(
+            querystr,
+            rows_table(rows),
+            None,
+            DoryProverPublicSetup::new(&prover_setup, DORY_SIGMA),
+            DoryVerifierPublicSetup::new(&verifier_setup, DORY_SIGMA),
+        )
+        .unwrap();
+        output::render(&table, output).unwrap()
+    }
+
+    #[test]
+    fn both_backends_prove_and_verify_the_same_query() {
+        let querystr = "SELECT * FROM table WHERE ticks > 0";
+
+        let inner_product = prove_inner_product(querystr, test_rows(), &csv_output());
+        let dory = prove_dory(querystr, test_rows(), &csv_output());
+
+        assert_eq!(inner_product, "pool,ticks\nusdc-weth,5\nwbtc-weth,8\n");
+        assert_eq!(dory, inner_product);
+    }
+
+    #[test]
+    fn unchanged_data_uses_the_cached_commitments() {
+        let dir = env::temp_dir().join(format!("snt_cache_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("ticks.csv");
+        let cache_path = dir.join("ticks.csv.commitments.json");
+        let (source, cache_path) = (source.to_str().unwrap(), cache_path.to_str().unwrap());
+        fs::write(source, "pool,ticks\nusdc-weth,-3\nusdc-weth,5\n").unwrap();
+        let commit = || {
+            let rows = read_rows(source, DEFAULT_POOL).unwrap();
+            let table = rows_table(rows);
+            let commitment = cached_table_commitment::<InnerProductProof>(
+                cache_path,
+                source,
+                &table,
+                DEFAULT_POOL,
+                &(),
+            );
+            (commitment, table.num_rows())
+        };
+
+        let (computed, rows) = commit();
+        let cached = load_cached_commitment(cache_path, modified_nanos(source), rows, DEFAULT_POOL);
+        let (second, _) = commit();
+        // A row appended to the source invalidates the cache
+        fs::write(
+            source,
+            "pool,ticks\nusdc-weth,-3\nusdc-weth,5\nusdc-weth,8\n",
+        )
+        .unwrap();
+        let stale = load_cached_commitment::<InnerProductCommitment>(
+            cache_path,
+            modified_nanos(source),
+            rows + 1,
+            DEFAULT_POOL,
+        );
+        let (recomputed, _) = commit();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(cached, Some(computed.clone()));
+        assert_eq!(second, computed);
+        assert!(stale.is_none());
+        assert_eq!(recomputed.num_rows(), 3);
+    }
+
+    #[test]
+    fn known_query_renders_as_csv() {
+        let querystr = "SELECT * FROM table WHERE ticks < 0";
+        let projected = OutputOptions {
+            columns: Some(vec!["ticks".to_string()]),
+            ..csv_output()
+        };
+
+        let csv = prove_inner_product(querystr, test_rows(), &csv_output());
+        let ticks = prove_inner_product(querystr, test_rows(), &projected);
+
+        assert_eq!(csv, "pool,ticks\nusdc-weth,-3\nusdc-weth,-1\n");
+        assert_eq!(ticks, "ticks\n-3\n-1\n");
+    }
+
+    #[test]
+    fn where_pool_returns_only_the_matching_rows() {
+        let path = env::temp_dir().join(format!("snt_pools_{}.jsonl", std::process::id()));
+        let lines = [
+            ("usdc-weth", -3),
+            ("wbtc-weth", 8),
+            ("usdc-weth", 5),
+            ("wbtc-weth", -1),
+        ]
+        .map(|(pool, tick)| format!("{{\"pool\": \"{}\", \"tick\": {}}}\n", pool, tick));
+        fs::write(&path, lines.concat()).unwrap();
+        let rows = read_rows(path.to_str().unwrap(), DEFAULT_POOL).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let csv = prove_inner_product(
+            "SELECT * FROM table WHERE pool = 'wbtc-weth'",
+            rows,
+            &csv_output(),
+        );
+
+        assert_eq!(csv, "pool,ticks\nwbtc-weth,8\nwbtc-weth,-1\n");
+    }
+
+    /// CSV results of `SELECT * FROM table` proved by `--tail` after every change of `path`,
+    /// which is appended to after the first result and truncated after the second.
+    fn tail_results<'a, CP: CommitmentEvaluationProof>(
+        path: &str,
+        prover_setup: CP::ProverPublicSetup<'a>,
+        verifier_setup: CP::VerifierPublicSetup<'a>,
+    ) -> Vec<String> {
+        let mut results = Vec::new();
+        let mut previous = None;
+        tail::tail(path, DEFAULT_POOL, |rows| {
+            let commitment = tail_commitment::<CP>(previous.take(), &rows, &prover_setup);
+            let table = prove_and_verify::<CP>(
+                "SELECT * FROM table",
+                rows_table(rows.clone()),
+                Some(commitment.clone()),
+                prover_setup,
+                verifier_setup,
+            )
+            .unwrap();
+            results.push(output::render(&table, &csv_output()).unwrap());
+            previous = Some((rows, commitment));
+            match results.len() {
+                1 => {
+                    let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+                    file.write_all(b"wbtc-weth,8\n").unwrap();
+                }
+                2 => fs::write(path, "pool,ticks\nusdc-weth,5\n").unwrap(),
+                _ => return ControlFlow::Break(()),
+            }
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        results
+    }
+
+    #[test]
+    fn tail_proves_the_appended_rows() {
+        let dir = env::temp_dir().join(format!("snt_tail_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ticks.csv");
+        fs::write(&path, "pool,ticks\nusdc-weth,-3\n").unwrap();
+
+        let results = tail_results::<InnerProductProof>(path.to_str().unwrap(), (), ());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            results,
+            [
+                "pool,ticks\nusdc-weth,-3\n",
+                "pool,ticks\nusdc-weth,-3\nwbtc-weth,8\n",
+                "pool,ticks\nusdc-weth,5\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn queries_parse_without_the_backend() {
+        let (accessor, _query) = load_and_parse::<InnerProductProof>(
+            "SELECT * FROM table WHERE pool = 'wbtc-weth'",
+            rows_table(test_rows()),
+            (),
+        );
+
+        assert_eq!(accessor.get_length(TABLE.parse().unwrap()), 4);
+        assert!(!BACKEND.is_completed());
+    }
+
+    #[test]
+    fn sum_above_i64_max_decodes_as_i128() {
+        let rows = Rows {
+            pools: ["usdc-weth", "usdc-weth", "wbtc-weth"]
+                .map(String::from)
+                .to_vec(),
+            ticks: vec![i64::MAX, i64::MAX, 1],
+        };
+        let querystr = "SELECT pool, SUM(ticks) AS total FROM table GROUP BY pool";
+
+        let csv = prove_inner_product(querystr, rows, &csv_output());
+
+        let total = 2 * i64::MAX as i128;
+        assert_eq!(
+            csv,
+            format!("pool,total\nusdc-weth,{}\nwbtc-weth,1\n", total)
+        );
+    }
 }
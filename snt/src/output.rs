@@ -0,0 +1,423 @@
+// Formatting of verified query results.
+
+use anyhow::{anyhow, bail, Result};
+use proof_of_sql::base::{
+    database::{OwnedColumn, OwnedTable},
+    scalar::Scalar,
+};
+use serde_json::{Map, Value};
+use std::iter;
+
+/// Width at which `--pretty` truncates cells by default.
+pub const DEFAULT_MAX_WIDTH: usize = 32;
+
+pub enum Format {
+    Table,
+    /// Table with numbers aligned right and cells truncated to `max_width`.
+    Pretty,
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "table" => Format::Table,
+            "pretty" => Format::Pretty,
+            "csv" => Format::Csv,
+            "json" => Format::Json,
+            _ => panic!(
+                "Unknown format {}, expected table, pretty, csv or json",
+                name
+            ),
+        }
+    }
+}
+
+pub struct OutputOptions {
+    pub format: Format,
+    /// Columns to print, in order. All columns when `None`.
+    pub columns: Option<Vec<String>>,
+    /// Longest cell of the pretty table, in characters, longer ones end with an ellipsis.
+    pub max_width: usize,
+}
+
+/// Decoded result column.
+#[derive(Clone)]
+struct Column {
+    name: String,
+    values: Vec<Value>,
+    /// Aligned right by the pretty table.
+    numeric: bool,
+}
+
+/// Renders the result table in the requested format, keeping only the projected columns.
+pub fn render<S: Scalar>(table: &OwnedTable<S>, options: &OutputOptions) -> Result<String> {
+    let mut columns = Vec::new();
+    for (name, column) in table.inner_table() {
+        columns.push(Column {
+            name: name.to_string(),
+            values: column_cells(column)?,
+            numeric: matches!(
+                column,
+                OwnedColumn::BigInt(_) | OwnedColumn::Int128(_) | OwnedColumn::Decimal75(..)
+            ),
+        });
+    }
+    if let Some(projection) = &options.columns {
+        columns = projection
+            .iter()
+            .map(|name| {
+                columns
+                    .iter()
+                    .find(|column| &column.name == name)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("No column {} in the query result", name))
+            })
+            .collect::<Result<_>>()?;
+    }
+    let rows = table.num_rows();
+
+    match options.format {
+        Format::Table => Ok(render_table(&columns, rows)),
+        Format::Pretty => Ok(render_pretty(&columns, rows, options.max_width)),
+        Format::Csv => render_csv(&columns, rows),
+        Format::Json => render_json(&columns, rows),
+    }
+}
+
+/// Decodes a column into typed JSON values.
+fn column_cells<S: Scalar>(column: &OwnedColumn<S>) -> Result<Vec<Value>> {
+    Ok(match column {
+        OwnedColumn::Boolean(values) => values.iter().map(|v| Value::from(*v)).collect(),
+        OwnedColumn::BigInt(values) => values.iter().map(|v| Value::from(*v)).collect(),
+        // i128 does not fit a JSON number
+        OwnedColumn::Int128(values) => values.iter().map(|v| Value::from(v.to_string())).collect(),
+        OwnedColumn::VarChar(values) => values.iter().map(|v| Value::from(v.as_str())).collect(),
+        // Integer aggregates, e.g. the SUM of a bigint column, widen to a decimal of scale 0
+        OwnedColumn::Decimal75(_, scale, values) if *scale <= 0 => values
+            .iter()
+            .map(|v| decimal_to_i128(*v, *scale).map(|v| Value::from(v.to_string())))
+            .collect::<Result<_>>()?,
+        OwnedColumn::Decimal75(_, scale, values) => values
+            .iter()
+            .map(|v| Value::from(decimal_to_string(*v, *scale)))
+            .collect(),
+        #[allow(unreachable_patterns)]
+        _ => bail!("Unsupported column type {:?}", column.column_type()),
+    })
+}
+
+/// Splits a signed scalar into its sign and magnitude.
+fn sign_magnitude<S: Scalar>(value: S) -> (bool, S) {
+    if value > S::MAX_SIGNED {
+        (true, -value)
+    } else {
+        (false, value)
+    }
+}
+
+/// Decodes an integer decimal, `scale <= 0`, failing rather than wrapping when it exceeds i128.
+fn decimal_to_i128<S: Scalar>(value: S, scale: i8) -> Result<i128> {
+    let (negative, magnitude) = sign_magnitude(value);
+    let limbs: [u64; 4] = magnitude.into();
+    let overflow = || {
+        anyhow!(
+            "Decimal {} does not fit in i128",
+            decimal_to_string(value, scale)
+        )
+    };
+    if limbs[2] != 0 || limbs[3] != 0 {
+        return Err(overflow());
+    }
+    let magnitude = ((limbs[1] as u128) << 64) | limbs[0] as u128;
+    // i128::MIN has no positive counterpart
+    let value = if negative {
+        0i128.checked_sub_unsigned(magnitude)
+    } else {
+        i128::try_from(magnitude).ok()
+    };
+    value
+        .and_then(|value| value.checked_mul(10i128.checked_pow(scale.unsigned_abs() as u32)?))
+        .ok_or_else(overflow)
+}
+
+/// Formats a decimal stored as a signed scalar with `scale` fractional digits.
+fn decimal_to_string<S: Scalar>(value: S, scale: i8) -> String {
+    let (negative, magnitude) = sign_magnitude(value);
+    let mut digits = limbs_to_string(magnitude.into());
+    if scale > 0 {
+        let scale = scale as usize;
+        if digits.len() <= scale {
+            digits = "0".repeat(scale - digits.len() + 1) + &digits;
+        }
+        digits.insert(digits.len() - scale, '.');
+    } else if digits != "0" {
+        digits.push_str(&"0".repeat(scale.unsigned_abs() as usize));
+    }
+    if negative {
+        digits.insert(0, '-');
+    }
+    digits
+}
+
+/// Decimal representation of a little-endian 256 bit integer.
+fn limbs_to_string(mut limbs: [u64; 4]) -> String {
+    let mut digits = Vec::new();
+    loop {
+        // Long division of the whole number by 10, most significant limb first
+        let mut remainder = 0u128;
+        for limb in limbs.iter_mut().rev() {
+            let current = (remainder << 64) | *limb as u128;
+            *limb = (current / 10) as u64;
+            remainder = current % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+        if limbs.iter().all(|limb| *limb == 0) {
+            break;
+        }
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+fn cell_to_string(cell: &Value) -> String {
+    match cell {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn render_table(columns: &[Column], rows: usize) -> String {
+    let cells: Vec<Vec<String>> = columns
+        .iter()
+        .map(|column| column.values.iter().map(cell_to_string).collect())
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .zip(&cells)
+        .map(|(Column { name, .. }, values)| {
+            values
+                .iter()
+                .map(String::len)
+                .chain([name.len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |values: Vec<&str>| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+    let mut output = vec![
+        line(columns.iter().map(|column| column.name.as_str()).collect()),
+        widths
+            .iter()
+            .map(|width| "-".repeat(*width))
+            .collect::<Vec<_>>()
+            .join("-+-"),
+    ];
+    for row in 0..rows {
+        output.push(line(
+            cells.iter().map(|column| column[row].as_str()).collect(),
+        ));
+    }
+    output.join("\n")
+}
+
+/// Cell truncated to `max_width` characters, the last one replaced by an ellipsis.
+fn truncate(cell: &str, max_width: usize) -> String {
+    if cell.chars().count() <= max_width {
+        return cell.to_string();
+    }
+    let mut truncated: String = cell.chars().take(max_width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn render_pretty(columns: &[Column], rows: usize, max_width: usize) -> String {
+    let cells: Vec<Vec<String>> = columns
+        .iter()
+        .map(|column| {
+            iter::once(column.name.clone())
+                .chain(column.values.iter().map(cell_to_string))
+                .map(|cell| truncate(&cell, max_width))
+                .collect()
+        })
+        .collect();
+    // Widths in characters, the ellipsis and pool names may not be ASCII
+    let widths: Vec<usize> = cells
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |row: usize| {
+        columns
+            .iter()
+            .zip(&cells)
+            .zip(&widths)
+            .map(|((column, cells), width)| {
+                let padding = " ".repeat(width - cells[row].chars().count());
+                if column.numeric {
+                    padding + &cells[row]
+                } else {
+                    cells[row].clone() + &padding
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+    let rule = widths
+        .iter()
+        .map(|width| "-".repeat(*width))
+        .collect::<Vec<_>>()
+        .join("-+-");
+    // Row 0 of the cells is the header
+    iter::once(line(0))
+        .chain([rule])
+        .chain((1..=rows).map(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_csv(columns: &[Column], rows: usize) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(columns.iter().map(|column| &column.name))?;
+    for row in 0..rows {
+        writer.write_record(
+            columns
+                .iter()
+                .map(|column| cell_to_string(&column.values[row])),
+        )?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+fn render_json(columns: &[Column], rows: usize) -> Result<String> {
+    let records: Vec<Value> = (0..rows)
+        .map(|row| {
+            let record: Map<String, Value> = columns
+                .iter()
+                .map(|column| (column.name.clone(), column.values[row].clone()))
+                .collect();
+            Value::Object(record)
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&records)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proof_of_sql::base::{database::owned_table_utility::*, scalar::Curve25519Scalar};
+
+    fn options(format: Format, columns: Option<&[&str]>) -> OutputOptions {
+        OutputOptions {
+            format,
+            columns: columns.map(|columns| columns.iter().map(|c| c.to_string()).collect()),
+            max_width: DEFAULT_MAX_WIDTH,
+        }
+    }
+
+    fn known_result() -> OwnedTable<Curve25519Scalar> {
+        owned_table([
+            varchar("pool", ["usdc-weth", "wbtc-weth"]),
+            bigint("ticks", [-3, 8]),
+            decimal75("fee", 10, 2, [150i64, -5]),
+        ])
+    }
+
+    #[test]
+    fn csv_has_the_header_and_typed_rows() {
+        let csv = render(&known_result(), &options(Format::Csv, None)).unwrap();
+        let projected = render(
+            &known_result(),
+            &options(Format::Csv, Some(&["fee", "pool"])),
+        )
+        .unwrap();
+
+        assert_eq!(
+            csv,
+            "pool,ticks,fee\nusdc-weth,-3,1.50\nwbtc-weth,8,-0.05\n"
+        );
+        assert_eq!(projected, "fee,pool\n1.50,usdc-weth\n-0.05,wbtc-weth\n");
+    }
+
+    #[test]
+    fn pretty_aligns_and_truncates_cells() {
+        let table = owned_table::<Curve25519Scalar>([
+            varchar("pool", ["usdc-weth", "dai"]),
+            bigint("ticks", [-3, 12345]),
+            decimal75("fee", 10, 2, [150i64, -5]),
+        ]);
+        let options = OutputOptions {
+            max_width: 8,
+            ..options(Format::Pretty, None)
+        };
+
+        let pretty = render(&table, &options).unwrap();
+
+        assert_eq!(
+            pretty,
+            "\
+pool     | ticks |   fee
+---------+-------+------
+usdc-we… |    -3 |  1.50
+dai      | 12345 | -0.05"
+        );
+    }
+
+    #[test]
+    fn wide_integer_sums_decode_as_i128() {
+        let above_i64 = 2 * i64::MAX as i128;
+        let table = owned_table::<Curve25519Scalar>([
+            int128("int128_sum", [above_i64, i128::MIN]),
+            decimal75("decimal_sum", 75, 0, [above_i64, -above_i64]),
+            decimal75("scaled_sum", 75, -2, [above_i64, 0]),
+        ]);
+
+        let csv = render(&table, &options(Format::Csv, None)).unwrap();
+
+        assert_eq!(
+            csv,
+            format!(
+                "int128_sum,decimal_sum,scaled_sum\n{},{},{}00\n{},-{},0\n",
+                above_i64,
+                above_i64,
+                above_i64,
+                i128::MIN,
+                above_i64
+            )
+        );
+    }
+
+    #[test]
+    fn sums_beyond_i128_are_an_error() {
+        let beyond_i128 = Curve25519Scalar::from(i128::MAX) + Curve25519Scalar::from(1);
+        let table = owned_table::<Curve25519Scalar>([decimal75("total", 75, 0, [beyond_i128])]);
+        let scaled = owned_table::<Curve25519Scalar>([decimal75("total", 75, -1, [i128::MAX])]);
+
+        let error = render(&table, &options(Format::Csv, None)).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Decimal 170141183460469231731687303715884105728 does not fit in i128"
+        );
+        assert!(render(&scaled, &options(Format::Csv, None)).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+// Watching of the ticks file for `--tail`.
+
+use crate::{
+    input::{read_rows, Rows},
+    verbosity,
+};
+use anyhow::{anyhow, Result};
+use notify::{RecursiveMode, Watcher};
+use std::{ops::ControlFlow, path::Path, sync::mpsc};
+
+/// Calls `on_rows` with the rows of `path`, then again every time they change, until it breaks
+/// or the watch fails. The directory is watched rather than the file so that a rotated file is
+/// picked up.
+pub fn tail(
+    path: &str,
+    default_pool: &str,
+    mut on_rows: impl FnMut(Rows) -> ControlFlow<()>,
+) -> Result<()> {
+    let file_name = Path::new(path)
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file", path))?;
+    let dir = match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    let mut last = None;
+    if reload(path, default_pool, &mut last, &mut on_rows).is_break() {
+        return Ok(());
+    }
+    if verbosity() >= 1 {
+        println!("Watching {} for new rows", path);
+    }
+    for event in receiver {
+        let event = event?;
+        let ours = event
+            .paths
+            .iter()
+            .any(|changed| changed.file_name() == Some(file_name));
+        if ours
+            && !event.kind.is_access()
+            && reload(path, default_pool, &mut last, &mut on_rows).is_break()
+        {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+// Several events can be raised for one append, only rows that differ from the last are passed on.
+fn reload(
+    path: &str,
+    default_pool: &str,
+    last: &mut Option<Rows>,
+    on_rows: &mut impl FnMut(Rows) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    match read_rows(path, default_pool) {
+        Ok(rows) if last.as_ref() != Some(&rows) => {
+            *last = Some(rows.clone());
+            on_rows(rows)
+        }
+        Ok(_) => ControlFlow::Continue(()),
+        // A row still being written or a file being rotated, the next event reloads it
+        Err(error) => {
+            eprintln!("Skipping reload of {}: {:#}", path, error);
+            ControlFlow::Continue(())
+        }
+    }
+}
@@ -4,7 +4,7 @@
 sp1_zkvm::entrypoint!(main);
 use alloy_sol_types::{sol, SolType};
 use fixed::types::I24F40 as Fixed;
-use tiny_keccak::{Hasher, Sha3};
+use tiny_keccak::{Hasher, Keccak};
 
 include!("../../script/src/data.rs");
 
@@ -37,24 +37,31 @@ pub fn tick_volatility2(
     let n_inv_sqrt = Fixed::from_be_bytes(n_inv_sqrt);
     let n1_inv = Fixed::from_be_bytes(n1_inv);
 
-    let mut ticks_prev = Fixed::from_num(i64::from_be_bytes(DATA[0]));
+    println!("cycle-tracker-report-start: volatility");
+    let mut ticks_prev = Fixed::from_be_bytes(DATA[0]);
     let (sum_u, sum_u2) =
         DATA.iter()
             .skip(1)
             .fold((Fixed::ZERO, Fixed::ZERO), |(sum_u, sum_u2), val| {
-                let ticks_curr = Fixed::from_num(i64::from_be_bytes(*val));
+                let ticks_curr = Fixed::from_be_bytes(*val);
                 let delta = ticks_curr - ticks_prev;
                 ticks_prev = ticks_curr;
                 (sum_u + delta * n_inv_sqrt, sum_u2 + delta * delta * n1_inv)
             });
 
-    let s2_bytes = Fixed::to_be_bytes(sum_u2 - (sum_u * sum_u) * n1_inv);
+    // Rounding of the products can leave a negative variance, whose sqrt would panic
+    let s2_bytes = Fixed::to_be_bytes((sum_u2 - (sum_u * sum_u) * n1_inv).max(Fixed::ZERO));
     let n_bytes = Fixed::to_be_bytes(n);
+    println!("cycle-tracker-report-end: volatility");
 
-    let mut sha3 = Sha3::v256();
+    // Keccak-256 rather than SHA3-256 so the digest equals Solidity's keccak256 over the
+    // packed big-endian ticks
+    println!("cycle-tracker-report-start: hash");
+    let mut keccak = Keccak::v256();
     let mut output = [0u8; 32];
-    DATA.iter().for_each(|x| sha3.update(x));
-    sha3.finalize(&mut output);
+    DATA.iter().for_each(|x| keccak.update(x));
+    keccak.finalize(&mut output);
+    println!("cycle-tracker-report-end: hash");
 
     (s2_bytes, n_bytes, output)
 }
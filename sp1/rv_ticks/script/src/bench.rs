@@ -0,0 +1,119 @@
+//! Proving throughput benchmark for `--iterations`, proving the same stdin repeatedly after a
+//! single setup so that only the proving time is measured.
+
+use crate::prove::with_retry;
+use crate::verbosity::status;
+use anyhow::Result;
+use serde::Serialize;
+use sp1_sdk::{ProverClient, SP1Stdin};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Wall times of the proofs of a benchmark, in milliseconds.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProveStats {
+    pub times_ms: Vec<u64>,
+    pub min_ms: u64,
+    pub median_ms: u64,
+    pub mean_ms: u64,
+    pub p95_ms: u64,
+}
+
+impl ProveStats {
+    pub fn from_times(times: &[Duration]) -> Self {
+        let times_ms: Vec<u64> = times.iter().map(|time| time.as_millis() as u64).collect();
+        let mut sorted = times_ms.clone();
+        sorted.sort_unstable();
+        let n = sorted.len().max(1);
+        let median_ms = match sorted.len() {
+            0 => 0,
+            len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) / 2,
+            len => sorted[len / 2],
+        };
+        // Nearest rank
+        let p95_rank = ((sorted.len() as f64 * 0.95).ceil() as usize).max(1);
+        Self {
+            min_ms: sorted.first().copied().unwrap_or(0),
+            median_ms,
+            mean_ms: sorted.iter().sum::<u64>() / n as u64,
+            p95_ms: sorted.get(p95_rank - 1).copied().unwrap_or(0),
+            times_ms,
+        }
+    }
+
+    /// Proofs per minute at the mean proving time.
+    pub fn proofs_per_minute(&self) -> f64 {
+        60_000.0 / self.mean_ms.max(1) as f64
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        status!("Metrics written to {}", path);
+        Ok(())
+    }
+}
+
+impl fmt::Display for ProveStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} proofs: min {} ms, median {} ms, mean {} ms, p95 {} ms ({:.2} proofs/min)",
+            self.times_ms.len(),
+            self.min_ms,
+            self.median_ms,
+            self.mean_ms,
+            self.p95_ms,
+            self.proofs_per_minute()
+        )
+    }
+}
+
+/// Proves `stdin` `iterations` times with keys set up once. Every proof is dropped before
+/// the next starts, so memory does not grow with the number of iterations.
+pub fn benchmark(
+    elf: &[u8],
+    stdin: SP1Stdin,
+    client: &ProverClient,
+    iterations: usize,
+    max_retries: u32,
+) -> Result<ProveStats> {
+    let (pk, _) = client.setup(elf);
+    let mut times = Vec::with_capacity(iterations);
+    for iteration in 1..=iterations {
+        let start_time = Instant::now();
+        with_retry(max_retries, || client.prove_plonk(&pk, stdin.clone()))?;
+        let time = start_time.elapsed();
+        status!(
+            "Proof {}/{}: {} ms",
+            iteration,
+            iterations,
+            time.as_millis()
+        );
+        times.push(time);
+    }
+    Ok(ProveStats::from_times(&times))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_iterations_populate_every_stat() {
+        let stats =
+            ProveStats::from_times(&[Duration::from_millis(300), Duration::from_millis(100)]);
+
+        assert_eq!(stats.times_ms, [300, 100]);
+        assert_eq!(stats.min_ms, 100);
+        // The mean of the two middle times, and the nearest rank of the 95th percentile
+        assert_eq!(stats.median_ms, 200);
+        assert_eq!(stats.mean_ms, 200);
+        assert_eq!(stats.p95_ms, 300);
+        assert_eq!(stats.proofs_per_minute(), 300.0);
+        assert_eq!(
+            stats.to_string(),
+            "2 proofs: min 100 ms, median 200 ms, mean 200 ms, p95 300 ms (300.00 proofs/min)"
+        );
+    }
+}
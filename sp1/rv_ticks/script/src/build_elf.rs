@@ -1,10 +1,14 @@
-use anyhow::{Result, Context};
+use crate::common::{encode_tick, fixed_slice_from_ticks, Fixed};
+use crate::swap::{quote, record_line, Swap, SwapKey};
+use crate::verbosity::status;
+use anyhow::{bail, Result, Context};
 use chrono::Local;
 use rand::thread_rng;
 use rand_distr::{Distribution, Normal};
-use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
 use std::{
     io::BufReader,
     process::{Command, Stdio},
@@ -12,49 +16,90 @@ use std::{
 };
 use jsonl::read;
 
+/// Big-endian `Fixed` bytes, the encoding of the ticks of every source.
 pub type NumberBytes = [u8; 8];
 
 const N: usize = 8192;
 
+// The guest reads `ticks[0]` and divides by `n - 1`
+const MIN_TICKS: usize = 2;
+
+/// Path given to `--ticks` to read jsonl swaps from standard input.
+pub const STDIN_PATH: &str = "-";
+
 pub enum TickSource {
     Random,
     Jsonl(String),
+    /// Overlapping substream jsonl files, merged by [`combine_windows`]
+    Windows(Vec<PathBuf>),
     Csv(String),
+    Stdin,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Swap {
-    evt_tx_hash: String,
-    evt_index: u32,
-    evt_block_time: String,
-    evt_block_num: u64,
-    sender: [u8; 20],
-    recipient: [u8; 20],
-    amount0: String,
-    amount1: String,
-    sqrt_price_x96: String,
-    liquidity: String,
-    pub tick: i64,
+impl TickSource {
+    /// Jsonl file source, stdin for the `-` path, or several files for comma separated paths.
+    pub fn jsonl(path: String) -> Self {
+        if path == STDIN_PATH {
+            TickSource::Stdin
+        } else if path.contains(',') {
+            TickSource::Windows(path.split(',').map(PathBuf::from).collect())
+        } else {
+            TickSource::Jsonl(path)
+        }
+    }
+
+    /// Csv file source for a `.csv` path, jsonl otherwise.
+    pub fn from_path(path: String) -> Self {
+        if path.ends_with(".csv") {
+            TickSource::Csv(path)
+        } else {
+            TickSource::jsonl(path)
+        }
+    }
 }
 
-pub fn read_ticks(source: TickSource) -> Vec<NumberBytes> {
+/// Reads the ticks of the source. With `per_block`, jsonl swaps are aggregated to the last
+/// tick of every block, the other sources have no blocks and are read as is.
+pub fn read_ticks(source: TickSource, per_block: bool) -> Result<Vec<NumberBytes>> {
     match source {
         TickSource::Random => ticks(),
         TickSource::Jsonl(file) => {
-            let file = std::fs::File::open(file).expect("Could not open file");
-            let mut reader = std::io::BufReader::new(file);
-            read_ticks_from_jsonl(&mut reader).unwrap()
+            let mut reader = BufReader::new(
+                File::open(&file).with_context(|| format!("Could not open {}", file))?,
+            );
+            read_ticks_from_jsonl(&mut reader, per_block)
+                .with_context(|| format!("Invalid swaps in {}", file))
         }
+        TickSource::Windows(files) if per_block => {
+            Ok(last_tick_per_block(combine_keyed(read_windows(&files)?)))
+        }
+        TickSource::Windows(files) => combine_windows(&files),
         TickSource::Csv(file) => {
-            let file = std::fs::File::open(file).expect("Could not open file");
-            let mut reader = std::io::BufReader::new(file);
+            let mut reader = BufReader::new(
+                File::open(&file).with_context(|| format!("Could not open {}", file))?,
+            );
             read_ticks_from_reader(&mut reader)
+                .with_context(|| format!("Invalid ticks in {}", file))
         }
+        TickSource::Stdin => read_ticks_from_stdin(&mut std::io::stdin().lock(), per_block),
     }
 }
 
+/// Reads jsonl swaps piped on stdin, which unlike a file must not be empty.
+fn read_ticks_from_stdin<R: BufRead>(reader: &mut R, per_block: bool) -> Result<Vec<NumberBytes>> {
+    let ticks =
+        read_ticks_from_jsonl(reader, per_block).context("Failed to read ticks from stdin")?;
+    if ticks.is_empty() {
+        bail!("No ticks received on stdin");
+    }
+    Ok(ticks)
+}
+
+/// Writes to a temporary file renamed into place, so that an interrupted run never leaves a
+/// truncated data.rs breaking the next guest build.
 fn write_ticks_to_file(ticks: Vec<NumberBytes>, file: &str) -> Result<()> {
-    let mut f = File::create(file)?;
+    let tmp_file = format!("{}.tmp", file);
+    let mut f = File::create(&tmp_file)?;
 
     writeln!(f, "const DATA: &[ [u8; 8] ] = &[\n").with_context(|| format!("Failed to write ticks to file, {:?}", f))?;
     for record in ticks {
@@ -69,6 +114,20 @@ fn write_ticks_to_file(ticks: Vec<NumberBytes>, file: &str) -> Result<()> {
         ).with_context(|| format!("Failed to write ticks to file, {:?}", f))?;
     }
     writeln!(f, "];").with_context(|| format!("Failed to write ticks to file, {:?}", f))?;
+    f.sync_all().context("Failed to flush the ticks file")?;
+    std::fs::rename(&tmp_file, file).context("Failed to move the ticks file into place")?;
+    Ok(())
+}
+
+/// Rejects tick sets the guest can not compute a volatility for, and warns when the
+/// sample size differs from the historical N since `n` is taken from the actual length.
+pub fn validate_ticks(ticks: &[NumberBytes]) -> Result<()> {
+    if ticks.len() < MIN_TICKS {
+        bail!("At least {} ticks are required, got {}", MIN_TICKS, ticks.len());
+    }
+    if ticks.len() != N {
+        status!("Warning: proving {} ticks instead of the usual {}", ticks.len(), N);
+    }
     Ok(())
 }
 
@@ -77,6 +136,7 @@ pub fn build_elf(
     tick_dest_file: &str,
     program_path: &str,
 ) -> Result<()> {
+    validate_ticks(&ticks)?;
     // Define the output directory relative to the build script's location
     write_ticks_to_file(ticks, tick_dest_file)?;
     build_program(program_path);
@@ -84,36 +144,114 @@ pub fn build_elf(
     Ok(())
 }
 
-pub fn read_ticks_from_jsonl<R: BufRead>(reader: &mut R) -> Result<Vec<NumberBytes>> {
+pub fn read_ticks_from_jsonl<R: BufRead>(
+    reader: &mut R,
+    per_block: bool,
+) -> Result<Vec<NumberBytes>> {
+    let ticks = read_keyed_ticks_from_jsonl(reader)?;
+    if per_block {
+        return Ok(last_tick_per_block(ticks));
+    }
+    Ok(ticks.into_iter().map(|(_, tick)| tick).collect())
+}
+
+/// Keeps one tick per block, the tick of its swap with the highest `evt_index`, so that busy
+/// blocks do not inflate the sample of a block level volatility. The ticks are ordered by block.
+pub fn last_tick_per_block(
+    ticks: impl IntoIterator<Item = (SwapKey, NumberBytes)>,
+) -> Vec<NumberBytes> {
+    let mut blocks: BTreeMap<u64, (u32, NumberBytes)> = BTreeMap::new();
+    for ((block, index), tick) in ticks {
+        let last = blocks.entry(block).or_insert((index, tick));
+        if index >= last.0 {
+            *last = (index, tick);
+        }
+    }
+    blocks.into_values().map(|(_, tick)| tick).collect()
+}
+
+/// Reads ticks keyed by the position of their swap in the chain, so that swaps repeated
+/// across overlapping files can be deduplicated. Errors name the line of the offending row.
+pub fn read_keyed_ticks_from_jsonl<R: BufRead>(
+    reader: &mut R,
+) -> Result<Vec<(SwapKey, NumberBytes)>> {
     let mut ticks = Vec::new();
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(false)
         .from_reader(reader);
-    for result in rdr.deserialize() {
-        let swap: Swap = result?;
-        ticks.push((swap.tick as i64).to_be_bytes());
+    for record in rdr.records() {
+        let record = record.context("Failed to read jsonl row")?;
+        let swap = Swap::from_record(&record)?;
+        let tick = encode_tick(swap.tick)
+            .with_context(|| {
+                format!(
+                    "Invalid tick on line {} at {}",
+                    record_line(&record),
+                    swap.evt_block_time
+                )
+            })?;
+        ticks.push((swap.key(), tick));
     }
     Ok(ticks)
 }
 
-fn read_ticks_from_reader<R: BufRead>(reader: &mut R) -> Vec<NumberBytes> {
+/// Reads the keyed ticks of one substream jsonl file.
+pub fn read_window(file: &Path) -> Result<Vec<(SwapKey, NumberBytes)>> {
+    let mut reader = BufReader::new(
+        File::open(file).with_context(|| format!("Could not open {}", file.display()))?,
+    );
+    read_keyed_ticks_from_jsonl(&mut reader)
+        .with_context(|| format!("Invalid swaps in {}", file.display()))
+}
+
+/// Merges the keyed ticks of several windows into the canonical sequence: consecutive files can
+/// overlap at their boundaries, keying by swap position counts each swap once and orders the
+/// ticks by block then index, whatever the order of the windows.
+pub fn combine_keyed(
+    windows: impl IntoIterator<Item = Vec<(SwapKey, NumberBytes)>>,
+) -> BTreeMap<SwapKey, NumberBytes> {
+    windows.into_iter().flatten().collect()
+}
+
+/// Reads the keyed ticks of every substream jsonl file, failing on the first that can not be read.
+pub fn read_windows(files: &[PathBuf]) -> Result<Vec<Vec<(SwapKey, NumberBytes)>>> {
+    files.iter().map(|file| read_window(file)).collect()
+}
+
+/// Reads substream jsonl files, in any order, into the deduplicated block ordered ticks.
+/// Callers selecting blocks use [`read_windows`] and [`combine_keyed`] to keep the keys.
+pub fn combine_windows(files: &[PathBuf]) -> Result<Vec<NumberBytes>> {
+    Ok(combine_keyed(read_windows(files)?).into_values().collect())
+}
+
+/// Reads a CSV column of ticks after its header. Ticks may be negative or fractional, such as
+/// derived mid-prices, and are rounded to the nearest `Fixed`. Blank lines are skipped.
+fn read_ticks_from_reader<R: BufRead>(reader: &mut R) -> Result<Vec<NumberBytes>> {
     let mut ticks = Vec::new();
     let mut line = String::new();
     // Skip the header line
-    reader.read_line(&mut line).expect("Failed to read line");
+    reader.read_line(&mut line).context("Failed to read the CSV header")?;
     line.clear();
-    while reader.read_line(&mut line).expect("Failed to read line") > 0 {
-        if let Ok(value) = line.trim().parse::<i64>() {
-            ticks.push((value).to_be_bytes());
-        } else {
-            panic!("Invalid number in CSV");
+    let mut number = 1;
+    while reader.read_line(&mut line).context("Failed to read line")? > 0 {
+        number += 1;
+        let value = line.trim();
+        if !value.is_empty() {
+            let tick = parse_tick(value)
+                .with_context(|| format!("Invalid tick on line {}: {}", number, quote(value)))?;
+            ticks.push(tick.to_be_bytes());
         }
         line.clear();
     }
-    ticks
+    Ok(ticks)
 }
 
-fn ticks() -> Vec<NumberBytes> {
+/// Parses an integer or decimal tick, failing if it does not fit in `Fixed`.
+fn parse_tick(value: &str) -> Result<Fixed> {
+    Ok(value.parse::<Fixed>()?)
+}
+
+fn ticks() -> Result<Vec<NumberBytes>> {
     // Create a random number generator
     let mut rng = thread_rng();
 
@@ -129,7 +267,10 @@ fn ticks() -> Vec<NumberBytes> {
             r_f64.round() as i64
         })
         .collect();
-    rand_vec.iter().map(|x| x.to_be_bytes()).collect()
+    Ok(fixed_slice_from_ticks(&rand_vec)?
+        .into_iter()
+        .map(Fixed::to_be_bytes)
+        .collect())
 }
 
 fn current_datetime() -> String {
@@ -138,7 +279,7 @@ fn current_datetime() -> String {
 }
 
 pub fn build_program(path: &str) {
-    println!("path: {:?}", path);
+    status!("path: {:?}", path);
     let program_dir = std::path::Path::new(path);
 
     // Tell cargo to rerun the script only if program/{src, Cargo.toml, Cargo.lock} changes
@@ -162,7 +303,7 @@ pub fn build_program(path: &str) {
         .as_ref()
         .map(|p| p.name.as_str())
         .unwrap_or("Program");
-    println!(
+    status!(
         "cargo:warning={} built at {}",
         root_package_name,
         current_datetime()
@@ -203,7 +344,7 @@ fn execute_build_cmd(
     // Pipe stdout and stderr to the parent process with [sp1] prefix
     let stdout_handle = thread::spawn(move || {
         stdout.lines().for_each(|line| {
-            println!("[sp1] {}", line.unwrap());
+            status!("[sp1] {}", line.unwrap());
         });
     });
     stderr.lines().for_each(|line| {
@@ -214,3 +355,147 @@ fn execute_build_cmd(
 
     child.wait()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swap::jsonl_row as row;
+    use std::fs;
+
+    /// Window of swaps given as (block, index, tick).
+    fn window(swaps: &[(u64, u32, i64)]) -> Vec<(SwapKey, NumberBytes)> {
+        swaps
+            .iter()
+            .map(|&(block, index, tick)| ((block, index), encode_tick(tick).unwrap()))
+            .collect()
+    }
+
+    fn ticks(ticks: &[i64]) -> Vec<NumberBytes> {
+        ticks
+            .iter()
+            .map(|tick| encode_tick(*tick).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn combine_counts_overlapping_swaps_once() {
+        let older = window(&[(10, 0, 1), (11, 0, 2), (11, 1, 3)]);
+        let newer = window(&[(11, 0, 2), (11, 1, 3), (12, 0, 4)]);
+
+        let combined = combine_keyed([older, newer])
+            .into_values()
+            .collect::<Vec<_>>();
+
+        assert_eq!(combined, ticks(&[1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn combine_keeps_the_ticks_around_a_gap() {
+        let combined = combine_keyed([window(&[(10, 0, 1)]), window(&[(50, 2, 7)])]);
+
+        assert_eq!(
+            combined.keys().copied().collect::<Vec<_>>(),
+            [(10, 0), (50, 2)]
+        );
+        assert_eq!(combined.into_values().collect::<Vec<_>>(), ticks(&[1, 7]));
+    }
+
+    #[test]
+    fn combine_orders_windows_given_out_of_order() {
+        let first = window(&[(10, 1, 2), (10, 0, 1)]);
+        let second = window(&[(20, 0, 3), (21, 0, 4)]);
+
+        let in_order = combine_keyed([first.clone(), second.clone()]);
+        let reversed = combine_keyed([second, first]);
+
+        assert_eq!(in_order, reversed);
+        assert_eq!(
+            reversed.into_values().collect::<Vec<_>>(),
+            ticks(&[1, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn combine_windows_reads_overlapping_files() {
+        let directory =
+            std::env::temp_dir().join(format!("combine_windows_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let newer = directory.join("11-12.jsonl");
+        let older = directory.join("10-11.jsonl");
+        fs::write(&newer, row(12, 0, 4) + &row(11, 0, 2)).unwrap();
+        fs::write(&older, row(11, 0, 2) + &row(10, 0, 1)).unwrap();
+
+        let combined = combine_windows(&[newer, older.clone()]);
+        let missing = combine_windows(&[older, directory.join("missing.jsonl")]);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(combined.unwrap(), ticks(&[1, 2, 4]));
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn validate_ticks_requires_two_ticks() {
+        assert!(validate_ticks(&[]).is_err());
+        assert!(validate_ticks(&ticks(&[1])).is_err());
+        assert!(validate_ticks(&ticks(&[1, 2])).is_ok());
+    }
+
+    #[test]
+    fn build_elf_rejects_a_single_tick_before_writing() {
+        let destination = std::env::temp_dir().join(format!("data_{}.rs", std::process::id()));
+        let result = build_elf(ticks(&[1]), destination.to_str().unwrap(), "unused");
+
+        assert!(result.is_err());
+        assert!(!destination.exists());
+    }
+
+    #[test]
+    fn stdin_reads_piped_jsonl() {
+        let input = [row(1, 0, 10), row(1, 1, 12), row(2, 0, 9)].concat();
+
+        let ticks = read_ticks_from_stdin(&mut input.as_bytes(), false).unwrap();
+
+        assert_eq!(ticks, self::ticks(&[10, 12, 9]));
+    }
+
+    #[test]
+    fn stdin_rejects_empty_input() {
+        let error = read_ticks_from_stdin(&mut &b""[..], false).unwrap_err();
+
+        assert_eq!(error.to_string(), "No ticks received on stdin");
+    }
+
+    #[test]
+    fn invalid_csv_ticks_name_their_line() {
+        let input = "tick\n1\n\nx\n2\n";
+
+        let error = read_ticks_from_reader(&mut input.as_bytes()).unwrap_err();
+
+        assert_eq!(error.to_string(), "Invalid tick on line 4: \"x\"");
+    }
+
+    #[test]
+    fn parse_tick_accepts_integer_negative_and_fractional_ticks() {
+        assert_eq!(parse_tick("887272").unwrap(), Fixed::from_num(887272));
+        assert_eq!(parse_tick("-5").unwrap(), Fixed::from_num(-5));
+        assert_eq!(parse_tick("1.5").unwrap(), Fixed::from_num(1.5));
+    }
+
+    #[test]
+    fn parse_tick_rejects_malformed_and_out_of_range_ticks() {
+        assert!(parse_tick("abc").is_err());
+        assert!(parse_tick("").is_err());
+        assert!(parse_tick("99999999").is_err());
+    }
+
+    #[test]
+    fn per_block_keeps_the_last_tick_of_each_block() {
+        let input = [row(1, 0, 10), row(1, 1, 11), row(2, 0, 20), row(2, 1, 21)].concat();
+
+        let per_block = read_ticks_from_jsonl(&mut input.as_bytes(), true).unwrap();
+        let per_swap = read_ticks_from_jsonl(&mut input.as_bytes(), false).unwrap();
+
+        assert_eq!(per_block, ticks(&[11, 21]));
+        assert_eq!(per_swap, ticks(&[10, 11, 20, 21]));
+    }
+}
@@ -0,0 +1,208 @@
+//! Fixed-point helpers shared by the host-side volatility computations.
+
+use crate::build_elf::NumberBytes;
+use fixed::traits::Fixed as FixedPoint;
+use fixed::types::{I24F40, I88F40};
+use thiserror::Error;
+
+/// Fixed-point type used by the guest program for the volatility computation.
+pub type Fixed = I24F40;
+
+/// Wide fixed-point type for [`tick_volatility_exact`], with the fraction bits of [`Fixed`].
+pub type WideFixed = I88F40;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConversionError {
+    #[error("tick {0} is outside the representable I24F40 range")]
+    OutOfRange(i64),
+}
+
+/// Decodes big-endian fixed-point bytes, the encoding of the ticks and of the public values.
+pub fn to_fixed(bytes: NumberBytes) -> Fixed {
+    Fixed::from_be_bytes(bytes)
+}
+
+/// Converts an integer tick straight to [`Fixed`], without the byte round-trip of
+/// [`encode_tick`] and [`to_fixed`], rejecting ticks that do not fit in the integer part of I24F40.
+pub fn fixed_from_tick(tick: i64) -> Result<Fixed, ConversionError> {
+    Fixed::checked_from_num(tick).ok_or(ConversionError::OutOfRange(tick))
+}
+
+/// [`fixed_from_tick`] over a batch, failing on the first tick out of range.
+pub fn fixed_slice_from_ticks(ticks: &[i64]) -> Result<Vec<Fixed>, ConversionError> {
+    ticks.iter().map(|tick| fixed_from_tick(*tick)).collect()
+}
+
+/// Encodes an integer tick as big-endian fixed-point bytes, rejecting ticks that do not fit
+/// in the integer part of I24F40.
+pub fn encode_tick(tick: i64) -> Result<NumberBytes, ConversionError> {
+    fixed_from_tick(tick).map(Fixed::to_be_bytes)
+}
+
+/// Intermediate sums of [`tick_volatility`], for diffing the accumulation across backends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VolatilityBreakdown<F> {
+    pub n: usize,
+    pub sum_u: F,
+    pub sum_u2: F,
+    pub s2: F,
+}
+
+/// Sample variance of the tick deltas, accumulated exactly as the guest program does.
+/// Generic over the fixed-point type so a wider type (e.g. `I40F24`) can be used for
+/// high-variance pools; the guest and `calculate_public_data` use [`Fixed`].
+pub fn tick_volatility<F: FixedPoint>(ticks: &[F], n_inv_sqrt: F, n1_inv: F) -> F {
+    tick_volatility_detailed(ticks, n_inv_sqrt, n1_inv).s2
+}
+
+/// Same computation as [`tick_volatility`], also returning the intermediate sums.
+pub fn tick_volatility_detailed<F: FixedPoint>(
+    ticks: &[F],
+    n_inv_sqrt: F,
+    n1_inv: F,
+) -> VolatilityBreakdown<F> {
+    let mut ticks_prev = ticks[0];
+    let (sum_u, sum_u2) =
+        ticks
+            .iter()
+            .skip(1)
+            .fold((F::ZERO, F::ZERO), |(su, su2), &ticks_curr| {
+                let delta = ticks_curr - ticks_prev;
+                ticks_prev = ticks_curr;
+                (su + delta * n_inv_sqrt, su2 + delta * delta * n1_inv)
+            });
+    VolatilityBreakdown {
+        n: ticks.len(),
+        sum_u,
+        sum_u2,
+        // Rounding of the products can leave a negative variance, clamped like the guest
+        s2: (sum_u2 - (sum_u * sum_u) * n1_inv).max(F::ZERO),
+    }
+}
+
+/// Sample variance of the tick deltas with the integer deltas and their squares summed
+/// exactly in `i128`, rounded once to [`WideFixed`] by the final division. Unlike
+/// [`tick_volatility`] under [`Fixed`], it cannot overflow for ticks within the uniswap range,
+/// whatever the variance. The ticks are whole, as [`encode_tick`] makes them.
+pub fn tick_volatility_exact(ticks: &[Fixed]) -> WideFixed {
+    let n = ticks.len() as i128;
+    let (sum_d, sum_d2) = ticks.windows(2).fold((0i128, 0i128), |(sd, sd2), pair| {
+        let delta = pair[1].to_num::<i128>() - pair[0].to_num::<i128>();
+        (sd + delta, sd2 + delta * delta)
+    });
+    // sum_u2 - sum_u^2 * n1_inv = (n * sum_d2 - sum_d^2) / (n * (n - 1))
+    WideFixed::from_num(n * sum_d2 - sum_d * sum_d) / WideFixed::from_num(n * (n - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_an_in_range_tick() {
+        // The uniswap tick range is [-887272, 887272]
+        assert_eq!(fixed_from_tick(-887272), Ok(Fixed::from_num(-887272)));
+        assert_eq!(
+            to_fixed(encode_tick(887272).unwrap()),
+            Fixed::from_num(887272)
+        );
+        // I24F40 holds the integers in [-2^23, 2^23)
+        assert!(fixed_from_tick(-(1 << 23)).is_ok());
+    }
+
+    #[test]
+    fn rejects_an_out_of_range_tick() {
+        assert_eq!(
+            fixed_from_tick(1 << 23),
+            Err(ConversionError::OutOfRange(1 << 23))
+        );
+        assert_eq!(
+            encode_tick(-(1 << 23) - 1),
+            Err(ConversionError::OutOfRange(-(1 << 23) - 1))
+        );
+        assert!(fixed_slice_from_ticks(&[0, i64::MAX]).is_err());
+    }
+    #[test]
+    fn exact_volatility_matches_the_guest_on_small_deltas() {
+        let ticks = fixed_slice_from_ticks(&[200000, 199990, 200005, 199952, 199957]).unwrap();
+        let n = Fixed::from_num(ticks.len());
+        let s2 = tick_volatility(&ticks, Fixed::ONE / n.sqrt(), Fixed::ONE / (n - Fixed::ONE));
+
+        let exact = tick_volatility_exact(&ticks);
+
+        assert!((exact.to_num::<f64>() - s2.to_num::<f64>()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn exact_volatility_stays_correct_on_high_variance() {
+        // Ticks jumping across the whole uniswap range: every squared delta is about 2^41,
+        // far beyond the 2^23 of I24F40
+        let n = 8192;
+        let ticks = (0..n)
+            .map(|i| fixed_from_tick(if i % 2 == 0 { -887272 } else { 887272 }).unwrap())
+            .collect::<Vec<_>>();
+
+        let exact = tick_volatility_exact(&ticks);
+
+        // n - 1 deltas of +-d summing to d, so s2 = d^2 * (1 - 1 / (n * (n - 1)))
+        let d2 = 1774544f64 * 1774544f64;
+        let expected = d2 * (1.0 - 1.0 / (n * (n - 1)) as f64);
+        assert!((exact.to_num::<f64>() - expected).abs() / expected < 1e-12);
+    }
+
+    #[test]
+    fn fixed_from_tick_matches_the_byte_round_trip() {
+        for tick in [-887272, -1, 0, 1, 199957, 887272, (1 << 23) - 1] {
+            assert_eq!(
+                fixed_from_tick(tick).unwrap(),
+                to_fixed(encode_tick(tick).unwrap())
+            );
+        }
+        assert_eq!(
+            fixed_slice_from_ticks(&[-3, 5]).unwrap(),
+            [
+                to_fixed(encode_tick(-3).unwrap()),
+                to_fixed(encode_tick(5).unwrap())
+            ]
+        );
+    }
+
+    #[test]
+    fn detailed_volatility_returns_the_same_variance() {
+        let ticks = fixed_slice_from_ticks(&[200000, 199990, 200005, 199952, 199957]).unwrap();
+        let n = Fixed::from_num(ticks.len());
+        let (n_inv_sqrt, n1_inv) = (Fixed::ONE / n.sqrt(), Fixed::ONE / (n - Fixed::ONE));
+
+        let detailed = tick_volatility_detailed(&ticks, n_inv_sqrt, n1_inv);
+
+        assert_eq!(detailed.n, ticks.len());
+        assert_eq!(detailed.s2, tick_volatility(&ticks, n_inv_sqrt, n1_inv));
+        assert_eq!(
+            detailed.s2,
+            (detailed.sum_u2 - detailed.sum_u * detailed.sum_u * n1_inv).max(Fixed::ZERO)
+        );
+    }
+
+    #[test]
+    fn volatility_agrees_across_fixed_point_widths() {
+        use fixed::types::I40F24;
+
+        fn s2<F: FixedPoint>(ticks: &[i64]) -> f64 {
+            let ticks = ticks
+                .iter()
+                .map(|tick| F::from_num(*tick))
+                .collect::<Vec<_>>();
+            let (n, one) = (F::from_num(ticks.len()), F::from_num(1));
+            tick_volatility(&ticks, one / n.sqrt(), one / (n - one)).to_num()
+        }
+
+        let ticks = [200000, 199990, 200005, 199952, 199957];
+        let narrow = s2::<Fixed>(&ticks);
+        let wide = s2::<I40F24>(&ticks);
+
+        // Only the fraction bits differ, 2^-24 of I40F24 against 2^-40 of I24F40
+        assert!((narrow - wide).abs() / narrow < 1e-5);
+        let exact = tick_volatility_exact(&fixed_slice_from_ticks(&ticks).unwrap());
+        assert!((exact.to_num::<f64>() - narrow).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,265 @@
+//! Cross-checks the independent volatility implementations on the same ticks, so that a
+//! backend drifting from the others (as the nexus `n1_inv` once did) is caught in one run.
+//! The ABI encoding of the public values is checked first against the decoding of the contract.
+//! `compare_backends` reports the same backends on any ticks without failing on divergence.
+
+use crate::build_elf::{read_ticks, NumberBytes, TickSource};
+use crate::common::{tick_volatility, to_fixed, Fixed};
+use crate::prove::PublicValuesTuple;
+use crate::verbosity::status;
+use alloy_sol_types::SolType;
+use anyhow::{bail, Context, Result};
+
+#[allow(dead_code)]
+#[path = "../../../../axiom/src/utils.rs"]
+mod axiom_utils;
+
+#[allow(dead_code)]
+#[path = "../../../../nexus/src/volatility.rs"]
+mod nexus_volatility;
+
+/// Shared fixture of 1024 realistic ticks, a seeded random walk small enough for every backend.
+pub const DEFAULT_FIXTURE: &str = "../fixtures/crosscheck_ticks.csv";
+
+/// Largest accepted relative difference to the reference. The nexus backend accumulates in
+/// f32 and lands within about 1e-6 of the f64 reference on the fixture; a wrong normalization
+/// such as 1/n instead of 1/(n-1) is off by 1/n, about 1e-3.
+const TOLERANCE: f64 = 1e-4;
+
+/// A volatility implementation, returning the squared volatility of the ticks.
+type Backend = (&'static str, fn(&[NumberBytes]) -> Result<f64>);
+
+/// The first backend is the reference the others are compared to.
+const BACKENDS: &[Backend] = &[
+    ("axiom", axiom),
+    ("nexus", nexus),
+    ("sp1-common", sp1_common),
+    #[cfg(feature = "crosscheck-exec")]
+    ("sp1-exec", sp1_exec),
+];
+
+/// Squared volatilities whose public values are checked. A sign or endianness bug turns the
+/// negative ones, from an overflowing guest, into huge positive values.
+const ENCODING_SAMPLES: [f64; 5] = [0.0, 1.5, 12345.678_9, -2.25, -0.000_001];
+
+/// Decodes the `bytes8` field `index` of the ABI encoded public values like the contract: the
+/// left-aligned 8 bytes of its 32 byte word, read as a big-endian int64 with 40 fractional bits.
+fn decode_like_contract(public_values: &[u8], index: usize) -> Result<f64> {
+    let word = public_values
+        .get(index * 32..(index + 1) * 32)
+        .with_context(|| format!("No field {} in the public values", index))?;
+    if word[8..].iter().any(|byte| *byte != 0) {
+        bail!("Field {} is not a left-aligned bytes8", index);
+    }
+    let raw = i64::from_be_bytes(word[..8].try_into()?);
+    Ok(raw as f64 / (1u64 << Fixed::FRAC_NBITS) as f64)
+}
+
+/// Encodes the public values as the guest does and checks that every `bytes8` field decodes
+/// on-chain to the value it was encoded from.
+fn check_public_values_encoding() -> Result<()> {
+    let n = Fixed::from_num(8192);
+    for sample in ENCODING_SAMPLES {
+        let fields = [
+            Fixed::ONE / n.sqrt(),
+            Fixed::ONE / (n - Fixed::ONE),
+            Fixed::from_num(sample),
+            n,
+        ];
+        let public_values = PublicValuesTuple::abi_encode(&(
+            fields[0].to_be_bytes(),
+            fields[1].to_be_bytes(),
+            fields[2].to_be_bytes(),
+            fields[3].to_be_bytes(),
+            [0u8; 32],
+        ));
+        for (index, field) in fields.iter().enumerate() {
+            let decoded = decode_like_contract(&public_values, index)?;
+            if decoded != field.to_num::<f64>() {
+                bail!(
+                    "Public value {} encodes {} but the contract decodes {}",
+                    index,
+                    field,
+                    decoded
+                );
+            }
+        }
+    }
+    println!("Public values decode on-chain to the encoded values");
+    Ok(())
+}
+
+fn to_floats(ticks: &[NumberBytes]) -> impl Iterator<Item = f64> + '_ {
+    ticks.iter().map(|tick| to_fixed(*tick).to_num())
+}
+
+fn axiom(ticks: &[NumberBytes]) -> Result<f64> {
+    let ticks = to_floats(ticks).collect::<Vec<_>>();
+    Ok(axiom_utils::calculate_original(&ticks))
+}
+
+fn nexus(ticks: &[NumberBytes]) -> Result<f64> {
+    let ticks = to_floats(ticks).map(|tick| tick as f32).collect::<Vec<_>>();
+    Ok(nexus_volatility::Volatility::new(&ticks).s2 as f64)
+}
+
+fn sp1_common(ticks: &[NumberBytes]) -> Result<f64> {
+    let n = Fixed::from_num(ticks.len());
+    let ticks = ticks.iter().map(|tick| to_fixed(*tick)).collect::<Vec<_>>();
+    let s2 = tick_volatility(&ticks, Fixed::ONE / n.sqrt(), Fixed::ONE / (n - Fixed::ONE));
+    Ok(s2.to_num())
+}
+
+/// Builds the guest over the ticks and executes it, without proving.
+#[cfg(feature = "crosscheck-exec")]
+fn sp1_exec(ticks: &[NumberBytes]) -> Result<f64> {
+    let (elf, stdin, client) = crate::prove::setup(
+        crate::ELF_PATH,
+        ticks.to_vec(),
+        sp1_sdk::ProverClient::new(),
+    )?;
+    let digest = crate::prove::ticks_digest(ticks);
+    Ok(crate::prove::exec(elf.as_slice(), stdin, &client, digest, None)?.to_num())
+}
+
+/// Squared volatility of the ticks by every backend, the reference first.
+fn run_backends(ticks: &[NumberBytes]) -> Result<Vec<(&'static str, f64)>> {
+    BACKENDS
+        .iter()
+        .map(|(name, backend)| {
+            let s2 = backend(ticks).with_context(|| format!("Backend {} failed", name))?;
+            Ok((*name, s2))
+        })
+        .collect()
+}
+
+/// Prints the squared volatility of the ticks of `path` by every backend, then the absolute
+/// and relative difference of every pair, making the quantization error of the fixed point
+/// backends visible. Unlike `crosscheck` no difference is an error.
+pub fn compare_backends(path: &str, per_block: bool) -> Result<()> {
+    let ticks = read_ticks(TickSource::from_path(path.to_string()), per_block)?;
+    status!("Comparing backends on {} ticks of {}", ticks.len(), path);
+
+    let results = run_backends(&ticks)?;
+    for (name, s2) in &results {
+        println!("{:<12} s2 = {}", name, s2);
+    }
+    println!(
+        "{:<12} {:<12} {:>16} {:>16}",
+        "backend", "backend", "difference", "relative"
+    );
+    for (index, (name, s2)) in results.iter().enumerate() {
+        for (other_name, other) in &results[index + 1..] {
+            let difference = other - s2;
+            println!(
+                "{:<12} {:<12} {:>16.6e} {:>16.3e}",
+                name,
+                other_name,
+                difference,
+                (difference / s2).abs()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Runs every backend on the fixture, failing if any differs from the reference by more
+/// than the tolerance.
+pub fn crosscheck(fixture: &str) -> Result<()> {
+    check_public_values_encoding()?;
+
+    let ticks = read_ticks(TickSource::Csv(fixture.to_string()), false)?;
+    status!("Cross-checking {} ticks from {}", ticks.len(), fixture);
+
+    let results = run_backends(&ticks)?;
+
+    let (reference_name, reference) = results[0];
+    let mut diverged = Vec::new();
+    for (name, s2) in &results {
+        let error = ((s2 - reference) / reference).abs();
+        println!("{:<12} s2 = {:<24} relative error {:.3e}", name, s2, error);
+        if error.is_nan() || error > TOLERANCE {
+            diverged.push(*name);
+        }
+    }
+    if !diverged.is_empty() {
+        bail!(
+            "{} diverged from {} by more than {}",
+            diverged.join(", "),
+            reference_name,
+            TOLERANCE
+        );
+    }
+    println!("All backends agree within {}", TOLERANCE);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Run from the script directory by cargo test, like the command line default
+    #[test]
+    fn backends_agree_on_the_fixture() {
+        crosscheck(DEFAULT_FIXTURE).unwrap();
+    }
+
+    #[test]
+    fn fixed_is_within_the_quantization_error_of_f64() {
+        // Quantizing n1_inv = 1/(n-1) to 40 fractional bits alone is off by up to
+        // (n - 1) * 2^-41 relatively, about 5e-10 for the 1024 ticks of the fixture
+        const QUANTIZATION_TOLERANCE: f64 = 1e-9;
+        let ticks = read_ticks(TickSource::Csv(DEFAULT_FIXTURE.to_string()), false).unwrap();
+
+        let reference = axiom(&ticks).unwrap();
+        let fixed = sp1_common(&ticks).unwrap();
+
+        let error = ((fixed - reference) / reference).abs();
+        assert!(error < QUANTIZATION_TOLERANCE, "{} vs {}", fixed, reference);
+    }
+
+    #[test]
+    fn public_values_decode_like_the_contract() {
+        check_public_values_encoding().unwrap();
+
+        let s2 = Fixed::from_num(-2.25);
+        let public_values = PublicValuesTuple::abi_encode(&(
+            [0u8; 8],
+            [0u8; 8],
+            s2.to_be_bytes(),
+            [0u8; 8],
+            [0u8; 32],
+        ));
+        assert_eq!(decode_like_contract(&public_values, 2).unwrap(), -2.25);
+        // Right-aligned, as if encoded as an int64 rather than a bytes8
+        let mut shifted = public_values.clone();
+        shifted[64..96].rotate_left(8);
+        assert!(decode_like_contract(&shifted, 2).is_err());
+    }
+
+    /// Keccak-256 of the fixture ticks packed as big-endian I24F40, computed with
+    /// `openssl dgst -keccak-256` rather than tiny-keccak.
+    const FIXTURE_DIGEST: &str = "ad080de74a41bdcc45fe579401a8dc8140b2e336d7bb193b8e36fe5d9fdfb2d8";
+
+    #[test]
+    fn host_digest_is_the_keccak_of_the_packed_fixture() {
+        let ticks = read_ticks(TickSource::Csv(DEFAULT_FIXTURE.to_string()), false).unwrap();
+
+        assert_eq!(
+            crate::prove::ticks_digest(&ticks),
+            crate::prove::parse_digest(FIXTURE_DIGEST).unwrap()
+        );
+    }
+
+    // `exec` fails unless the digest committed by the guest equals the host digest
+    #[cfg(feature = "crosscheck-exec")]
+    #[test]
+    fn guest_commits_the_host_digest_of_the_fixture() {
+        let ticks = read_ticks(TickSource::Csv(DEFAULT_FIXTURE.to_string()), false).unwrap();
+        let digest = crate::prove::ticks_digest(&ticks);
+        let (elf, stdin, client) =
+            crate::prove::setup(crate::ELF_PATH, ticks, sp1_sdk::ProverClient::new()).unwrap();
+
+        crate::prove::exec(elf.as_slice(), stdin, &client, digest, None).unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+//! Archives proofs on an IPFS node through the `add` endpoint of its HTTP API, so that the CID can
+//! be referenced on-chain.
+
+use anyhow::{Context, Result};
+use reqwest::blocking::{multipart::Form, Client};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AddResponse {
+    hash: String,
+}
+
+/// Uploads and pins the file at `path` on the IPFS node whose API is at `api_url`, e.g.
+/// `http://127.0.0.1:5001`, returning its CID.
+pub fn upload(api_url: &str, path: &str) -> Result<String> {
+    let url = format!("{}/api/v0/add?pin=true", api_url.trim_end_matches('/'));
+    let form = Form::new()
+        .file("file", path)
+        .with_context(|| format!("Failed to read {}", path))?;
+    let response = Client::new()
+        .post(&url)
+        .multipart(form)
+        .send()
+        .with_context(|| format!("Failed to reach the IPFS API at {}", api_url))?
+        .error_for_status()
+        .context("IPFS rejected the upload")?;
+    let added: AddResponse = response
+        .json()
+        .context("Unexpected response of the IPFS API")?;
+    Ok(added.hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    /// Answers one request on a local port like the IPFS API would, with `status` and the JSON
+    /// `body`. The handle returns the request line and body the API received.
+    fn stub_api(
+        status: &'static str,
+        body: &'static str,
+    ) -> (String, JoinHandle<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api_url = format!("http://{}/", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut reader = BufReader::new(listener.accept().unwrap().0);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                match header.split_once(':') {
+                    Some((name, value)) if name.eq_ignore_ascii_case("content-length") => {
+                        length = value.trim().parse().unwrap()
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            let mut request_body = vec![0; length];
+            reader.read_exact(&mut request_body).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+            (
+                request_line,
+                String::from_utf8_lossy(&request_body).into_owned(),
+            )
+        });
+        (api_url, handle)
+    }
+
+    fn proof_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}.json", name, std::process::id()));
+        std::fs::write(&path, r#"{"proof":"0x1234"}"#).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn upload_returns_the_cid_of_the_pinned_proof() {
+        let (api_url, api) = stub_api("200 OK", r#"{"Name":"proof","Hash":"QmProof","Size":"18"}"#);
+        let path = proof_file("ipfs_upload");
+
+        let cid = upload(&api_url, &path);
+        let (request_line, request_body) = api.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(cid.unwrap(), "QmProof");
+        assert!(request_line.starts_with("POST /api/v0/add?pin=true "));
+        assert!(request_body.contains(r#"{"proof":"0x1234"}"#));
+    }
+
+    #[test]
+    fn upload_reports_a_rejected_upload() {
+        let (api_url, api) = stub_api("500 Internal Server Error", r#"{"Message":"full"}"#);
+        let path = proof_file("ipfs_rejected");
+
+        let error = upload(&api_url, &path).unwrap_err();
+        api.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(error.to_string(), "IPFS rejected the upload");
+    }
+}
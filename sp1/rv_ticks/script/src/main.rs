@@ -1,18 +1,37 @@
 //! A simple script to generate and verify the proof of a given program.
 
+mod bench;
 mod build_elf;
+mod common;
+mod crosscheck;
+mod ipfs;
 mod prove;
+#[path = "../../../../nexus/src/state.rs"]
+mod state;
+#[path = "../../../../nexus/src/swap.rs"]
+mod swap;
+mod verbosity;
 mod watcher;
+mod windows;
 
 use build_elf::{read_ticks, TickSource};
 use clap::Parser;
+use prove::{RunSummary, Sp1RvTicksFixture};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use verbosity::{detail, status};
 
 const ELF_PATH: &str = "../program/elf/riscv32im-succinct-zkvm-elf";
 
+/// Set by Ctrl-C, the watch loop stops once the window in flight is proven.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// A flag to specify ticks TickSource
+    /// A flag to specify ticks TickSource, `-` reads jsonl from stdin, comma separated jsonl
+    /// files are merged into one deduplicated block ordered sequence
     #[arg(short, long)]
     ticks: Option<String>,
 
@@ -23,38 +42,375 @@ struct Args {
     /// A flag to execute only, no proof generation
     #[arg(short, long)]
     execute: bool,
+
+    /// A flag to execute first, reporting the volatility right away, then prove the same build
+    #[arg(long, conflicts_with = "execute")]
+    execute_and_prove: bool,
+
+    /// Number of times a transient proving failure is retried
+    #[arg(long, default_value_t = 3)]
+    max_retries: u32,
+
+    /// A flag to prove on the SP1 prover network instead of locally
+    #[arg(long)]
+    network: bool,
+
+    /// Maximum number of files read into one window in watch mode, even if they cover fewer
+    /// than 8192 blocks
+    #[arg(long)]
+    max_files: Option<NonZeroUsize>,
+
+    /// Cross-check every volatility implementation on a ticks CSV, the shared fixture by default,
+    /// after the contract decoding of the public values. Build with `--features crosscheck-exec` to include the SP1 execution.
+    #[arg(long, num_args = 0..=1, default_missing_value = crosscheck::DEFAULT_FIXTURE)]
+    crosscheck: Option<String>,
+
+    /// Print the volatility of every --crosscheck backend on a tick file, csv or jsonl by its
+    /// extension, with their pairwise differences, without failing on them
+    #[arg(long, conflicts_with = "crosscheck")]
+    compare_backends: Option<String>,
+
+    /// Write a JSON summary of the run, with the volatility and the proof fixture, to this file
+    #[arg(long, conflicts_with = "watch")]
+    output_json: Option<String>,
+
+    /// Keep only the last tick of every block, by `evt_index`, when reading jsonl swaps
+    #[arg(long)]
+    per_block: bool,
+
+    /// File keeping the latest block proven in watch mode, so that a restart resumes from it
+    #[arg(long, requires = "watch")]
+    state: Option<String>,
+
+    /// Prove once the swaps of blocks START to END inclusive, read from the --watch directory
+    #[arg(long, num_args = 2, value_names = ["START", "END"], requires = "watch")]
+    block_range: Option<Vec<u64>>,
+
+    /// Prove the same input this many times after a single setup, reporting the proving times
+    #[arg(long, conflicts_with_all = ["watch", "execute", "execute_and_prove"])]
+    iterations: Option<NonZeroUsize>,
+
+    /// Write the proving times of --iterations to this file as JSON
+    #[arg(long, requires = "iterations")]
+    metrics_out: Option<String>,
+
+    /// Write the cycle count breakdown of the execution to this file
+    #[arg(long, conflicts_with = "watch")]
+    profile_out: Option<String>,
+
+    /// Regenerate fixture.json from a saved proof, the one of the last run by default, without
+    /// proving again
+    #[arg(long, num_args = 0..=1, default_missing_value = prove::PROOF_FILE)]
+    fixture_from: Option<String>,
+
+    /// Verify a saved proof, the one of the last run by default, without proving again
+    #[arg(long, num_args = 0..=1, default_missing_value = prove::PROOF_FILE)]
+    verify_in: Option<String>,
+
+    /// Hex keccak256 digest of the ticks the proof passed to --verify-in must commit to
+    #[arg(long, requires = "verify_in")]
+    verify_digest: Option<String>,
+
+    /// Prove the most recent ticks for each of these window sizes, e.g. 512,2048,8192, saving one
+    /// proof file per window. The ELF is rebuilt for every window
+    #[arg(
+        long,
+        value_delimiter = ',',
+        conflicts_with_all = ["watch", "execute", "execute_and_prove", "iterations"]
+    )]
+    windows: Option<Vec<usize>>,
+
+    /// Upload the proof to the IPFS node whose HTTP API is at this URL, e.g.
+    /// http://127.0.0.1:5001, adding its CID to the JSON summary
+    #[arg(long, conflicts_with_all = ["watch", "execute", "iterations", "windows"])]
+    ipfs_api: Option<String>,
+
+    /// Only print errors and the results, without the progress and timing lines
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print details of the inputs on top of the progress
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+}
+
+/// A first Ctrl-C lets the current window finish, a second one exits right away.
+fn install_shutdown_handler() {
+    let result = ctrlc::set_handler(|| {
+        if SHUTDOWN.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        println!("Shutting down after the current window, press Ctrl-C again to exit now");
+    });
+    if let Err(error) = result {
+        println!("Failed to install the Ctrl-C handler: {}", error);
+    }
+}
+
+/// The block to resume watching from, 0 when there is no state or it can't be read.
+fn saved_latest_block(state_path: Option<&str>) -> u64 {
+    let Some(path) = state_path else {
+        return 0;
+    };
+    match state::load_latest_block(path) {
+        Ok(Some(block)) => {
+            status!("Resuming from block {} saved in {}", block, path);
+            block
+        }
+        Ok(None) => 0,
+        Err(error) => {
+            println!("Warning: {:#}, starting from block 0", error);
+            0
+        }
+    }
+}
+
+/// Watches from `latest_block` until `shutdown` is set, checked between iterations so that the
+/// window in flight is always finished. Every new latest block is saved to the state file when
+/// given, errors are printed and the directory watched again. Returns the latest block.
+fn watch_until_shutdown(
+    shutdown: &AtomicBool,
+    mut latest_block: u64,
+    state_path: Option<&str>,
+    mut watch: impl FnMut(u64) -> anyhow::Result<u64>,
+) -> u64 {
+    while !shutdown.load(Ordering::SeqCst) {
+        match watch(latest_block) {
+            Ok(block) => {
+                latest_block = block;
+                status!("Latest block: {}", block);
+                if let Some(path) = state_path {
+                    if let Err(error) = state::save_latest_block(path, block) {
+                        println!("Failed to save the latest block: {:#}", error);
+                    }
+                }
+            }
+            Err(error) => println!("Error loading and proving {}", error),
+        }
+    }
+    latest_block
 }
 
 fn main() {
     let args = Args::parse();
+    verbosity::set(args.quiet, args.verbose);
+    if let Some(fixture) = args.crosscheck {
+        if let Err(error) = crosscheck::crosscheck(&fixture) {
+            println!("Cross-check failed: {}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(path) = args.compare_backends {
+        if let Err(error) = crosscheck::compare_backends(&path, args.per_block) {
+            println!("Backend comparison failed: {}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let mode = prove::ProverMode::from_flag(args.network);
+    if let Some(proof_path) = args.fixture_from {
+        let fixture = mode
+            .client()
+            .and_then(|client| Sp1RvTicksFixture::refresh(ELF_PATH, &proof_path, &client));
+        match fixture {
+            Ok(fixture) => println!("Fixture regenerated from {}: {:?}", proof_path, fixture),
+            Err(error) => {
+                println!("Failed to regenerate the fixture: {}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(proof_path) = args.verify_in {
+        let verified = args
+            .verify_digest
+            .as_deref()
+            .map(prove::parse_digest)
+            .transpose()
+            .and_then(|digest| {
+                let client = mode.client()?;
+                prove::verify_saved_proof(ELF_PATH, &proof_path, &client, digest)
+            });
+        match verified {
+            Ok(fixture) => println!("Proof {} is valid: {:?}", proof_path, fixture),
+            Err(error) => {
+                println!("Proof {} is invalid: {}", proof_path, error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
     match args.watch {
         // Continually read files from a dir.
         // When there are new files, load the ticks and generate a new proof using those ticks.
         // Start from the latest available block and load backwards until there are >= 8192 values for the proof.
-        Some(path) => {
-            let mut latest_block = 0;
-            loop {
-                match watcher::watch_directory(ELF_PATH, &path, latest_block, args.execute) {
-                    Ok(block) => {
-                        latest_block = block;
-                        println!("Latest block: {}", block);
-                    }
-                    Err(error) => println!("Error loading and proving {}", error),
-                }
+        Some(path) if args.block_range.is_some() => {
+            let range = args.block_range.unwrap();
+            let result = watcher::read_block_range(&path, (range[0], range[1]), args.per_block)
+                .and_then(|ticks| {
+                    watcher::prove_ticks(ELF_PATH, ticks, args.execute, args.max_retries, mode)
+                });
+            if let Err(error) = result {
+                println!("Error loading and proving the block range: {}", error);
+                std::process::exit(1);
             }
         }
+        Some(path) => {
+            install_shutdown_handler();
+            let latest_block = saved_latest_block(args.state.as_deref());
+            let latest_block = watch_until_shutdown(
+                &SHUTDOWN,
+                latest_block,
+                args.state.as_deref(),
+                |latest_block| {
+                    watcher::watch_directory(
+                        ELF_PATH,
+                        &path,
+                        latest_block,
+                        args.execute,
+                        args.max_retries,
+                        mode,
+                        args.max_files,
+                        args.per_block,
+                    )
+                },
+            );
+            status!("Watcher stopped at block {}", latest_block);
+        }
         None => {
             let ticks_source = match args.ticks {
-                Some(ticks) => TickSource::Jsonl(ticks),
+                Some(ticks) => TickSource::jsonl(ticks),
                 None => TickSource::Random,
             };
-            let ticks = read_ticks(ticks_source);
-            let (elf, stdin, client) = prove::setup(ELF_PATH, ticks).unwrap();
-            if args.execute {
-                prove::exec(elf.as_slice(), stdin, client).unwrap();
+            let ticks = read_ticks(ticks_source, args.per_block).unwrap();
+            if let Some(sizes) = &args.windows {
+                let proofs =
+                    windows::prove_windows(ELF_PATH, &ticks, sizes, mode, args.max_retries)
+                        .unwrap();
+                for proof in &proofs {
+                    println!("{}", proof);
+                }
+                if let Some(path) = &args.output_json {
+                    std::fs::write(path, serde_json::to_string_pretty(&proofs).unwrap()).unwrap();
+                }
+                return;
+            }
+            let num_ticks = ticks.len();
+            let client = mode.client().unwrap();
+            let digest = prove::ticks_digest(&ticks);
+            detail!("Read {} ticks, digest {}", num_ticks, prove::hex(&digest));
+            let (elf, stdin, client) = prove::setup(ELF_PATH, ticks, client).unwrap();
+            if let Some(iterations) = args.iterations {
+                let stats = bench::benchmark(
+                    elf.as_slice(),
+                    stdin,
+                    &client,
+                    iterations.get(),
+                    args.max_retries,
+                )
+                .unwrap();
+                println!("{}", stats);
+                if let Some(path) = &args.metrics_out {
+                    stats.write(path).unwrap();
+                }
+                return;
+            }
+            let summary = if args.execute {
+                let start_time = Instant::now();
+                let s2 = prove::exec(
+                    elf.as_slice(),
+                    stdin,
+                    &client,
+                    digest,
+                    args.profile_out.as_deref(),
+                )
+                .unwrap();
+                RunSummary {
+                    execution_ms: Some(start_time.elapsed().as_millis() as u64),
+                    ..RunSummary::new(num_ticks, digest, s2)
+                }
             } else {
-                prove::prove(elf.as_slice(), stdin, client).unwrap();
+                let mut execution_ms = None;
+                if args.execute_and_prove {
+                    // The ELF and stdin from the single setup are shared by both runs
+                    let start_time = Instant::now();
+                    prove::exec(
+                        elf.as_slice(),
+                        stdin.clone(),
+                        &client,
+                        digest,
+                        args.profile_out.as_deref(),
+                    )
+                    .unwrap();
+                    execution_ms = Some(start_time.elapsed().as_millis() as u64);
+                }
+                let start_time = Instant::now();
+                let fixture =
+                    prove::prove(elf.as_slice(), stdin, client, args.max_retries, digest).unwrap();
+                let prove_ms = Some(start_time.elapsed().as_millis() as u64);
+                // A failed upload leaves the proof saved locally, only its CID is missing
+                let ipfs_cid = args.ipfs_api.as_deref().and_then(|api_url| {
+                    match ipfs::upload(api_url, prove::PROOF_FILE) {
+                        Ok(cid) => {
+                            println!("Proof uploaded to IPFS: {}", cid);
+                            Some(cid)
+                        }
+                        Err(error) => {
+                            println!(
+                                "Failed to upload {} to IPFS: {:#}",
+                                prove::PROOF_FILE,
+                                error
+                            );
+                            None
+                        }
+                    }
+                });
+                RunSummary {
+                    execution_ms,
+                    prove_ms,
+                    ipfs_cid,
+                    ..RunSummary::proved(num_ticks, digest, fixture)
+                }
+            };
+            if let Some(path) = &args.output_json {
+                summary.write(path).unwrap();
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shutdown_flag_breaks_the_watch_loop_after_the_current_window() {
+        let shutdown = AtomicBool::new(false);
+        let state_path =
+            std::env::temp_dir().join(format!("sp1_watch_state_{}", std::process::id()));
+        let mut windows = 0;
+
+        let latest_block =
+            watch_until_shutdown(&shutdown, 100, state_path.to_str(), |latest_block| {
+                windows += 1;
+                match windows {
+                    2 => anyhow::bail!("no new blocks"),
+                    3 => shutdown.store(true, Ordering::SeqCst),
+                    _ => {}
+                }
+                Ok(latest_block + 10)
+            });
+        let saved = state::load_latest_block(&state_path).unwrap();
+        std::fs::remove_file(&state_path).unwrap();
+
+        // The failed window is watched again, the one during which the flag is set still finishes
+        assert_eq!(windows, 3);
+        assert_eq!(latest_block, 120);
+        assert_eq!(saved, Some(120));
+        assert_eq!(
+            watch_until_shutdown(&shutdown, 7, None, |_| unreachable!()),
+            7
+        );
+    }
+}
@@ -1,15 +1,31 @@
 //! A simple script to generate and verify the proof of a given program.
 
 use crate::build_elf::{self, NumberBytes};
+use crate::common::{tick_volatility_detailed, tick_volatility_exact, to_fixed, Fixed, WideFixed};
 use crate::prove;
+use crate::verbosity::{detail, status};
 use alloy_sol_types::{sol, SolType};
-use anyhow::Result;
-use fixed::types::I24F40 as Fixed;
+use anyhow::{bail, Context, Result};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use sp1_sdk::{HashableKey, ProverClient, SP1Stdin};
+use sp1_sdk::{
+    ExecutionReport, HashableKey, ProverClient, SP1PlonkBn254Proof, SP1Stdin, SP1VerifyingKey,
+};
 use std::fs::read;
+use std::io::ErrorKind;
 use std::path::PathBuf;
-use std::time::Instant;
+use std::thread;
+use std::time::{Duration, Instant};
+use tiny_keccak::{Hasher, Keccak};
+
+/// Delay before the first retry, doubled on every subsequent attempt.
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Environment variable holding the private key used to authenticate with the SP1 prover network.
+const NETWORK_KEY_VAR: &str = "SP1_PRIVATE_KEY";
+
+/// Proof saved by every proving run, which `--fixture-from` reads back by default.
+pub const PROOF_FILE: &str = "proof-with-io.json";
 
 /// The public values encoded as a tuple that can be easily deserialized inside Solidity.
 pub type PublicValuesTuple = sol! {
@@ -19,7 +35,7 @@ pub type PublicValuesTuple = sol! {
 /// A fixture that can be used to test the verification of SP1 zkVM proofs inside Solidity.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct Sp1RvTicksFixture {
+pub struct Sp1RvTicksFixture {
     s: i64,
     s2: i64,
     n: u64,
@@ -30,6 +46,105 @@ struct Sp1RvTicksFixture {
     public_values: String,
     proof: String,
 }
+
+impl Sp1RvTicksFixture {
+    /// Builds the fixture of a proof from the public values it commits to.
+    pub fn from_proof(proof: &SP1PlonkBn254Proof, vk: &SP1VerifyingKey) -> Result<Self> {
+        let bytes = proof.public_values.as_slice();
+        let (n_inv_sqrt, n1_inv, s2, n, digest) = PublicValuesTuple::abi_decode(bytes, false)?;
+        let s2_bytes: NumberBytes = s2.as_slice().try_into()?;
+        let s = to_fixed(s2_bytes).sqrt();
+        Ok(Self {
+            n_inv_sqrt: u64::from_be_bytes(n_inv_sqrt.as_slice().try_into()?),
+            n1_inv: u64::from_be_bytes(n1_inv.as_slice().try_into()?),
+            s: i64::from_be_bytes(s.to_be_bytes()),
+            s2: i64::from_be_bytes(s2_bytes),
+            n: u64::from_be_bytes(n.as_slice().try_into()?),
+            digest: digest.to_string(),
+            vkey: vk.bytes32().to_string(),
+            public_values: proof.public_values.bytes().to_string(),
+            proof: proof.bytes().to_string(),
+        })
+    }
+
+    /// Regenerates the fixture of a proof saved by a previous run, without proving again. `vk`
+    /// must be the verifying key of the program that produced it.
+    pub fn from_saved_proof(proof_path: &str, vk: &SP1VerifyingKey) -> Result<Self> {
+        let proof = SP1PlonkBn254Proof::load(proof_path)
+            .with_context(|| format!("Failed to load proof {}", proof_path))?;
+        Self::from_proof(&proof, vk)
+    }
+
+    /// Regenerates `fixture.json` from a saved proof of the program `elf_path`, whose setup gives
+    /// the verifying key.
+    pub fn refresh(elf_path: &str, proof_path: &str, client: &ProverClient) -> Result<Self> {
+        let elf = read(elf_path).with_context(|| format!("Failed to read {}", elf_path))?;
+        let (_, vk) = client.setup(&elf);
+        let fixture = Self::from_saved_proof(proof_path, &vk)?;
+        fixture.save()?;
+        Ok(fixture)
+    }
+
+    /// Writes the fixture to `fixture.json` in the manifest directory, for the Solidity tests.
+    pub fn save(&self) -> Result<()> {
+        let fixture_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        std::fs::create_dir_all(&fixture_path).expect("failed to create fixture path");
+        std::fs::write(
+            fixture_path.join("fixture.json"),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// Consolidated record of a successful run, written with `--output-json`. The volatility is
+/// dequantized, the raw public values are in the fixture when a proof was generated.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummary {
+    pub ticks: usize,
+    pub digest: String,
+    pub volatility_squared: f64,
+    pub std_dev: f64,
+    pub execution_ms: Option<u64>,
+    pub prove_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fixture: Option<Sp1RvTicksFixture>,
+    /// CID of the proof file uploaded with `--ipfs-api`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipfs_cid: Option<String>,
+}
+
+impl RunSummary {
+    pub fn new(ticks: usize, digest: [u8; 32], s2: Fixed) -> Self {
+        Self {
+            ticks,
+            digest: format!("0x{}", hex(&digest)),
+            volatility_squared: s2.to_num(),
+            std_dev: s2.sqrt().to_num(),
+            execution_ms: None,
+            prove_ms: None,
+            fixture: None,
+            ipfs_cid: None,
+        }
+    }
+
+    /// Summary of a proof, with the squared volatility read back from its fixture.
+    pub fn proved(ticks: usize, digest: [u8; 32], fixture: Sp1RvTicksFixture) -> Self {
+        let s2 = Fixed::from_bits(fixture.s2);
+        Self {
+            fixture: Some(fixture),
+            ..Self::new(ticks, digest, s2)
+        }
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        status!("Run summary written to {}", path);
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 pub struct PublicData {
     pub n_inv_sqrt: Fixed,
@@ -37,39 +152,161 @@ pub struct PublicData {
     pub s2: Fixed,
 }
 
-pub fn setup(elf_path: &str, ticks: Vec<NumberBytes>) -> Result<(Vec<u8>, SP1Stdin, ProverClient)> {
+/// Selects where proofs are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProverMode {
+    /// Prove on this machine.
+    Local,
+    /// Dispatch proving to the SP1 prover network.
+    Network,
+}
+
+impl ProverMode {
+    pub fn from_flag(network: bool) -> Self {
+        if network {
+            ProverMode::Network
+        } else {
+            ProverMode::Local
+        }
+    }
+
+    /// Constructs the prover client for this mode, failing early if the
+    /// network key is missing rather than deep inside the SDK.
+    pub fn client(self) -> Result<ProverClient> {
+        match self {
+            ProverMode::Local => Ok(ProverClient::new()),
+            ProverMode::Network => {
+                if std::env::var(NETWORK_KEY_VAR).map_or(true, |key| key.is_empty()) {
+                    bail!(
+                        "{} must be set to prove on the SP1 prover network",
+                        NETWORK_KEY_VAR
+                    );
+                }
+                Ok(ProverClient::network())
+            }
+        }
+    }
+}
+
+pub fn setup(
+    elf_path: &str,
+    ticks: Vec<NumberBytes>,
+    client: ProverClient,
+) -> Result<(Vec<u8>, SP1Stdin, ProverClient)> {
     build_elf::build_elf(ticks.clone(), "src/data.rs", "../program")?;
     let elf = read(elf_path)?;
 
-    let public_io = prove::calculate_public_data(&ticks);
+    let public_io = prove::calculate_public_data(&ticks)?;
     let stdin = prove::configure_stdin(public_io.clone());
-    let client = ProverClient::new();
     Ok((elf, stdin, client))
 }
 
-pub fn calculate_public_data(ticks: &[NumberBytes]) -> PublicData {
+/// Relative difference between the narrow and exact variance above which the narrow
+/// accumulation is assumed to have overflowed, far above the rounding of `n1_inv`.
+const OVERFLOW_TOLERANCE: f64 = 1e-6;
+
+pub fn calculate_public_data(ticks: &[NumberBytes]) -> Result<PublicData> {
     let n = Fixed::from_num(ticks.len());
     let n_inv_sqrt = Fixed::ONE / n.sqrt();
     let n1_inv = Fixed::ONE / (n - Fixed::ONE);
-    let mut ticks_prev = Fixed::from_num(i64::from_be_bytes(ticks[0]));
-    let (sum_u, sum_u2) =
-        ticks
-            .iter()
-            .skip(1)
-            .fold((Fixed::ZERO, Fixed::ZERO), |(su, su2), tick| {
-                let ticks_curr = Fixed::from_num(i64::from_be_bytes(*tick));
-                let delta = ticks_curr - ticks_prev;
-                ticks_prev = ticks_curr;
-                (su + delta * n_inv_sqrt, su2 + delta * delta * n1_inv)
-            });
-    let s2 = sum_u2 - (sum_u * sum_u) * n1_inv;
-    println!("Volatility squared {}", s2);
-    PublicData {
+    let ticks = ticks.iter().map(|tick| to_fixed(*tick)).collect::<Vec<_>>();
+    let exact = tick_volatility_exact(&ticks);
+    let breakdown = tick_volatility_detailed(&ticks, n_inv_sqrt, n1_inv);
+    detail!(
+        "Volatility squared {} (n: {}, sum_u: {}, sum_u2: {})",
+        breakdown.s2,
+        breakdown.n,
+        breakdown.sum_u,
+        breakdown.sum_u2
+    );
+    let s2 = breakdown.s2;
+    if diverges(s2, exact) {
+        status!(
+            "Warning: volatility squared {} differs from the exact {}, the I24F40 accumulation likely overflowed",
+            s2, exact
+        );
+    }
+    Ok(PublicData {
         n_inv_sqrt,
         n1_inv,
         s2,
+    })
+}
+
+/// Keccak-256 of the concatenated big-endian ticks, the digest committed by the guest and
+/// what Solidity's `keccak256(abi.encodePacked(ticks))` gives for the ticks as `int64` with
+/// 40 fraction bits, i.e. integer ticks shifted left by 40.
+pub fn ticks_digest(ticks: &[NumberBytes]) -> [u8; 32] {
+    let mut keccak = Keccak::v256();
+    let mut output = [0u8; 32];
+    ticks.iter().for_each(|tick| keccak.update(tick));
+    keccak.finalize(&mut output);
+    output
+}
+
+fn check_digest(digest: [u8; 32], expected: [u8; 32]) -> Result<()> {
+    if digest != expected {
+        bail!(
+            "committed digest 0x{} does not match the keccak256 of the ticks 0x{}",
+            hex(&digest),
+            hex(&expected)
+        );
     }
+    Ok(())
 }
+
+/// Parses a keccak256 digest given as 64 hex characters, with or without the `0x` prefix.
+pub fn parse_digest(hex: &str) -> Result<[u8; 32]> {
+    let hex = hex.trim_start_matches("0x");
+    if hex.len() != 64 || !hex.is_ascii() {
+        bail!("digest must be 32 bytes of hex, got {:?}", hex);
+    }
+    let mut digest = [0u8; 32];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)?;
+    }
+    Ok(digest)
+}
+
+/// Verifies a saved proof of the program `elf_path`, without proving again. A valid proof over
+/// other ticks is rejected when `expected_digest` is given and differs from the committed one.
+pub fn verify_saved_proof(
+    elf_path: &str,
+    proof_path: &str,
+    client: &ProverClient,
+    expected_digest: Option<[u8; 32]>,
+) -> Result<Sp1RvTicksFixture> {
+    let elf = read(elf_path).with_context(|| format!("Failed to read {}", elf_path))?;
+    let (_, vk) = client.setup(&elf);
+    let proof = SP1PlonkBn254Proof::load(proof_path)
+        .with_context(|| format!("Failed to load proof {}", proof_path))?;
+    status!("Verifying...");
+    client.verify_plonk(&proof, &vk)?;
+
+    let (_, _, _, _, digest) =
+        PublicValuesTuple::abi_decode(proof.public_values.as_slice(), false)?;
+    if let Some(expected) = expected_digest {
+        if digest.0 != expected {
+            bail!(
+                "proof commits to digest 0x{}, expected 0x{}",
+                hex(&digest.0),
+                hex(&expected)
+            );
+        }
+    }
+    Sp1RvTicksFixture::from_proof(&proof, &vk)
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn diverges(s2: Fixed, exact: WideFixed) -> bool {
+    let exact = exact.to_num::<f64>();
+    let error = (s2.to_num::<f64>() - exact).abs();
+    error > OVERFLOW_TOLERANCE * exact.abs().max(1.0)
+}
+
 pub fn configure_stdin(public_io: PublicData) -> SP1Stdin {
     let n_inv_sqrt_bytes = Fixed::to_be_bytes(public_io.n_inv_sqrt);
     let n1_inv_bytes = Fixed::to_be_bytes(public_io.n1_inv);
@@ -79,69 +316,139 @@ pub fn configure_stdin(public_io: PublicData) -> SP1Stdin {
     stdin
 }
 
-pub fn prove(elf: &[u8], stdin: SP1Stdin, client: ProverClient) -> Result<()> {
+/// Returns true when a cause of the error is a network or I/O hiccup worth retrying: an I/O
+/// error of a transient kind, or an HTTP request that timed out, failed to connect, or got a
+/// rate limit or server error status. Anything else, such as a malformed key or program, is
+/// deterministic. Causes are classified by their type, never by their message.
+fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(io_error) = cause.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io_error.kind(),
+                ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::WouldBlock
+            );
+        }
+        if let Some(http_error) = cause.downcast_ref::<reqwest::Error>() {
+            return http_error.is_timeout()
+                || http_error.is_connect()
+                || http_error.status().is_some_and(|status| {
+                    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                });
+        }
+        false
+    })
+}
+
+/// Runs `op`, retrying transient failures up to `max_retries` times with exponential backoff.
+pub fn with_retry<T>(max_retries: u32, op: impl FnMut() -> Result<T>) -> Result<T> {
+    retry_with_delay(max_retries, BASE_RETRY_DELAY, op)
+}
+
+/// `with_retry` waiting `base_delay` before the first retry.
+fn retry_with_delay<T>(
+    max_retries: u32,
+    base_delay: Duration,
+    mut op: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(error) if attempt < max_retries && is_transient(&error) => {
+                let delay = base_delay * 2u32.pow(attempt);
+                attempt += 1;
+                status!(
+                    "Attempt {} failed: {}. Retrying in {} seconds...",
+                    attempt,
+                    error,
+                    delay.as_secs()
+                );
+                thread::sleep(delay);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+pub fn prove(
+    elf: &[u8],
+    stdin: SP1Stdin,
+    client: ProverClient,
+    max_retries: u32,
+    expected_digest: [u8; 32],
+) -> Result<Sp1RvTicksFixture> {
     // Calculate  1/(n-1) and the square root of 1/n.
     // These values are used in the volatility proof.
     let (pk, vk) = client.setup(elf);
 
     // Generate proof.
     // let mut proof = client.prove(&pk, stdin).expect("proving failed");
-    println!("Proving...");
+    status!("Proving...");
     let start_time = Instant::now();
-    let mut proof = client.prove_plonk(&pk, stdin)?;
-    println!("Done!");
+    let proof = with_retry(max_retries, || client.prove_plonk(&pk, stdin.clone()))?;
+    status!("Done!");
     let prove_time = Instant::now() - start_time;
-    println!("Prove time: {} seconds", prove_time.as_secs());
-
-    // Read output.
-    let s2 = proof.public_values.read::<NumberBytes>();
-    let n = proof.public_values.read::<NumberBytes>();
-    let digest = proof.public_values.read::<[u8; 32]>();
+    status!("Prove time: {} seconds", prove_time.as_secs());
 
     // Save proof.
-    proof.save("proof-with-io.json")?;
+    proof.save(PROOF_FILE)?;
 
     // Deserialize the public values
-    let bytes = proof.public_values.as_slice();
-    let (n_inv_sqrt, n1_inv, s2, n, digest) = PublicValuesTuple::abi_decode(bytes, false)?;
-    let s2_bytes: NumberBytes = s2.as_slice().try_into()?;
-    let n_inv_sqrt_bytes: NumberBytes = n_inv_sqrt.as_slice().try_into()?;
-    let n_bytes: NumberBytes = n.as_slice().try_into()?;
-    let n1_inv_bytes: NumberBytes = n1_inv.as_slice().try_into()?;
-    let s2_fixed = Fixed::from_be_bytes(s2_bytes);
-    let s = s2_fixed.sqrt();
-    // Create the testing fixture so we can test things end-ot-end.
-    let fixture = Sp1RvTicksFixture {
-        n_inv_sqrt: u64::from_be_bytes(n_inv_sqrt_bytes),
-        n1_inv: u64::from_be_bytes(n1_inv_bytes),
-        s: i64::from_be_bytes(s.to_be_bytes()),
-        s2: i64::from_be_bytes(s2_bytes),
-        n: u64::from_be_bytes(n_bytes),
-        digest: digest.to_string(),
-        vkey: vk.bytes32().to_string(),
-        public_values: proof.public_values.bytes().to_string(),
-        proof: proof.bytes().to_string(),
-    };
+    let (_, _, _, _, digest) =
+        PublicValuesTuple::abi_decode(proof.public_values.as_slice(), false)?;
+    check_digest(digest.0, expected_digest)?;
+    let fixture = Sp1RvTicksFixture::from_proof(&proof, &vk)?;
 
     // Verify proof.
-    println!("Verifying...");
+    status!("Verifying...");
     client.verify_plonk(&proof, &vk)?;
-    println!("Done!");
+    status!("Done!");
 
-    let fixture_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    std::fs::create_dir_all(&fixture_path).expect("failed to create fixture path");
-    std::fs::write(
-        fixture_path.join("fixture.json"),
-        serde_json::to_string_pretty(&fixture).unwrap(),
-    )?;
+    fixture.save()?;
 
-    println!("successfully generated and verified proof for the program!");
-    Ok(())
+    status!("successfully generated and verified proof for the program!");
+    Ok(fixture)
 }
 
-pub fn exec(elf: &[u8], stdin: SP1Stdin, client: ProverClient) -> Result<()> {
-    println!("Execution only.");
-    let (mut public_values, _) = client.execute(elf, stdin)?;
+/// Cycle count breakdown of an execution, the total and every region the guest marks with
+/// `cycle-tracker-report-start`/`end`, followed by SP1's own opcode and syscall report.
+fn profile(report: &ExecutionReport) -> String {
+    let mut profile = format!("Total cycles: {}\n", report.total_instruction_count());
+    let mut regions: Vec<_> = report.cycle_tracker.iter().collect();
+    regions.sort();
+    for (region, cycles) in regions {
+        profile.push_str(&format!("  {}: {} cycles\n", region, cycles));
+    }
+    profile.push_str(&report.to_string());
+    profile
+}
+
+/// Executes the guest without proving, returning the squared volatility it committed.
+/// The cycle count breakdown is printed, and written to `profile_out` when given.
+pub fn exec(
+    elf: &[u8],
+    stdin: SP1Stdin,
+    client: &ProverClient,
+    expected_digest: [u8; 32],
+    profile_out: Option<&str>,
+) -> Result<Fixed> {
+    status!("Executing...");
+    let start_time = Instant::now();
+    let (mut public_values, report) = client.execute(elf, stdin)?;
+    status!("Execution time: {} ms", start_time.elapsed().as_millis());
+
+    let profile = profile(&report);
+    status!("{}", profile);
+    if let Some(path) = profile_out {
+        std::fs::write(path, &profile)?;
+        status!("Profile written to {}", path);
+    }
 
     // Read output.
     let s2 = public_values.read::<NumberBytes>();
@@ -151,12 +458,235 @@ pub fn exec(elf: &[u8], stdin: SP1Stdin, client: ProverClient) -> Result<()> {
     // Deserialize the public values
     let bytes = public_values.as_slice();
     let (n_inv_sqrt, n1_inv, s2, n, digest) = PublicValuesTuple::abi_decode(bytes, false)?;
-    let s2_fixed = Fixed::from_be_bytes(s2.as_slice().try_into()?);
+    check_digest(digest.0, expected_digest)?;
+    let s2_fixed = to_fixed(s2.as_slice().try_into()?);
     println!("Volatility squared: {}", s2_fixed);
     let s = s2_fixed.sqrt();
     // Create the testing fixture so we can test things end-ot-end.
 
     println!("Volatility: {}", s);
 
-    Ok(())
+    Ok(s2_fixed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_elf::{read_ticks, TickSource};
+    use crate::crosscheck::DEFAULT_FIXTURE;
+    use anyhow::anyhow;
+    use sp1_sdk::SP1PublicValues;
+
+    fn timed_out() -> anyhow::Error {
+        std::io::Error::new(ErrorKind::TimedOut, "prover timed out").into()
+    }
+
+    /// Calls `retry_with_delay` with an operation failing with `error` `failures` times.
+    fn retry_failing(
+        max_retries: u32,
+        failures: u32,
+        error: fn() -> anyhow::Error,
+    ) -> (Result<u32>, u32) {
+        let mut calls = 0;
+        let result = retry_with_delay(max_retries, Duration::ZERO, || {
+            calls += 1;
+            if calls <= failures {
+                Err(error())
+            } else {
+                Ok(calls)
+            }
+        });
+        (result, calls)
+    }
+
+    #[test]
+    fn retries_transient_failures_until_success() {
+        let (result, calls) = retry_failing(3, 2, timed_out);
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let (result, calls) = retry_failing(2, 5, timed_out);
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn does_not_retry_deterministic_failures() {
+        let (result, calls) = retry_failing(3, 1, || anyhow!("malformed vkey"));
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn network_flag_selects_the_network_prover() {
+        assert_eq!(ProverMode::from_flag(false), ProverMode::Local);
+        assert_eq!(ProverMode::from_flag(true), ProverMode::Network);
+    }
+
+    #[test]
+    fn network_prover_requires_the_key() {
+        std::env::remove_var(NETWORK_KEY_VAR);
+        let error = ProverMode::Network.client().err().unwrap();
+        assert!(error.to_string().contains(NETWORK_KEY_VAR));
+    }
+
+    #[test]
+    fn classifies_by_type_not_message() {
+        assert!(!is_transient(&anyhow!("connection timed out")));
+        assert!(is_transient(&timed_out().context("Failed to prove")));
+    }
+    /// Proof of the committed program, saved as `PROOF_FILE` by a local proving run of the
+    /// script (`cargo run --release`) and copied here, so that the tamper test does not prove.
+    const PROOF_FIXTURE: &str = "../fixtures/proof-with-io.json";
+
+    #[test]
+    #[ignore = "needs the program ELF and the PROOF_FIXTURE proof, slow as it sets up the keys"]
+    fn tampered_proofs_are_rejected() {
+        let client = ProverClient::new();
+        let (_, vk) = client.setup(&read(crate::ELF_PATH).unwrap());
+        let load = || SP1PlonkBn254Proof::load(PROOF_FIXTURE).unwrap();
+        client.verify_plonk(&load(), &vk).unwrap();
+
+        // s2 is the third bytes8 field, left-aligned in its 32 byte word
+        let mut tampered = load();
+        let mut public_values = tampered.public_values.to_vec();
+        public_values[2 * 32] ^= 1;
+        tampered.public_values = SP1PublicValues::from(&public_values);
+        assert!(client.verify_plonk(&tampered, &vk).is_err());
+
+        let mut tampered = load();
+        let raw_proof = &mut tampered.proof.raw_proof;
+        let middle = raw_proof.len() / 2;
+        let digit = if raw_proof.as_bytes()[middle] == b'0' {
+            "1"
+        } else {
+            "0"
+        };
+        raw_proof.replace_range(middle..middle + 1, digit);
+        assert!(client.verify_plonk(&tampered, &vk).is_err());
+    }
+
+    #[test]
+    #[ignore = "needs the program ELF and the PROOF_FIXTURE proof, slow as it sets up the keys"]
+    fn saved_proofs_round_trip_into_their_fixture() {
+        let client = ProverClient::new();
+        let (_, vk) = client.setup(&read(crate::ELF_PATH).unwrap());
+        let proof = SP1PlonkBn254Proof::load(PROOF_FIXTURE).unwrap();
+
+        let fixture = Sp1RvTicksFixture::from_saved_proof(PROOF_FIXTURE, &vk).unwrap();
+        let json = serde_json::to_string(&fixture).unwrap();
+        let fixture: Sp1RvTicksFixture = serde_json::from_str(&json).unwrap();
+
+        // The public values of the fixture decode back to its fields
+        let hex_values = fixture.public_values.trim_start_matches("0x");
+        let public_values = (0..hex_values.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex_values[i..i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(public_values, proof.public_values.as_slice());
+        let (n_inv_sqrt, n1_inv, s2, n, digest) =
+            PublicValuesTuple::abi_decode(&public_values, false).unwrap();
+        assert_eq!(fixture.n_inv_sqrt.to_be_bytes(), n_inv_sqrt.0);
+        assert_eq!(fixture.n1_inv.to_be_bytes(), n1_inv.0);
+        assert_eq!(fixture.s2.to_be_bytes(), s2.0);
+        assert_eq!(fixture.n.to_be_bytes(), n.0);
+        assert_eq!(fixture.digest, digest.to_string());
+        assert_eq!(
+            Fixed::from_bits(fixture.s),
+            Fixed::from_bits(fixture.s2).sqrt()
+        );
+        assert_eq!(fixture.vkey, vk.bytes32());
+    }
+
+    #[test]
+    fn digest_check_accepts_only_the_matching_digest() {
+        let digest = parse_digest(&format!("0x{}", "ab".repeat(32))).unwrap();
+        let mut other = digest;
+        other[31] ^= 1;
+
+        assert_eq!(digest, [0xab; 32]);
+        assert_eq!(parse_digest(&hex(&digest)).unwrap(), digest);
+        assert!(parse_digest(&"ab".repeat(31)).is_err());
+        assert!(check_digest(digest, digest).is_ok());
+        assert_eq!(
+            check_digest(other, digest).unwrap_err().to_string(),
+            format!(
+                "committed digest 0x{} does not match the keccak256 of the ticks 0x{}",
+                hex(&other),
+                hex(&digest)
+            )
+        );
+    }
+
+    #[test]
+    #[ignore = "needs the program ELF and the PROOF_FIXTURE proof, slow as it sets up the keys"]
+    fn saved_proofs_are_checked_against_the_expected_digest() {
+        let client = ProverClient::new();
+        let proof = SP1PlonkBn254Proof::load(PROOF_FIXTURE).unwrap();
+        let (_, _, _, _, digest) =
+            PublicValuesTuple::abi_decode(proof.public_values.as_slice(), false).unwrap();
+        let mut other = digest.0;
+        other[0] ^= 1;
+
+        let matching = verify_saved_proof(crate::ELF_PATH, PROOF_FIXTURE, &client, Some(digest.0));
+        let mismatching = verify_saved_proof(crate::ELF_PATH, PROOF_FIXTURE, &client, Some(other));
+
+        assert!(matching.is_ok());
+        assert_eq!(
+            mismatching.unwrap_err().to_string(),
+            format!(
+                "proof commits to digest 0x{}, expected 0x{}",
+                hex(&digest.0),
+                hex(&other)
+            )
+        );
+    }
+
+    /// Builds the program over the first 16 ticks of the crosscheck fixture, returning the
+    /// outputs of `setup` and the digest of the ticks.
+    fn setup_small_window() -> (Vec<u8>, SP1Stdin, ProverClient, [u8; 32]) {
+        let ticks = read_ticks(TickSource::Csv(DEFAULT_FIXTURE.to_string()), false).unwrap();
+        let ticks = ticks[..16].to_vec();
+        let digest = ticks_digest(&ticks);
+        let (elf, stdin, client) = setup(crate::ELF_PATH, ticks, ProverClient::new()).unwrap();
+        (elf, stdin, client, digest)
+    }
+
+    #[test]
+    #[ignore = "slow, builds the program and proves"]
+    fn execution_and_proof_share_the_public_values() {
+        let (elf, stdin, client, digest) = setup_small_window();
+        let (pk, _) = client.setup(&elf);
+
+        let s2 = exec(&elf, stdin.clone(), &client, digest, None).unwrap();
+        let (executed, _) = client.execute(&elf, stdin.clone()).unwrap();
+        let proof = client.prove_plonk(&pk, stdin).unwrap();
+
+        assert_eq!(proof.public_values.as_slice(), executed.as_slice());
+        let (_, _, proved_s2, _, proved_digest) =
+            PublicValuesTuple::abi_decode(proof.public_values.as_slice(), false).unwrap();
+        assert_eq!(proved_s2.0, s2.to_be_bytes());
+        assert_eq!(proved_digest.0, digest);
+    }
+
+    #[test]
+    #[ignore = "slow, builds the program"]
+    fn execution_reports_the_cycles_of_every_region() {
+        let (elf, stdin, client, _) = setup_small_window();
+
+        let (_, report) = client.execute(&elf, stdin).unwrap();
+        let profile = profile(&report);
+
+        assert!(report.total_instruction_count() > 0);
+        assert!(profile.starts_with(&format!(
+            "Total cycles: {}\n",
+            report.total_instruction_count()
+        )));
+        for region in ["hash", "volatility"] {
+            assert!(report.cycle_tracker[region] > 0, "no cycles in {}", region);
+        }
+    }
 }
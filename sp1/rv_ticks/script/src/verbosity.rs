@@ -0,0 +1,47 @@
+//! Verbosity of the progress output, set once from `--quiet` and `--verbose`. Errors and the
+//! results of a run are printed at every level, only the status lines are gated.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Only errors and results.
+pub const QUIET: u8 = 0;
+/// Progress, timings and warnings, the default.
+pub const NORMAL: u8 = 1;
+/// Details of the inputs on top of the normal output.
+pub const VERBOSE: u8 = 2;
+
+static LEVEL: AtomicU8 = AtomicU8::new(NORMAL);
+
+/// Level of the `--quiet` flag and the number of `-v` flags.
+pub fn set(quiet: bool, verbose: u8) {
+    let level = if quiet {
+        QUIET
+    } else {
+        NORMAL.saturating_add(verbose)
+    };
+    LEVEL.store(level, Ordering::Relaxed);
+}
+
+pub fn enabled(level: u8) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level
+}
+
+/// `println!` at the normal level.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::verbosity::enabled($crate::verbosity::NORMAL) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `println!` at the verbose level.
+macro_rules! detail {
+    ($($arg:tt)*) => {
+        if $crate::verbosity::enabled($crate::verbosity::VERBOSE) {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {detail, status};
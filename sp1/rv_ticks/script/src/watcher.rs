@@ -1,9 +1,13 @@
-use crate::build_elf::{read_ticks_from_jsonl, NumberBytes};
+use crate::build_elf::{
+    combine_keyed, last_tick_per_block, read_window, read_windows, NumberBytes,
+};
 use crate::prove;
-use anyhow::Result;
+use crate::verbosity::status;
+use anyhow::{anyhow, bail, Result};
 use regex::Regex;
 use std::cmp::Reverse;
 use std::fs;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
 // Given a the path to a directory:
@@ -15,19 +19,36 @@ pub fn watch_directory(
     path: &str,
     latest_block: u64,
     exec_flag: bool,
+    max_retries: u32,
+    mode: prove::ProverMode,
+    max_files: Option<NonZeroUsize>,
+    per_block: bool,
 ) -> Result<u64> {
-    let (ticks, latest_block) = match read_latest_ticks(path, latest_block) {
+    let (ticks, latest_block) = match read_latest_ticks(path, latest_block, max_files, per_block) {
         Ok(ticks) => ticks,
         Err(error) => return Err(error),
     };
-    let (elf, stdin, client) = prove::setup(elf_path, ticks)?;
+    prove_ticks(elf_path, ticks, exec_flag, max_retries, mode)?;
+
+    Ok(latest_block)
+}
+
+/// Executes, or proves, the guest over the ticks of one window.
+pub fn prove_ticks(
+    elf_path: &str,
+    ticks: Vec<NumberBytes>,
+    exec_flag: bool,
+    max_retries: u32,
+    mode: prove::ProverMode,
+) -> Result<()> {
+    let digest = prove::ticks_digest(&ticks);
+    let (elf, stdin, client) = prove::setup(elf_path, ticks, mode.client()?)?;
     if exec_flag {
-        prove::exec(elf.as_slice(), stdin, client)?;
+        prove::exec(elf.as_slice(), stdin, &client, digest, None)?;
     } else {
-        prove::prove(elf.as_slice(), stdin, client)?;
+        prove::prove(elf.as_slice(), stdin, client, max_retries, digest)?;
     }
-
-    Ok(latest_block)
+    Ok(())
 }
 
 // A function to parse the .jsonl files output by the realized_volatility_substream.
@@ -46,33 +67,109 @@ fn parse_filename(filename: &str) -> Result<(u64, u64)> {
     }
 }
 
-fn read_latest_ticks(directory: &str, latest_block: u64) -> Result<(Vec<NumberBytes>, u64)> {
-    let mut files: Vec<PathBuf> = fs::read_dir(directory)?
-        .filter_map(Result::ok)
-        .map(|entry| entry.path())
-        .filter(|path| path.is_file())
+// Lists the files of the directory named by their block range, latest first. Files that can't
+// be listed or don't match the substream naming are skipped with a warning.
+fn list_block_files(directory: &str) -> Result<Vec<(PathBuf, u64, u64)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(error) => {
+                status!("Warning: skipping unreadable directory entry: {}", error);
+                continue;
+            }
+        };
+        if !path.is_file() {
+            continue;
+        }
+        let range = path
+            .to_str()
+            .ok_or_else(|| anyhow!("non UTF-8 file name"))
+            .and_then(parse_filename);
+        match range {
+            Ok((start_block, end_block)) => files.push((path, start_block, end_block)),
+            Err(error) => status!("Warning: skipping {}: {}", path.display(), error),
+        }
+    }
+    files.sort_by_key(|(_, _, end_block)| Reverse(*end_block));
+    Ok(files)
+}
+
+/// Reads the ticks of the swaps in blocks `start` to `end` inclusive, from the files whose block
+/// range overlaps it. Unlike the latest window, a file that fails to read fails the selection.
+pub fn read_block_range(
+    directory: &str,
+    (start, end): (u64, u64),
+    per_block: bool,
+) -> Result<Vec<NumberBytes>> {
+    if start > end {
+        bail!("Empty block range {}-{}", start, end);
+    }
+    let files: Vec<PathBuf> = list_block_files(directory)?
+        .into_iter()
+        .filter(|(_, start_block, end_block)| *end_block >= start && *start_block <= end)
+        .map(|(file, _, _)| file)
         .collect();
+    let mut ticks = combine_keyed(read_windows(&files)?);
+    ticks.retain(|(block, _), _| (start..=end).contains(block));
+    if ticks.is_empty() {
+        bail!("No swaps in blocks {}-{} of {}", start, end, directory);
+    }
+    status!("Read {} swaps in blocks {}-{}", ticks.len(), start, end);
+    if per_block {
+        return Ok(last_tick_per_block(ticks));
+    }
+    Ok(ticks.into_values().collect())
+}
 
-    files.sort_by_key(|name| {
-        let (_, end_block) = parse_filename(name.to_str().expect("bad file name")).unwrap();
-        Reverse(end_block)
-    });
-    let (_, new_latest_block) = parse_filename(files[0].to_str().expect("bad file name"))?;
+// Reads at most `max_files` files when given, even if they cover fewer than 8192 blocks, in which
+// case the short window is reported and left to the tick validation of the build. Files that fail
+// to read are skipped, it only fails when none could be read.
+fn read_latest_ticks(
+    directory: &str,
+    latest_block: u64,
+    max_files: Option<NonZeroUsize>,
+    per_block: bool,
+) -> Result<(Vec<NumberBytes>, u64)> {
+    let files = list_block_files(directory)?;
+    let Some(&(_, _, new_latest_block)) = files.first() else {
+        bail!("No block files in {}", directory);
+    };
     if new_latest_block <= latest_block {
         return Err(anyhow::anyhow!("No new blocks"));
     }
-    println!("Latest block: {}", new_latest_block);
-    let mut ticks: Vec<NumberBytes> = Vec::new();
-    for file in files {
-        let (start_block, _) = parse_filename(file.to_str().expect("bad file name"))?;
-        let file = std::fs::File::open(file).expect("Could not open file");
-        let mut reader = std::io::BufReader::new(file);
-        let new_ticks = read_ticks_from_jsonl(&mut reader)?;
-        ticks.extend(new_ticks.into_iter());
+    status!("Latest block: {}", new_latest_block);
+    let mut windows = Vec::new();
+    for (file, start_block, _) in files {
+        match read_window(&file) {
+            Ok(window) => windows.push(window),
+            Err(error) => {
+                status!("Warning: skipping {}: {:#}", file.display(), error);
+                continue;
+            }
+        }
+        let read = windows.len();
         let num_blocks = new_latest_block - start_block;
         if num_blocks >= 8192 {
             break;
         };
+        if max_files.is_some_and(|max_files| read >= max_files.get()) {
+            status!(
+                "Warning: stopped after {} files covering {} blocks, the window is short",
+                read,
+                num_blocks
+            );
+            break;
+        }
+    }
+    if windows.is_empty() {
+        bail!("None of the block files in {} could be read", directory);
     }
+    let ticks = combine_keyed(windows);
+    let ticks = if per_block {
+        last_tick_per_block(ticks)
+    } else {
+        ticks.into_values().collect()
+    };
     Ok((ticks, new_latest_block))
 }
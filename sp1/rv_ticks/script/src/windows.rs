@@ -0,0 +1,132 @@
+//! Proofs of several window lengths over the most recent ticks of one source, e.g. to build a
+//! volatility term structure in one run.
+
+use crate::build_elf::{validate_ticks, NumberBytes};
+use crate::prove::{self, ProverMode, RunSummary, PROOF_FILE};
+use crate::verbosity::status;
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::fmt;
+use std::time::Instant;
+
+/// Proof of the most recent `size` ticks. The guest bakes its ticks into the ELF, so every
+/// window pays for a rebuild, reported apart from the proving time.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowProof {
+    pub size: usize,
+    pub build_ms: u64,
+    pub proof_path: String,
+    #[serde(flatten)]
+    pub summary: RunSummary,
+}
+
+impl fmt::Display for WindowProof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Window {}: volatility {}, build {} ms, prove {} ms, proof {}",
+            self.size,
+            self.summary.std_dev,
+            self.build_ms,
+            self.summary.prove_ms.unwrap_or_default(),
+            self.proof_path
+        )
+    }
+}
+
+/// Proof file of the window of `size` ticks.
+pub fn window_proof_file(size: usize) -> String {
+    format!("proof-with-io-{}.json", size)
+}
+
+/// Proves the most recent `size` ticks for every size, in the given order, saving each proof
+/// to its `window_proof_file`. Fails before building anything if a size is not provable.
+pub fn prove_windows(
+    elf_path: &str,
+    ticks: &[NumberBytes],
+    sizes: &[usize],
+    mode: ProverMode,
+    max_retries: u32,
+) -> Result<Vec<WindowProof>> {
+    for &size in sizes {
+        if size > ticks.len() {
+            bail!(
+                "Window of {} ticks but only {} were read",
+                size,
+                ticks.len()
+            );
+        }
+        validate_ticks(&ticks[ticks.len() - size..])
+            .with_context(|| format!("Invalid window of {} ticks", size))?;
+    }
+
+    let mut proofs = Vec::with_capacity(sizes.len());
+    for &size in sizes {
+        let window = ticks[ticks.len() - size..].to_vec();
+        let digest = prove::ticks_digest(&window);
+
+        let start_time = Instant::now();
+        let (elf, stdin, client) = prove::setup(elf_path, window, mode.client()?)?;
+        let build_ms = start_time.elapsed().as_millis() as u64;
+        status!("Window {}: ELF rebuilt in {} ms", size, build_ms);
+
+        let start_time = Instant::now();
+        let fixture = prove::prove(elf.as_slice(), stdin, client, max_retries, digest)?;
+        let prove_ms = start_time.elapsed().as_millis() as u64;
+
+        let proof_path = window_proof_file(size);
+        std::fs::rename(PROOF_FILE, &proof_path)
+            .with_context(|| format!("Failed to move the proof to {}", proof_path))?;
+        proofs.push(WindowProof {
+            size,
+            build_ms,
+            proof_path,
+            summary: RunSummary {
+                prove_ms: Some(prove_ms),
+                ..RunSummary::proved(size, digest, fixture)
+            },
+        });
+    }
+    Ok(proofs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_elf::{read_ticks, TickSource};
+    use crate::common::{encode_tick, Fixed};
+    use crate::crosscheck::DEFAULT_FIXTURE;
+
+    #[test]
+    fn windows_larger_than_the_ticks_are_rejected_before_building() {
+        let ticks = [1, 2, 3].map(|tick| encode_tick(tick).unwrap());
+
+        let error = prove_windows("unused", &ticks, &[2, 4], ProverMode::Local, 0).unwrap_err();
+
+        assert_eq!(error.to_string(), "Window of 4 ticks but only 3 were read");
+    }
+
+    #[test]
+    #[ignore = "slow, builds the program and proves every window"]
+    fn every_window_gets_its_own_proof() {
+        let ticks = read_ticks(TickSource::Csv(DEFAULT_FIXTURE.to_string()), false).unwrap();
+
+        let proofs =
+            prove_windows(crate::ELF_PATH, &ticks, &[16, 64], ProverMode::Local, 0).unwrap();
+        for proof in &proofs {
+            std::fs::remove_file(&proof.proof_path).unwrap();
+        }
+
+        assert_eq!(proofs.len(), 2);
+        for (proof, size) in proofs.iter().zip([16, 64]) {
+            assert_eq!(proof.size, size);
+            assert_eq!(proof.proof_path, window_proof_file(size));
+            let digest = prove::ticks_digest(&ticks[ticks.len() - size..]);
+            assert_eq!(proof.summary.digest, format!("0x{}", prove::hex(&digest)));
+            let fixture = serde_json::to_value(&proof.summary.fixture).unwrap();
+            let n = Fixed::from_bits(fixture["n"].as_i64().unwrap());
+            assert_eq!(n, Fixed::from_num(size));
+        }
+    }
+}